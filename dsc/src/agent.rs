@@ -0,0 +1,328 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use crate::args::{AgentMode, AgentSubCommand};
+use crate::util::{EXIT_CTRL_C, EXIT_DSC_ERROR, EXIT_INVALID_ARGS, EXIT_JSON_ERROR, exit, set_dscconfigroot, write_output};
+use dsc_lib::audit::get_document_hash;
+use dsc_lib::configure::{Configurator, config_doc::Operation, config_result::ResourceTestResult};
+use dsc_lib::dscerror::DscError;
+use dsc_lib::dscresources::invoke_result::TestResult;
+use dsc_lib::settings::{self, get_settings, get_user_state_directory, DiscoverySettings};
+use dsc_lib::util::parse_input_to_json;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::fs::{self, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{atomic::{AtomicBool, Ordering}, Mutex};
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessRefreshKind, RefreshKind, System};
+use tracing::{debug, error, info, warn};
+
+pub const DEFAULT_INTERVAL_SECONDS: u64 = 1800;
+const LOCK_FILE_NAME: &str = "agent.lock";
+const STATE_FILE_NAME: &str = "agent.state.json";
+
+// the lock is released by the Ctrl-C handler as the process exits without unwinding
+static AGENT_LOCK: Mutex<Option<PathBuf>> = Mutex::new(None);
+// set by the Ctrl-C handler so the agent stops between runs instead of in the middle of one
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+struct AgentOptions {
+    document_path: String,
+    parameters_path: Option<String>,
+    mode: settings::AgentMode,
+    interval: Duration,
+    jitter: Duration,
+    state_directory: PathBuf,
+    discovery_settings: DiscoverySettings,
+}
+
+// the state kept between runs so an unchanged document isn't applied again
+#[derive(Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentState {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    applied_hash: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum AgentAction {
+    /// The document was applied as it wasn't applied before or changed.
+    Applied,
+    /// The instances were tested for drift.
+    Monitored,
+    /// Drift was found and corrected.
+    Corrected,
+    /// Nothing was done as the document is applied and the mode is `ApplyOnly`.
+    None,
+}
+
+/// The report of a run of the agent, written as a line to the output.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentRunReport {
+    timestamp: String,
+    mode: settings::AgentMode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<AgentAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    in_desired_state: Option<bool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    drifted_resources: Vec<String>,
+    had_errors: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Run the agent in the foreground, applying the configured document and testing it periodically.
+///
+/// The options of the command line take precedence over the `agent` settings.
+pub fn run(subcommand: &AgentSubCommand, discovery_settings: &DiscoverySettings) {
+    let AgentSubCommand::Run { path, parameters_file, mode, interval, jitter, once, format } = subcommand;
+    let agent_settings = match get_settings() {
+        Ok(settings) => settings.agent.unwrap_or_default(),
+        Err(err) => {
+            error!("Error: {err}");
+            exit(EXIT_DSC_ERROR);
+        }
+    };
+    let Some(document_path) = path.clone().or(agent_settings.document_path) else {
+        error!("Error: No configuration document specified with --path or the `agent.documentPath` setting");
+        exit(EXIT_INVALID_ARGS);
+    };
+    let Some(state_directory) = agent_settings.state_path.map(PathBuf::from).or_else(|| get_user_state_directory().map(|directory| directory.join("agent"))) else {
+        error!("Error: Can't locate the state directory of the agent");
+        exit(EXIT_DSC_ERROR);
+    };
+    let options = AgentOptions {
+        document_path: set_dscconfigroot(&document_path),
+        parameters_path: parameters_file.clone().or(agent_settings.parameters_path),
+        mode: mode.map_or(agent_settings.mode.unwrap_or_default(), get_agent_mode),
+        interval: Duration::from_secs(interval.or(agent_settings.interval_seconds).unwrap_or(DEFAULT_INTERVAL_SECONDS)),
+        jitter: Duration::from_secs(jitter.or(agent_settings.jitter_seconds).unwrap_or_default()),
+        state_directory,
+        discovery_settings: discovery_settings.clone(),
+    };
+    if options.interval.is_zero() {
+        error!("Error: The interval of the agent must be at least 1 second");
+        exit(EXIT_INVALID_ARGS);
+    }
+
+    if let Err(err) = acquire_lock(&options.state_directory) {
+        error!("Error: {err}");
+        exit(EXIT_DSC_ERROR);
+    }
+    info!("Agent running '{}' in {:?} mode", options.document_path, options.mode);
+    loop {
+        let report = run_once(&options);
+        let had_errors = report.had_errors;
+        let json = match serde_json::to_string(&report) {
+            Ok(json) => json,
+            Err(err) => {
+                error!("JSON Error: {err}");
+                release_lock();
+                exit(EXIT_JSON_ERROR);
+            }
+        };
+        write_output(&json, format);
+        if *once {
+            release_lock();
+            exit(if had_errors { EXIT_DSC_ERROR } else { 0 });
+        }
+
+        let delay = options.interval + get_jitter(options.jitter);
+        debug!("Next run in {} seconds", delay.as_secs());
+        if !wait_for_next_run(delay) {
+            info!("Agent stopped");
+            release_lock();
+            exit(EXIT_CTRL_C);
+        }
+    }
+}
+
+// the delay is slept in steps so a stop requested during a run or the delay isn't held up, returns false on stop
+fn wait_for_next_run(delay: Duration) -> bool {
+    let next_run = Instant::now() + delay;
+    while !STOP_REQUESTED.load(Ordering::SeqCst) {
+        let remaining = next_run.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return true;
+        }
+        std::thread::sleep(remaining.min(Duration::from_millis(200)));
+    }
+    false
+}
+
+fn get_agent_mode(mode: AgentMode) -> settings::AgentMode {
+    match mode {
+        AgentMode::ApplyOnly => settings::AgentMode::ApplyOnly,
+        AgentMode::ApplyAndMonitor => settings::AgentMode::ApplyAndMonitor,
+        AgentMode::ApplyAndAutoCorrect => settings::AgentMode::ApplyAndAutoCorrect,
+    }
+}
+
+fn get_jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    // the keys of `RandomState` are random, which is enough to spread the runs of many machines
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_millis(random % u64::try_from(max.as_millis()).unwrap_or(u64::MAX))
+}
+
+fn run_once(options: &AgentOptions) -> AgentRunReport {
+    let mut report = AgentRunReport {
+        timestamp: chrono::Local::now().to_rfc3339(),
+        mode: options.mode,
+        action: None,
+        in_desired_state: None,
+        drifted_resources: Vec::new(),
+        had_errors: false,
+        error: None,
+    };
+    if let Err(err) = invoke_run(options, &mut report) {
+        error!("Error: {err}");
+        report.had_errors = true;
+        report.error = Some(err.to_string());
+    }
+    report
+}
+
+// the document and parameters are read on every run so changes to them are applied
+fn invoke_run(options: &AgentOptions, report: &mut AgentRunReport) -> Result<(), DscError> {
+    let document = parse_input_to_json(&fs::read_to_string(&options.document_path)?)?;
+    let parameters = match &options.parameters_path {
+        Some(path) => Some(parse_input_to_json(&fs::read_to_string(path)?)?),
+        None => None,
+    };
+    let hash = get_document_hash(&format!("{document}\n{}", parameters.clone().unwrap_or_default()));
+    let mut configurator = Configurator::with_discovery_settings(&document, options.discovery_settings.clone())?;
+    configurator.set_parameters(&parameters.map(|parameters| serde_json::from_str(&parameters)).transpose()?)?;
+
+    let mut state = read_state(&options.state_directory);
+    if state.applied_hash.as_ref() != Some(&hash) {
+        info!("Applying configuration document");
+        report.action = Some(AgentAction::Applied);
+        // an apply interrupted by a failure or a reboot continues where it stopped
+        configurator.enable_checkpoints(true)?;
+        let result = configurator.invoke_set(false)?;
+        record_history(&configurator, Operation::Set, &result);
+        report.had_errors = result.had_errors;
+        // a document applied with errors is applied again on the next run
+        if !result.had_errors {
+            state.applied_hash = Some(hash);
+            write_state(&options.state_directory, &state)?;
+        }
+        return Ok(());
+    }
+
+    if options.mode == settings::AgentMode::ApplyOnly {
+        debug!("Configuration document already applied");
+        report.action = Some(AgentAction::None);
+        return Ok(());
+    }
+
+    report.action = Some(AgentAction::Monitored);
+    let result = configurator.invoke_test()?;
+    record_history(&configurator, Operation::Test, &result);
+    report.had_errors = result.had_errors;
+    add_drifted_resources("", &result.results, &mut report.drifted_resources);
+    report.in_desired_state = Some(report.drifted_resources.is_empty());
+    if report.drifted_resources.is_empty() {
+        return Ok(());
+    }
+    warn!("Drift detected for resources: {}", report.drifted_resources.join(", "));
+
+    if options.mode == settings::AgentMode::ApplyAndAutoCorrect {
+        info!("Correcting drift");
+        report.action = Some(AgentAction::Corrected);
+        let result = configurator.invoke_set(false)?;
+        record_history(&configurator, Operation::Set, &result);
+        report.had_errors |= result.had_errors;
+    }
+    Ok(())
+}
+
+// the instances of groups are reported with the name of the group as prefix
+fn add_drifted_resources(prefix: &str, results: &[ResourceTestResult], drifted: &mut Vec<String>) {
+    for result in results {
+        match &result.result {
+            TestResult::Resource(response) if !response.in_desired_state => drifted.push(format!("{prefix}{}", result.name)),
+            TestResult::Resource(_) => {},
+            TestResult::Group(group) => add_drifted_resources(&format!("{prefix}{}/", result.name), group, drifted),
+        }
+    }
+}
+
+fn record_history<T: Serialize>(configurator: &Configurator, operation: Operation, result: &T) {
+    if let Err(err) = configurator.record_history(operation, result) {
+        warn!("Failed to record run in history: {err}");
+    }
+}
+
+fn read_state(directory: &Path) -> AgentState {
+    fs::read_to_string(directory.join(STATE_FILE_NAME)).ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn write_state(directory: &Path, state: &AgentState) -> Result<(), DscError> {
+    fs::write(directory.join(STATE_FILE_NAME), serde_json::to_string(state)?)?;
+    Ok(())
+}
+
+// the lock holds the process id of the agent so the lock of an agent that was killed can be taken over
+fn acquire_lock(directory: &Path) -> Result<(), DscError> {
+    fs::create_dir_all(directory)?;
+    let path = directory.join(LOCK_FILE_NAME);
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(std::process::id().to_string().as_bytes())?;
+                debug!("Acquired agent lock {path:?}");
+                *AGENT_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner) = Some(path);
+                return Ok(());
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                let pid = fs::read_to_string(&path)?.trim().parse::<usize>().ok();
+                if let Some(pid) = pid.filter(|pid| is_process_running(*pid)) {
+                    return Err(DscError::Operation(format!("Agent is already running as process {pid}, lock {path:?}")));
+                }
+                warn!("Removing stale agent lock {path:?}");
+                fs::remove_file(&path)?;
+            },
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+fn is_process_running(pid: usize) -> bool {
+    let sys = System::new_with_specifics(RefreshKind::new().with_processes(ProcessRefreshKind::new()));
+    sys.process(Pid::from(pid)).is_some()
+}
+
+/// Request the agent running in this process to stop once its current run completes.
+///
+/// # Returns
+///
+/// `false` if no agent is running or a stop was already requested, then the caller terminates the process.
+pub fn request_stop() -> bool {
+    if AGENT_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner).is_none() {
+        return false;
+    }
+    !STOP_REQUESTED.swap(true, Ordering::SeqCst)
+}
+
+/// Release the lock of the agent if it is running in this process.
+pub fn release_lock() {
+    let path = AGENT_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner).take();
+    if let Some(path) = path {
+        debug!("Releasing agent lock {path:?}");
+        if let Err(err) = fs::remove_file(&path) {
+            warn!("Failed to remove agent lock {path:?}: {err}");
+        }
+    }
+}
@@ -37,10 +37,25 @@ pub struct Args {
     pub trace_level: Option<TraceLevel>,
     #[clap(short = 'f', long, help = "Trace format to use", value_enum, default_value = "default")]
     pub trace_format: TraceFormat,
+    #[clap(long, help = "Directory to search for resources before PATH, can be specified multiple times")]
+    pub search_path: Option<Vec<String>>,
+    #[clap(long, help = "Search subdirectories of the search paths and DSC_RESOURCE_PATH")]
+    pub recurse: bool,
+    #[clap(long, help = "Maximum depth of subdirectories to search", requires = "recurse")]
+    pub max_depth: Option<usize>,
+    #[clap(long, help = "Wildcard pattern of files and directories to skip when searching for resources, can be specified multiple times")]
+    pub exclude: Option<Vec<String>>,
+    #[clap(long, help = "Don't search the directories of PATH for resources")]
+    pub no_path: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, Subcommand)]
 pub enum SubCommand {
+    #[clap(name = "agent", about = "Keep a configuration document applied")]
+    Agent {
+        #[clap(subcommand)]
+        subcommand: AgentSubCommand,
+    },
     #[clap(name = "completer", about = "Generate a shell completion script")]
     Completer {
         /// The shell to generate a completion script for
@@ -61,6 +76,11 @@ pub enum SubCommand {
         #[clap(long, hide = true)]
         as_include: bool,
     },
+    #[clap(name = "history", about = "List, show and compare the results of previous configuration runs")]
+    History {
+        #[clap(subcommand)]
+        subcommand: HistorySubCommand,
+    },
     #[clap(name = "resource", about = "Invoke a specific DSC resource")]
     Resource {
         #[clap(subcommand)]
@@ -75,6 +95,27 @@ pub enum SubCommand {
     },
 }
 
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum AgentSubCommand {
+    #[clap(name = "run", about = "Run the agent in the foreground, applying the document and testing it periodically")]
+    Run {
+        #[clap(short = 'p', long, help = "The path to the configuration document, defaults to the `agent.documentPath` setting")]
+        path: Option<String>,
+        #[clap(long, help = "The path to a JSON or YAML file with the parameters of the document")]
+        parameters_file: Option<String>,
+        #[clap(short = 'm', long, help = "What the agent does after the document is applied, defaults to apply-and-monitor")]
+        mode: Option<AgentMode>,
+        #[clap(long, help = "The seconds between runs, at least 1 and defaults to 1800")]
+        interval: Option<u64>,
+        #[clap(long, help = "The maximum of the random seconds added to the interval")]
+        jitter: Option<u64>,
+        #[clap(long, help = "Run once and exit instead of running periodically")]
+        once: bool,
+        #[clap(short = 'f', long, help = "The output format to use for the report of each run")]
+        format: Option<OutputFormat>,
+    },
+}
+
 #[derive(Debug, PartialEq, Eq, Subcommand)]
 pub enum ConfigSubCommand {
     #[clap(name = "get", about = "Retrieve the current configuration")]
@@ -96,6 +137,34 @@ pub enum ConfigSubCommand {
         format: Option<OutputFormat>,
         #[clap(short = 'w', long, help = "Run as a what-if operation instead of executing the configuration or resource")]
         what_if: bool,
+        #[clap(long, help = "Continue an interrupted run of the same document and parameters from its checkpoint", conflicts_with_all = ["restart", "what_if"])]
+        resume: bool,
+        #[clap(long, help = "Discard the checkpoint of an interrupted run and start from the beginning", conflicts_with = "what_if")]
+        restart: bool,
+        #[clap(long, help = "What to do when a resource requires a restart to complete its changes, defaults to continue")]
+        restart_policy: Option<RestartPolicy>,
+    },
+    #[clap(name = "plan", about = "Capture the changes set would make so they can be reviewed and applied")]
+    Plan {
+        #[clap(short = 'd', long, help = "The document to pass to the configuration or resource", conflicts_with = "path")]
+        document: Option<String>,
+        #[clap(short = 'p', long, help = "The path to a file used as input to the configuration or resource", conflicts_with = "document")]
+        path: Option<String>,
+        #[clap(short = 'o', long, help = "The path of the file the plan is written to instead of the output")]
+        output: Option<String>,
+        #[clap(short = 'f', long, help = "The output format to use")]
+        format: Option<OutputFormat>,
+    },
+    #[clap(name = "apply", about = "Apply the changes of a plan if the state didn't change since it was created", arg_required_else_help = true)]
+    Apply {
+        /// The path of the plan created by `config plan`
+        plan: String,
+        #[clap(long, help = "What to do when the state changed since the plan was created, defaults to refuse")]
+        on_drift: Option<DriftAction>,
+        #[clap(long, help = "What to do when a resource requires a restart to complete its changes, defaults to continue")]
+        restart_policy: Option<RestartPolicy>,
+        #[clap(short = 'f', long, help = "The output format to use")]
+        format: Option<OutputFormat>,
     },
     #[clap(name = "test", about = "Test the current configuration")]
     Test {
@@ -137,6 +206,31 @@ pub enum ConfigSubCommand {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum HistorySubCommand {
+    #[clap(name = "list", about = "List the recorded configuration runs, oldest first")]
+    List {
+        #[clap(short = 'f', long, help = "The output format to use")]
+        format: Option<OutputFormat>,
+    },
+    #[clap(name = "show", about = "Show the result of a configuration run", arg_required_else_help = true)]
+    Show {
+        /// The identifier of the run, a unique prefix of it, or `latest`
+        id: String,
+        #[clap(short = 'f', long, help = "The output format to use")]
+        format: Option<OutputFormat>,
+    },
+    #[clap(name = "diff", about = "Compare the instances of two configuration runs", arg_required_else_help = true)]
+    Diff {
+        /// The identifier of the earlier run, a unique prefix of it, or `latest`
+        from: String,
+        /// The identifier of the later run, a unique prefix of it, or `latest`
+        to: String,
+        #[clap(short = 'f', long, help = "The output format to use")]
+        format: Option<OutputFormat>,
+    },
+}
+
 #[derive(Debug, PartialEq, Eq, Subcommand)]
 pub enum ResourceSubCommand {
     #[clap(name = "list", about = "List or find resources")]
@@ -150,6 +244,10 @@ pub enum ResourceSubCommand {
         description: Option<String>,
         #[clap(short, long, help = "Tag to search for in the resource tags")]
         tags: Option<Vec<String>>,
+        #[clap(long, help = "Report invalid manifests, duplicate resources, failing adapters, and the search path instead of the resources", conflicts_with_all = ["description", "tags"])]
+        diagnostics: bool,
+        #[clap(long, help = "List installed resource packages instead of the resources", conflicts_with_all = ["adapter_name", "description", "tags", "diagnostics"])]
+        installed: bool,
         #[clap(short = 'f', long, help = "The output format to use")]
         format: Option<OutputFormat>,
     },
@@ -211,6 +309,70 @@ pub enum ResourceSubCommand {
         #[clap(short = 'f', long, help = "The output format to use")]
         format: Option<OutputFormat>,
     },
+    #[clap(name = "lint", about = "Check resource manifests for errors", arg_required_else_help = true)]
+    Lint {
+        /// The path to a resource manifest or a directory containing resource manifests
+        path: String,
+        #[clap(short = 'f', long, help = "The output format to use")]
+        format: Option<OutputFormat>,
+    },
+    #[clap(name = "install", about = "Install a resource package from a local archive or directory", arg_required_else_help = true)]
+    Install {
+        /// The path to a .zip, .tar, .tar.gz, or .tgz package or a package directory
+        path: String,
+        #[clap(short, long, help = "The package store to install into", value_enum, default_value = "user")]
+        scope: PackageScope,
+        #[clap(long, help = "Replace the version of the package if it is already installed")]
+        force: bool,
+        #[clap(short = 'f', long, help = "The output format to use")]
+        format: Option<OutputFormat>,
+    },
+    #[clap(name = "uninstall", about = "Uninstall an installed resource package", arg_required_else_help = true)]
+    Uninstall {
+        /// The name of the package
+        name: String,
+        #[clap(short, long, help = "The version to uninstall, all versions if not specified")]
+        version: Option<String>,
+        #[clap(short, long, help = "The package store to uninstall from", value_enum, default_value = "user")]
+        scope: PackageScope,
+        #[clap(short = 'f', long, help = "The output format to use")]
+        format: Option<OutputFormat>,
+    },
+    #[clap(name = "sign", about = "Sign a resource manifest and its executables", arg_required_else_help = true)]
+    Sign {
+        /// The path to the resource manifest, the signature is written next to it
+        path: String,
+        #[clap(short, long, help = "The path to a file containing the base64 encoded ed25519 private key")]
+        key: String,
+        #[clap(short = 'f', long, help = "The output format to use")]
+        format: Option<OutputFormat>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AgentMode {
+    ApplyOnly,
+    ApplyAndMonitor,
+    ApplyAndAutoCorrect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DriftAction {
+    Refuse,
+    Report,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RestartPolicy {
+    Continue,
+    Stop,
+    Exit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PackageScope {
+    User,
+    System,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
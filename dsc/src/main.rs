@@ -6,15 +6,16 @@ use atty::Stream;
 use clap::{CommandFactory, Parser};
 use clap_complete::generate;
 use std::io::{self, Read};
-use std::process::exit;
 use sysinfo::{Process, RefreshKind, System, get_current_pid, ProcessRefreshKind};
 use tracing::{error, info, warn, debug};
+use util::exit;
 
 #[cfg(debug_assertions)]
 use crossterm::event;
 #[cfg(debug_assertions)]
 use std::env;
 
+pub mod agent;
 pub mod args;
 pub mod resolve;
 pub mod resource_command;
@@ -36,6 +37,7 @@ fn main() {
     let args = Args::parse();
 
     util::enable_tracing(&args.trace_level, &args.trace_format);
+    let discovery_settings = util::get_discovery_settings(&args);
 
     debug!("Running dsc {}", env!("CARGO_PKG_VERSION"));
 
@@ -64,6 +66,9 @@ fn main() {
     };
 
     match args.subcommand {
+        SubCommand::Agent { subcommand } => {
+            agent::run(&subcommand, &discovery_settings);
+        },
         SubCommand::Completer { shell } => {
             info!("Generating completion script for {:?}", shell);
             let mut cmd = Args::command();
@@ -73,7 +78,7 @@ fn main() {
             if let Some(file_name) = parameters_file {
                 info!("Reading parameters from file {file_name}");
                 match std::fs::read_to_string(&file_name) {
-                    Ok(parameters) => subcommand::config(&subcommand, &Some(parameters), &input, &as_group, &as_include),
+                    Ok(parameters) => subcommand::config(&subcommand, &Some(parameters), &input, &as_group, &as_include, &discovery_settings),
                     Err(err) => {
                         error!("Error: Failed to read parameters file '{file_name}': {err}");
                         exit(util::EXIT_INVALID_INPUT);
@@ -81,11 +86,14 @@ fn main() {
                 }
             }
             else {
-                subcommand::config(&subcommand, &parameters, &input, &as_group, &as_include);
+                subcommand::config(&subcommand, &parameters, &input, &as_group, &as_include, &discovery_settings);
             }
         },
+        SubCommand::History { subcommand } => {
+            subcommand::history(&subcommand);
+        },
         SubCommand::Resource { subcommand } => {
-            subcommand::resource(&subcommand, &input);
+            subcommand::resource(&subcommand, &input, &discovery_settings);
         },
         SubCommand::Schema { dsc_type , format } => {
             let schema = util::get_schema(dsc_type);
@@ -105,22 +113,29 @@ fn main() {
 
 fn ctrlc_handler() {
     warn!("Ctrl-C received");
+    // a running agent isn't stopped in the middle of applying a document, a second Ctrl-C terminates it
+    if agent::request_stop() {
+        warn!("Agent stops after the current run, press Ctrl-C again to terminate it");
+        return;
+    }
+    // the current process is terminated along with its subprocesses so the lock is released first
+    agent::release_lock();
 
     // get process tree for current process and terminate all processes
     let sys = System::new_with_specifics(RefreshKind::new().with_processes(ProcessRefreshKind::new()));
     info!("Found {} processes", sys.processes().len());
     let Ok(current_pid) = get_current_pid() else {
         error!("Could not get current process id");
-        exit(util::EXIT_CTRL_C);
+        std::process::exit(util::EXIT_CTRL_C);
     };
     info!("Current process id: {}", current_pid);
     let Some(current_process) = sys.process(current_pid) else {
         error!("Could not get current process");
-        exit(util::EXIT_CTRL_C);
+        std::process::exit(util::EXIT_CTRL_C);
     };
 
     terminate_subprocesses(&sys, current_process);
-    exit(util::EXIT_CTRL_C);
+    std::process::exit(util::EXIT_CTRL_C);
 }
 
 fn terminate_subprocesses(sys: &System, process: &Process) {
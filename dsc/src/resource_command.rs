@@ -1,39 +1,39 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use crate::args::OutputFormat;
-use crate::util::{EXIT_DSC_ERROR, EXIT_INVALID_ARGS, EXIT_JSON_ERROR, add_type_name_to_json, write_output};
+use crate::args::{OutputFormat, PackageScope};
+use crate::util::{EXIT_DSC_ERROR, EXIT_INVALID_ARGS, EXIT_JSON_ERROR, EXIT_VALIDATION_FAILED, exit, write_output};
+use dsc_lib::audit::{AuditEntry, AuditLog, AuditOperation, Secrets};
 use dsc_lib::configure::config_doc::{Configuration, ExecutionKind};
 use dsc_lib::configure::add_resource_export_results_to_configuration;
+use dsc_lib::configure::context::Context;
 use dsc_lib::dscresources::invoke_result::{GetResult, ResourceGetResponse};
+use dsc_lib::dscresources::manifest_lint::{is_manifest_file_name, lint_manifest};
+use dsc_lib::dscresources::resource_manifest::RetryPolicy;
+use dsc_lib::dscresources::retry::{get_retry_policy, invoke_with_retry};
 use dsc_lib::dscerror::DscError;
+use dsc_lib::integrity::sign_manifest;
+use dsc_lib::package::{self, install_package, uninstall_package};
 use tracing::{error, debug};
 
 use dsc_lib::{
     dscresources::dscresource::{Invoke, DscResource},
     DscManager
 };
-use std::process::exit;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-pub fn get(dsc: &DscManager, resource_type: &str, mut input: String, format: &Option<OutputFormat>) {
-    let Some(mut resource) = get_resource(dsc, resource_type) else {
+pub fn get(dsc: &DscManager, resource_type: &str, input: &str, format: &Option<OutputFormat>) {
+    let Some(resource) = get_resource(dsc, resource_type) else {
         error!("{}", DscError::ResourceNotFound(resource_type.to_string()).to_string());
         return
     };
 
     debug!("resource.type_name - {} implemented_as - {:?}", resource.type_name, resource.implemented_as);
-    if let Some(requires) = &resource.require_adapter {
-        input = add_type_name_to_json(input, resource.type_name.clone());
-        if let Some(pr) = get_resource(dsc, requires) {
-            resource = pr;
-        } else {
-            error!("Adapter '{}' not found", requires);
-            return;
-        };
-    }
 
-    match resource.get(input.as_str()) {
-        Ok(result) => {
+    let retry_policy = get_manifest_retry_policy(resource);
+    match invoke_with_retry(&retry_policy, &resource.type_name, "get", || resource.get(input)) {
+        Ok((result, _)) => {
             // convert to json
             let json = match serde_json::to_string(&result) {
                 Ok(json) => json,
@@ -52,24 +52,14 @@ pub fn get(dsc: &DscManager, resource_type: &str, mut input: String, format: &Op
 }
 
 pub fn get_all(dsc: &DscManager, resource_type: &str, format: &Option<OutputFormat>) {
-    let mut input = String::new();
-    let Some(mut resource) = get_resource(dsc, resource_type) else {
+    let Some(resource) = get_resource(dsc, resource_type) else {
         error!("{}", DscError::ResourceNotFound(resource_type.to_string()).to_string());
         return
     };
 
     debug!("resource.type_name - {} implemented_as - {:?}", resource.type_name, resource.implemented_as);
-    if let Some(requires) = &resource.require_adapter {
-        input = add_type_name_to_json(input, resource.type_name.clone());
-        if let Some(pr) = get_resource(dsc, requires) {
-            resource = pr;
-        } else {
-            error!("Adapter '{}' not found", requires);
-            return;
-        };
-    }
 
-    let export_result = match resource.export(&input) {
+    let export_result = match resource.export("") {
         Ok(export) => { export }
         Err(err) => {
             error!("Error: {err}");
@@ -94,31 +84,24 @@ pub fn get_all(dsc: &DscManager, resource_type: &str, format: &Option<OutputForm
     }
 }
 
-pub fn set(dsc: &DscManager, resource_type: &str, mut input: String, format: &Option<OutputFormat>) {
+pub fn set(dsc: &DscManager, resource_type: &str, input: &str, format: &Option<OutputFormat>) {
     if input.is_empty() {
         error!("Error: Desired input is empty");
         exit(EXIT_INVALID_ARGS);
     }
 
-    let Some(mut resource) = get_resource(dsc, resource_type) else {
+    let Some(resource) = get_resource(dsc, resource_type) else {
         error!("{}", DscError::ResourceNotFound(resource_type.to_string()).to_string());
         return
     };
 
     debug!("resource.type_name - {} implemented_as - {:?}", resource.type_name, resource.implemented_as);
 
-    if let Some(requires) = &resource.require_adapter {
-        input = add_type_name_to_json(input, resource.type_name.clone());
-        if let Some(pr) = get_resource(dsc, requires) {
-            resource = pr;
-        } else {
-            error!("Adapter '{}' not found", requires);
-            return;
-        };
-    }
-
-    match resource.set(input.as_str(), true, &ExecutionKind::Actual) {
-        Ok(result) => {
+    let audit_log = get_audit_log();
+    let retry_policy = get_manifest_retry_policy(resource);
+    match invoke_with_retry(&retry_policy, &resource.type_name, "set", || resource.set(input, true, &ExecutionKind::Actual)) {
+        Ok((result, _)) => {
+            write_audit_entry(audit_log.as_ref(), &new_audit_entry(AuditOperation::Set, resource).with_result(&result, &Secrets::default()));
             // convert to json
             let json = match serde_json::to_string(&result) {
                 Ok(json) => json,
@@ -131,36 +114,28 @@ pub fn set(dsc: &DscManager, resource_type: &str, mut input: String, format: &Op
         }
         Err(err) => {
             error!("Error: {err}");
+            write_audit_entry(audit_log.as_ref(), &new_audit_entry(AuditOperation::Set, resource).with_error(&err));
             exit(EXIT_DSC_ERROR);
         }
     }
 }
 
-pub fn test(dsc: &DscManager, resource_type: &str, mut input: String, format: &Option<OutputFormat>) {
+pub fn test(dsc: &DscManager, resource_type: &str, input: &str, format: &Option<OutputFormat>) {
     if input.is_empty() {
         error!("Error: Expected input is required");
         exit(EXIT_INVALID_ARGS);
     }
 
-    let Some(mut resource) = get_resource(dsc, resource_type) else {
+    let Some(resource) = get_resource(dsc, resource_type) else {
         error!("{}", DscError::ResourceNotFound(resource_type.to_string()).to_string());
         return
     };
 
     debug!("resource.type_name - {} implemented_as - {:?}", resource.type_name, resource.implemented_as);
 
-    if let Some(requires) = &resource.require_adapter {
-        input = add_type_name_to_json(input, resource.type_name.clone());
-        if let Some(pr) = get_resource(dsc, requires) {
-            resource = pr;
-        } else {
-            error!("Adapter '{}' not found", requires);
-            return;
-        };
-    }
-
-    match resource.test(input.as_str()) {
-        Ok(result) => {
+    let retry_policy = get_manifest_retry_policy(resource);
+    match invoke_with_retry(&retry_policy, &resource.type_name, "test", || resource.test(input)) {
+        Ok((result, _)) => {
             // convert to json
             let json = match serde_json::to_string(&result) {
                 Ok(json) => json,
@@ -178,26 +153,32 @@ pub fn test(dsc: &DscManager, resource_type: &str, mut input: String, format: &O
     }
 }
 
-pub fn delete(dsc: &DscManager, resource_type: &str, mut input: String) {
-    let Some(mut resource) = get_resource(dsc, resource_type) else {
+pub fn delete(dsc: &DscManager, resource_type: &str, input: &str) {
+    let Some(resource) = get_resource(dsc, resource_type) else {
         error!("{}", DscError::ResourceNotFound(resource_type.to_string()).to_string());
         return
     };
 
     debug!("resource.type_name - {} implemented_as - {:?}", resource.type_name, resource.implemented_as);
 
-    if let Some(requires) = &resource.require_adapter {
-        input = add_type_name_to_json(input, resource.type_name.clone());
-        if let Some(pr) = get_resource(dsc, requires) {
-            resource = pr;
-        } else {
-            error!("Adapter '{}' not found", requires);
-            return;
-        };
+    let audit_log = get_audit_log();
+    let retry_policy = get_manifest_retry_policy(resource);
+    match invoke_with_retry(&retry_policy, &resource.type_name, "delete", || resource.delete(input)) {
+        Ok(((), _)) => {
+            write_audit_entry(audit_log.as_ref(), &new_audit_entry(AuditOperation::Delete, resource));
+        }
+        Err(err) => {
+            error!("Error: {err}");
+            write_audit_entry(audit_log.as_ref(), &new_audit_entry(AuditOperation::Delete, resource).with_error(&err));
+            exit(EXIT_DSC_ERROR);
+        }
     }
+}
 
-    match resource.delete(input.as_str()) {
-        Ok(()) => {}
+// the audit log is opened before the resource is invoked so a change is never made without being recorded
+fn get_audit_log() -> Option<AuditLog> {
+    match AuditLog::from_settings() {
+        Ok(audit_log) => audit_log,
         Err(err) => {
             error!("Error: {err}");
             exit(EXIT_DSC_ERROR);
@@ -205,6 +186,20 @@ pub fn delete(dsc: &DscManager, resource_type: &str, mut input: String) {
     }
 }
 
+// the entries of resource commands record the security context of the process like the ones of configurations
+fn new_audit_entry(operation: AuditOperation, resource: &DscResource) -> AuditEntry {
+    AuditEntry::new(operation, &resource.type_name, Context::new().security_context)
+}
+
+fn write_audit_entry(audit_log: Option<&AuditLog>, entry: &AuditEntry) {
+    if let Some(audit_log) = audit_log {
+        if let Err(err) = audit_log.write(entry) {
+            error!("Error: {err}");
+            exit(EXIT_DSC_ERROR);
+        }
+    }
+}
+
 pub fn schema(dsc: &DscManager, resource_type: &str, format: &Option<OutputFormat>) {
     let Some(resource) = get_resource(dsc, resource_type) else {
         error!("{}", DscError::ResourceNotFound(resource_type.to_string()).to_string());
@@ -219,7 +214,7 @@ pub fn schema(dsc: &DscManager, resource_type: &str, format: &Option<OutputForma
                     error!("Error: {err}");
                     exit(EXIT_JSON_ERROR);
                 }
-            };
+            }
             write_output(&json, format);
         }
         Err(err) => {
@@ -230,26 +225,14 @@ pub fn schema(dsc: &DscManager, resource_type: &str, format: &Option<OutputForma
 }
 
 pub fn export(dsc: &mut DscManager, resource_type: &str, format: &Option<OutputFormat>) {
-    let mut input = String::new();
     let Some(dsc_resource) = get_resource(dsc, resource_type) else {
         error!("{}", DscError::ResourceNotFound(resource_type.to_string()).to_string());
         return
     };
 
-    let mut adapter_resource: Option<&DscResource> = None;
-    if let Some(requires) = &dsc_resource.require_adapter {
-        input = add_type_name_to_json(input, dsc_resource.type_name.clone());
-        if let Some(pr) = get_resource(dsc, requires) {
-            adapter_resource = Some(pr);
-        } else {
-            error!("Adapter '{}' not found", requires);
-            return;
-        };
-    }
-
     let mut conf = Configuration::new();
 
-    if let Err(err) = add_resource_export_results_to_configuration(dsc_resource, adapter_resource, &mut conf, &input) {
+    if let Err(err) = add_resource_export_results_to_configuration(dsc_resource, &mut conf, "") {
         error!("Error: {err}");
         exit(EXIT_DSC_ERROR);
     }
@@ -264,8 +247,131 @@ pub fn export(dsc: &mut DscManager, resource_type: &str, format: &Option<OutputF
     write_output(&json, format);
 }
 
+pub fn lint(path: &str, format: &Option<OutputFormat>) {
+    let path = Path::new(path);
+    let manifests: Vec<PathBuf> = if path.is_dir() {
+        let entries = match path.read_dir() {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("Error: {err}");
+                exit(EXIT_INVALID_ARGS);
+            }
+        };
+        let mut manifests = entries.filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|manifest| manifest.is_file() && manifest.file_name().and_then(|name| name.to_str()).is_some_and(is_manifest_file_name))
+            .collect::<Vec<PathBuf>>();
+        manifests.sort();
+        manifests
+    } else {
+        vec![path.to_path_buf()]
+    };
+    if manifests.is_empty() {
+        error!("No resource manifests found in '{}'", path.display());
+        exit(EXIT_INVALID_ARGS);
+    }
+
+    let mut valid = true;
+    for manifest in manifests {
+        let result = lint_manifest(&manifest);
+        valid &= result.valid;
+        let json = match serde_json::to_string(&result) {
+            Ok(json) => json,
+            Err(err) => {
+                error!("JSON Error: {err}");
+                exit(EXIT_JSON_ERROR);
+            }
+        };
+        write_output(&json, format);
+    }
+    if !valid {
+        exit(EXIT_VALIDATION_FAILED);
+    }
+}
+
+pub fn install(path: &str, scope: PackageScope, force: bool, format: &Option<OutputFormat>) {
+    let package = match install_package(Path::new(path), get_package_scope(scope), force) {
+        Ok(package) => package,
+        Err(err) => {
+            error!("Error: {err}");
+            exit(EXIT_DSC_ERROR);
+        }
+    };
+    let json = match serde_json::to_string(&package) {
+        Ok(json) => json,
+        Err(err) => {
+            error!("JSON Error: {err}");
+            exit(EXIT_JSON_ERROR);
+        }
+    };
+    write_output(&json, format);
+}
+
+pub fn uninstall(name: &str, version: Option<&str>, scope: PackageScope, format: &Option<OutputFormat>) {
+    let packages = match uninstall_package(name, version, get_package_scope(scope)) {
+        Ok(packages) => packages,
+        Err(err) => {
+            error!("Error: {err}");
+            exit(EXIT_DSC_ERROR);
+        }
+    };
+    for package in packages {
+        let json = match serde_json::to_string(&package) {
+            Ok(json) => json,
+            Err(err) => {
+                error!("JSON Error: {err}");
+                exit(EXIT_JSON_ERROR);
+            }
+        };
+        write_output(&json, format);
+    }
+}
+
+pub fn sign(path: &str, key_path: &str, format: &Option<OutputFormat>) {
+    let private_key = match fs::read_to_string(key_path) {
+        Ok(private_key) => private_key,
+        Err(err) => {
+            error!("Error: Failed to read key file '{key_path}': {err}");
+            exit(EXIT_INVALID_ARGS);
+        }
+    };
+    let signature = match sign_manifest(Path::new(path), &private_key) {
+        Ok(signature) => signature,
+        Err(err) => {
+            error!("Error: {err}");
+            exit(EXIT_DSC_ERROR);
+        }
+    };
+    let json = match serde_json::to_string(&signature) {
+        Ok(json) => json,
+        Err(err) => {
+            error!("JSON Error: {err}");
+            exit(EXIT_JSON_ERROR);
+        }
+    };
+    write_output(&json, format);
+}
+
+fn get_package_scope(scope: PackageScope) -> package::PackageScope {
+    match scope {
+        PackageScope::User => package::PackageScope::User,
+        PackageScope::System => package::PackageScope::System,
+    }
+}
+
 #[must_use]
 pub fn get_resource<'a>(dsc: &'a DscManager, resource: &str) -> Option<&'a DscResource> {
     //TODO: add dynamically generated resource to dsc
     dsc.find_resource(resource)
 }
+
+// operations invoked directly are retried according to the policy declared by the manifest
+fn get_manifest_retry_policy(resource: &DscResource) -> RetryPolicy {
+    match get_retry_policy(resource, None) {
+        Ok(retry_policy) => retry_policy,
+        Err(err) => {
+            error!("Error: {err}");
+            exit(EXIT_DSC_ERROR);
+        }
+    }
+}
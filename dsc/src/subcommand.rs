@@ -1,13 +1,13 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use crate::args::{ConfigSubCommand, DscType, OutputFormat, ResourceSubCommand};
+use crate::args::{ConfigSubCommand, DriftAction, DscType, HistorySubCommand, OutputFormat, ResourceSubCommand, RestartPolicy};
 use crate::resolve::{get_contents, Include};
 use crate::resource_command::{get_resource, self};
 use crate::Stream;
 use crate::tablewriter::Table;
-use crate::util::{DSC_CONFIG_ROOT, EXIT_DSC_ERROR, EXIT_INVALID_INPUT, EXIT_JSON_ERROR, get_schema, write_output, get_input, set_dscconfigroot, validate_json};
-use dsc_lib::configure::{Configurator, config_doc::{Configuration, ExecutionKind}, config_result::ResourceGetResult};
+use crate::util::{DSC_CONFIG_ROOT, EXIT_DSC_ERROR, EXIT_INVALID_INPUT, EXIT_JSON_ERROR, EXIT_RESTART_REQUIRED, exit, get_schema, write_output, get_input, set_dscconfigroot, validate_json};
+use dsc_lib::configure::{Configurator, context::Context, config_doc::{self, Configuration, ExecutionKind, Operation}, config_result::ResourceGetResult, plan::{self, ConfigurationPlan}};
 use dsc_lib::dscerror::DscError;
 use dsc_lib::dscresources::invoke_result::ResolveResult;
 use dsc_lib::{
@@ -15,10 +15,13 @@ use dsc_lib::{
     dscresources::invoke_result::ValidateResult,
     dscresources::dscresource::{Capability, ImplementedAs, Invoke},
     dscresources::resource_manifest::{import_manifest, ResourceManifest},
+    history::{diff_runs, HistoryStore},
+    package::list_installed_packages,
+    settings::DiscoverySettings,
 };
-use std::collections::HashMap;
-use std::process::exit;
-use tracing::{debug, error, trace};
+use serde::Serialize;
+use std::{collections::HashMap, fs};
+use tracing::{debug, error, info, trace, warn};
 
 pub fn config_get(configurator: &mut Configurator, format: &Option<OutputFormat>, as_group: &bool)
 {
@@ -43,6 +46,7 @@ pub fn config_get(configurator: &mut Configurator, format: &Option<OutputFormat>
                     }
                 };
                 write_output(&json, format);
+                record_history(configurator, Operation::Get, &result);
                 if result.had_errors {
                     exit(EXIT_DSC_ERROR);
                 }
@@ -78,9 +82,11 @@ pub fn config_set(configurator: &mut Configurator, format: &Option<OutputFormat>
                     }
                 };
                 write_output(&json, format);
+                record_history(configurator, Operation::Set, &result);
                 if result.had_errors {
                     exit(EXIT_DSC_ERROR);
                 }
+                exit_if_restart_required(&configurator.context);
             }
         },
         Err(err) => {
@@ -128,6 +134,7 @@ pub fn config_test(configurator: &mut Configurator, format: &Option<OutputFormat
                     }
                 };
                 write_output(&json, format);
+                record_history(configurator, Operation::Test, &result);
                 if result.had_errors {
                     exit(EXIT_DSC_ERROR);
                 }
@@ -140,6 +147,84 @@ pub fn config_test(configurator: &mut Configurator, format: &Option<OutputFormat
     }
 }
 
+pub fn config_plan(configurator: &mut Configurator, output: &Option<String>, format: &Option<OutputFormat>)
+{
+    let plan = match configurator.invoke_plan() {
+        Ok(plan) => plan,
+        Err(err) => {
+            error!("Error: {err}");
+            exit(EXIT_DSC_ERROR);
+        }
+    };
+    let json = match serde_json::to_string(&plan) {
+        Ok(json) => json,
+        Err(err) => {
+            error!("JSON Error: {err}");
+            exit(EXIT_JSON_ERROR);
+        }
+    };
+    let Some(output) = output else {
+        write_output(&json, format);
+        return;
+    };
+    if let Err(err) = fs::write(output, json) {
+        error!("Error: Failed to write plan '{output}': {err}");
+        exit(EXIT_DSC_ERROR);
+    }
+    info!("Plan written to '{output}'");
+}
+
+pub fn config_apply(configurator: &mut Configurator, plan: &ConfigurationPlan, on_drift: &Option<DriftAction>, format: &Option<OutputFormat>)
+{
+    let drift_action = match on_drift {
+        Some(DriftAction::Report) => plan::DriftAction::Report,
+        Some(DriftAction::Refuse) | None => plan::DriftAction::Refuse,
+    };
+    match configurator.invoke_apply(plan, drift_action) {
+        Ok(result) => {
+            let json = match serde_json::to_string(&result) {
+                Ok(json) => json,
+                Err(err) => {
+                    error!("JSON Error: {err}");
+                    exit(EXIT_JSON_ERROR);
+                }
+            };
+            write_output(&json, format);
+            record_history(configurator, Operation::Set, &result);
+            if result.had_errors {
+                exit(EXIT_DSC_ERROR);
+            }
+            exit_if_restart_required(&configurator.context);
+        },
+        Err(err) => {
+            error!("Error: {err}");
+            exit(EXIT_DSC_ERROR);
+        }
+    }
+}
+
+// a restart is only signaled by the exit code when the restart policy doesn't continue without it
+fn exit_if_restart_required(context: &Context) {
+    if context.reboot_required && context.execution_type == ExecutionKind::Actual && context.restart_policy != config_doc::RestartPolicy::Continue {
+        exit(EXIT_RESTART_REQUIRED);
+    }
+}
+
+fn read_plan(path: &str) -> Result<(ConfigurationPlan, String), DscError> {
+    let plan: ConfigurationPlan = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let document = plan.get_document()?;
+    Ok((plan, document))
+}
+
+// a run that can't be recorded doesn't fail the operation
+fn record_history<T: Serialize>(configurator: &Configurator, operation: Operation, result: &T) {
+    match configurator.record_history(operation, result) {
+        Ok(Some(id)) => debug!("Run recorded in history as '{id}'"),
+        Ok(None) => {},
+        Err(err) => warn!("Failed to record run in history: {err}"),
+    }
+}
+
 pub fn config_export(configurator: &mut Configurator, format: &Option<OutputFormat>)
 {
     match configurator.invoke_export() {
@@ -185,11 +270,21 @@ fn initialize_config_root(path: &Option<String>) -> Option<String> {
     }
 }
 
+fn get_restart_policy(restart_policy: RestartPolicy) -> config_doc::RestartPolicy {
+    match restart_policy {
+        RestartPolicy::Continue => config_doc::RestartPolicy::Continue,
+        RestartPolicy::Stop => config_doc::RestartPolicy::Stop,
+        RestartPolicy::Exit => config_doc::RestartPolicy::Exit,
+    }
+}
+
 #[allow(clippy::too_many_lines)]
-pub fn config(subcommand: &ConfigSubCommand, parameters: &Option<String>, stdin: &Option<String>, as_group: &bool, as_include: &bool) {
+pub fn config(subcommand: &ConfigSubCommand, parameters: &Option<String>, stdin: &Option<String>, as_group: &bool, as_include: &bool, discovery_settings: &DiscoverySettings) {
+    let mut plan = None;
     let (new_parameters, json_string) = match subcommand {
         ConfigSubCommand::Get { document, path, .. } |
         ConfigSubCommand::Set { document, path, .. } |
+        ConfigSubCommand::Plan { document, path, .. } |
         ConfigSubCommand::Test { document, path, .. } |
         ConfigSubCommand::Validate { document, path, .. } |
         ConfigSubCommand::Export { document, path, .. } => {
@@ -208,6 +303,20 @@ pub fn config(subcommand: &ConfigSubCommand, parameters: &Option<String>, stdin:
                 (None, input)
             }
         },
+        ConfigSubCommand::Apply { plan: plan_path, .. } => {
+            // paths of the document are relative to the plan
+            initialize_config_root(&Some(plan_path.clone()));
+            match read_plan(plan_path) {
+                Ok((read, document)) => {
+                    plan = Some(read);
+                    (None, document)
+                },
+                Err(err) => {
+                    error!("Error: Invalid plan '{plan_path}': {err}");
+                    exit(EXIT_INVALID_INPUT);
+                }
+            }
+        },
         ConfigSubCommand::Resolve { document, path, .. } => {
             let new_path = initialize_config_root(path);
             let input = get_input(document, stdin, &new_path);
@@ -222,7 +331,7 @@ pub fn config(subcommand: &ConfigSubCommand, parameters: &Option<String>, stdin:
         }
     };
 
-    let mut configurator = match Configurator::new(&json_string) {
+    let mut configurator = match Configurator::with_discovery_settings(&json_string, discovery_settings.clone()) {
         Ok(configurator) => configurator,
         Err(err) => {
             error!("Error: {err}");
@@ -230,11 +339,20 @@ pub fn config(subcommand: &ConfigSubCommand, parameters: &Option<String>, stdin:
         }
     };
 
-    if let ConfigSubCommand::Set { what_if , .. } = subcommand {
-        if *what_if {
-            configurator.context.execution_type = ExecutionKind::WhatIf;
-        }
-    };
+    match subcommand {
+        ConfigSubCommand::Set { what_if, restart_policy, .. } => {
+            if *what_if {
+                configurator.context.execution_type = ExecutionKind::WhatIf;
+            }
+            if let Some(restart_policy) = restart_policy {
+                configurator.context.restart_policy = get_restart_policy(*restart_policy);
+            }
+        },
+        ConfigSubCommand::Apply { restart_policy: Some(restart_policy), .. } => {
+            configurator.context.restart_policy = get_restart_policy(*restart_policy);
+        },
+        _ => {},
+    }
 
     let parameters: Option<serde_json::Value> = match if new_parameters.is_some() {
         &new_parameters
@@ -270,11 +388,42 @@ pub fn config(subcommand: &ConfigSubCommand, parameters: &Option<String>, stdin:
         }
     };
 
+    // the parameters of a plan are the ones it was created with, secure parameters are passed again
+    let parameters = match &plan {
+        Some(plan) => match plan.get_parameters(&parameters) {
+            Ok(parameters) => parameters,
+            Err(err) => {
+                error!("Error: Parameter input failure: {err}");
+                exit(EXIT_INVALID_INPUT);
+            }
+        },
+        None => parameters,
+    };
+
     if let Err(err) = configurator.set_parameters(&parameters) {
         error!("Error: Parameter input failure: {err}");
         exit(EXIT_INVALID_INPUT);
     }
 
+    // the nested runs of group resources are resumed by running the whole group again
+    if let ConfigSubCommand::Set { what_if: false, resume, restart, .. } = subcommand {
+        if !*as_group {
+            match configurator.enable_checkpoints(*resume) {
+                Ok(Some(completed)) if !*resume && !*restart => {
+                    warn!("Discarded the checkpoint of an interrupted run with {completed} completed resources and started from the beginning, use --resume to continue it");
+                },
+                Ok(None) if *resume => {
+                    warn!("No checkpoint of an interrupted run of this document and parameters, starting from the beginning");
+                },
+                Ok(_) => {},
+                Err(err) => {
+                    error!("Error: {err}");
+                    exit(EXIT_DSC_ERROR);
+                }
+            }
+        }
+    }
+
     match subcommand {
         ConfigSubCommand::Get { format, .. } => {
             config_get(&mut configurator, format, as_group);
@@ -282,6 +431,14 @@ pub fn config(subcommand: &ConfigSubCommand, parameters: &Option<String>, stdin:
         ConfigSubCommand::Set { format, .. } => {
             config_set(&mut configurator, format, as_group);
         },
+        ConfigSubCommand::Plan { output, format, .. } => {
+            config_plan(&mut configurator, output, format);
+        },
+        ConfigSubCommand::Apply { on_drift, format, .. } => {
+            if let Some(plan) = &plan {
+                config_apply(&mut configurator, plan, on_drift, format);
+            }
+        },
         ConfigSubCommand::Test { format, as_get, .. } => {
             config_test(&mut configurator, format, as_group, as_get);
         },
@@ -303,7 +460,7 @@ pub fn config(subcommand: &ConfigSubCommand, parameters: &Option<String>, stdin:
                     }
                 }
             } else {
-                match validate_config(configurator.get_config()) {
+                match validate_config(configurator.get_config(), discovery_settings) {
                     Ok(()) => {
                         // valid, so do nothing
                     },
@@ -363,6 +520,7 @@ pub fn config(subcommand: &ConfigSubCommand, parameters: &Option<String>, stdin:
 /// # Arguments
 ///
 /// * `config` - The configuration to validate.
+/// * `discovery_settings` - The discovery settings of the command line.
 ///
 /// # Returns
 ///
@@ -371,13 +529,13 @@ pub fn config(subcommand: &ConfigSubCommand, parameters: &Option<String>, stdin:
 /// # Errors
 ///
 /// * `DscError` - The error that occurred.
-pub fn validate_config(config: &Configuration) -> Result<(), DscError> {
+pub fn validate_config(config: &Configuration, discovery_settings: &DiscoverySettings) -> Result<(), DscError> {
     // first validate against the config schema
     debug!("Validating configuration against schema");
     let schema = serde_json::to_value(get_schema(DscType::Configuration))?;
     let config_value = serde_json::to_value(config)?;
     validate_json("Configuration", &schema, &config_value)?;
-    let mut dsc = DscManager::new()?;
+    let mut dsc = DscManager::with_discovery_settings(discovery_settings.clone())?;
 
     // then validate each resource
     let Some(resources) = config_value["resources"].as_array() else {
@@ -441,15 +599,119 @@ pub fn validate_config(config: &Configuration) -> Result<(), DscError> {
             } else {
                 return Err(DscError::Validation(format!("Error: Resource {type_name} does not have a manifest")));
             }
+        } else {
+            // custom resources are validated using the schema generated from their properties
+            trace!("Resource {type_name} is a custom resource, using schema");
+            let schema = serde_json::from_str(&resource.schema()?)?;
+
+            validate_json(&resource.type_name, &schema, &resource_block["properties"])?;
         }
     }
 
     Ok(())
 }
 
+pub fn history(subcommand: &HistorySubCommand) {
+    let store = match HistoryStore::from_settings() {
+        Ok(store) => store,
+        Err(err) => {
+            error!("Error: {err}");
+            exit(EXIT_DSC_ERROR);
+        }
+    };
+
+    match subcommand {
+        HistorySubCommand::List { format } => {
+            list_history(&store, format);
+        },
+        HistorySubCommand::Show { id, format } => {
+            let record = match store.get(id) {
+                Ok(record) => record,
+                Err(err) => {
+                    error!("Error: {err}");
+                    exit(EXIT_DSC_ERROR);
+                }
+            };
+            let json = match serde_json::to_string(&record) {
+                Ok(json) => json,
+                Err(err) => {
+                    error!("JSON Error: {err}");
+                    exit(EXIT_JSON_ERROR);
+                }
+            };
+            write_output(&json, format);
+        },
+        HistorySubCommand::Diff { from, to, format } => {
+            let (from, to) = match (store.get(from), store.get(to)) {
+                (Ok(from), Ok(to)) => (from, to),
+                (Err(err), _) | (_, Err(err)) => {
+                    error!("Error: {err}");
+                    exit(EXIT_DSC_ERROR);
+                }
+            };
+            let json = match serde_json::to_string(&diff_runs(&from, &to)) {
+                Ok(json) => json,
+                Err(err) => {
+                    error!("JSON Error: {err}");
+                    exit(EXIT_JSON_ERROR);
+                }
+            };
+            write_output(&json, format);
+        },
+    }
+}
+
+fn list_history(store: &HistoryStore, format: &Option<OutputFormat>) {
+    let summaries = match store.list() {
+        Ok(summaries) => summaries,
+        Err(err) => {
+            error!("Error: {err}");
+            exit(EXIT_DSC_ERROR);
+        }
+    };
+
+    let write_table = format.is_none() && atty::is(Stream::Stdout);
+    let mut table = Table::new(&["Id", "Timestamp", "Operation", "ExecutionType", "Resources", "Status"]);
+    for summary in summaries {
+        if write_table {
+            let status = if summary.had_errors {
+                "Errors".to_string()
+            } else if let Some(in_desired_state) = summary.in_desired_state {
+                if in_desired_state { "InDesiredState".to_string() } else { "NotInDesiredState".to_string() }
+            } else if let Some(changed) = summary.changed {
+                format!("{changed} changed")
+            } else {
+                String::new()
+            };
+            table.add_row(vec![
+                summary.id,
+                summary.timestamp,
+                format!("{:?}", summary.operation),
+                format!("{:?}", summary.execution_type),
+                summary.resources.to_string(),
+                status,
+            ]);
+            continue;
+        }
+        let json = match serde_json::to_string(&summary) {
+            Ok(json) => json,
+            Err(err) => {
+                error!("JSON Error: {err}");
+                exit(EXIT_JSON_ERROR);
+            }
+        };
+        write_output(&json, format);
+        // insert newline separating instances if writing to console
+        if atty::is(Stream::Stdout) { println!(); }
+    }
+    if write_table {
+        table.print();
+    }
+}
+
 #[allow(clippy::too_many_lines)]
-pub fn resource(subcommand: &ResourceSubCommand, stdin: &Option<String>) {
-    let mut dsc = match DscManager::new() {
+pub fn resource(subcommand: &ResourceSubCommand, stdin: &Option<String>, discovery_settings: &DiscoverySettings) {
+    let mut dsc = match DscManager::with_discovery_settings(discovery_settings.clone()) {
         Ok(dsc) => dsc,
         Err(err) => {
             error!("Error: {err}");
@@ -458,8 +720,14 @@ pub fn resource(subcommand: &ResourceSubCommand, stdin: &Option<String>) {
     };
 
     match subcommand {
-        ResourceSubCommand::List { resource_name, adapter_name, description, tags, format } => {
-            list_resources(&mut dsc, resource_name, adapter_name, description, tags, format);
+        ResourceSubCommand::List { resource_name, adapter_name, description, tags, diagnostics, installed, format } => {
+            if *installed {
+                list_installed(resource_name, format);
+            } else if *diagnostics {
+                list_diagnostics(&mut dsc, resource_name, adapter_name, format);
+            } else {
+                list_resources(&mut dsc, resource_name, adapter_name, description, tags, format);
+            }
         },
         ResourceSubCommand::Schema { resource , format } => {
             dsc.find_resources(&[resource.to_string()]);
@@ -474,27 +742,89 @@ pub fn resource(subcommand: &ResourceSubCommand, stdin: &Option<String>) {
             if *all { resource_command::get_all(&dsc, resource, format); }
             else {
                 let parsed_input = get_input(input, stdin, path);
-                resource_command::get(&dsc, resource, parsed_input, format);
+                resource_command::get(&dsc, resource, &parsed_input, format);
             }
         },
         ResourceSubCommand::Set { resource, input, path, format } => {
             dsc.find_resources(&[resource.to_string()]);
             let parsed_input = get_input(input, stdin, path);
-            resource_command::set(&dsc, resource, parsed_input, format);
+            resource_command::set(&dsc, resource, &parsed_input, format);
         },
         ResourceSubCommand::Test { resource, input, path, format } => {
             dsc.find_resources(&[resource.to_string()]);
             let parsed_input = get_input(input, stdin, path);
-            resource_command::test(&dsc, resource, parsed_input, format);
+            resource_command::test(&dsc, resource, &parsed_input, format);
         },
         ResourceSubCommand::Delete { resource, input, path } => {
             dsc.find_resources(&[resource.to_string()]);
             let parsed_input = get_input(input, stdin, path);
-            resource_command::delete(&dsc, resource, parsed_input);
+            resource_command::delete(&dsc, resource, &parsed_input);
+        },
+        ResourceSubCommand::Lint { path, format } => {
+            resource_command::lint(path, format);
+        },
+        ResourceSubCommand::Install { path, scope, force, format } => {
+            resource_command::install(path, *scope, *force, format);
+        },
+        ResourceSubCommand::Uninstall { name, version, scope, format } => {
+            resource_command::uninstall(name, version.as_deref(), *scope, format);
+        },
+        ResourceSubCommand::Sign { path, key, format } => {
+            resource_command::sign(path, key, format);
         },
     }
 }
 
+fn list_installed(package_name: &Option<String>, format: &Option<OutputFormat>) {
+    let packages = match list_installed_packages(&package_name.clone().unwrap_or("*".to_string())) {
+        Ok(packages) => packages,
+        Err(err) => {
+            error!("Error: {err}");
+            exit(EXIT_DSC_ERROR);
+        }
+    };
+
+    let write_table = format.is_none() && atty::is(Stream::Stdout);
+    let mut table = Table::new(&["Name", "Version", "Scope", "Resources", "Description"]);
+    for package in packages {
+        if write_table {
+            table.add_row(vec![
+                package.name,
+                package.version,
+                format!("{:?}", package.scope),
+                package.resources.join(", "),
+                package.description.unwrap_or_default()
+            ]);
+            continue;
+        }
+        let json = match serde_json::to_string(&package) {
+            Ok(json) => json,
+            Err(err) => {
+                error!("JSON Error: {err}");
+                exit(EXIT_JSON_ERROR);
+            }
+        };
+        write_output(&json, format);
+        // insert newline separating instances if writing to console
+        if atty::is(Stream::Stdout) { println!(); }
+    }
+    if write_table {
+        table.print();
+    }
+}
+
+fn list_diagnostics(dsc: &mut DscManager, resource_name: &Option<String>, adapter_name: &Option<String>, format: &Option<OutputFormat>) {
+    let diagnostics = dsc.get_discovery_diagnostics(&resource_name.clone().unwrap_or("*".to_string()), &adapter_name.clone().unwrap_or_default());
+    let json = match serde_json::to_string(&diagnostics) {
+        Ok(json) => json,
+        Err(err) => {
+            error!("JSON Error: {err}");
+            exit(EXIT_JSON_ERROR);
+        }
+    };
+    write_output(&json, format);
+}
+
 fn list_resources(dsc: &mut DscManager, resource_name: &Option<String>, adapter_name: &Option<String>, description: &Option<String>, tags: &Option<Vec<String>>, format: &Option<OutputFormat>) {
     let mut write_table = false;
     let mut table = Table::new(&["Type", "Kind", "Version", "Caps", "RequireAdapter", "Description"]);
@@ -552,6 +882,16 @@ fn list_resources(dsc: &mut DscManager, resource_name: &Option<String>, adapter_
                 }
                 if !found { continue; }
             }
+        } else if let ImplementedAs::Custom(_) = resource.implemented_as {
+            // custom resources don't have a manifest, so only the description can be used for filtering
+            if let Some(description) = description {
+                if !resource.description.clone().unwrap_or_default().to_lowercase().contains(&description.to_lowercase()) {
+                    continue;
+                }
+            }
+            if tags.is_some() {
+                continue;
+            }
         } else {
             // resource does not have a manifest but filtering on description or tags was requested - skip such resource
             if description.is_some() || tags.is_some() {
@@ -1,7 +1,7 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use crate::args::{DscType, OutputFormat, TraceFormat, TraceLevel};
+use crate::args::{Args, DscType, OutputFormat, TraceFormat, TraceLevel};
 
 use atty::Stream;
 use crate::resolve::Include;
@@ -21,18 +21,17 @@ use dsc_lib::{
             SetResult,
             TestResult,
             ResolveResult,
-        }, resource_manifest::ResourceManifest
+        }, resource_host::shutdown_resource_hosts, resource_manifest::ResourceManifest
     },
+    settings::DiscoverySettings,
     util::parse_input_to_json,
 };
 use jsonschema::JSONSchema;
 use path_absolutize::Absolutize;
 use schemars::{schema_for, schema::RootSchema};
 use serde_json::Value;
-use std::collections::HashMap;
 use std::env;
 use std::path::Path;
-use std::process::exit;
 use syntect::{
     easy::HighlightLines,
     highlighting::ThemeSet,
@@ -50,10 +49,21 @@ pub const EXIT_JSON_ERROR: i32 = 3;
 pub const EXIT_INVALID_INPUT: i32 = 4;
 pub const EXIT_VALIDATION_FAILED: i32 = 5;
 pub const EXIT_CTRL_C: i32 = 6;
+pub const EXIT_RESTART_REQUIRED: i32 = 7;
 
 pub const DSC_CONFIG_ROOT: &str = "DSC_CONFIG_ROOT";
 pub const DSC_TRACE_LEVEL: &str = "DSC_TRACE_LEVEL";
 
+/// Stop the running resource hosts and exit the process.
+///
+/// # Arguments
+///
+/// * `code` - The exit code of the process
+pub fn exit(code: i32) -> ! {
+    shutdown_resource_hosts();
+    std::process::exit(code)
+}
+
 /// Get string representation of JSON value.
 ///
 /// # Arguments
@@ -75,66 +85,6 @@ pub fn serde_json_value_to_string(json: &serde_json::Value) -> String
     }
 }
 
-/// Add fields to the JSON.
-///
-/// # Arguments
-///
-/// * `json` - The JSON to add the fields to
-/// * `fields_to_add` - The fields to add
-///
-/// # Returns
-///
-/// * `String` - The JSON with the fields added
-///
-/// # Errors
-///
-/// * `DscError` - The JSON is invalid
-#[allow(clippy::implicit_hasher)]
-pub fn add_fields_to_json(json: &str, fields_to_add: &HashMap<String, String>) -> Result<String, DscError>
-{
-    let mut v = serde_json::from_str::<serde_json::Value>(json)?;
-
-    if let serde_json::Value::Object(ref mut map) = v {
-        for (k, v) in fields_to_add {
-            map.insert(k.clone(), serde_json::Value::String(v.clone()));
-        }
-    }
-
-    let result = serde_json::to_string(&v)?;
-    Ok(result)
-}
-
-/// Add the type property value to the JSON.
-///
-/// # Arguments
-///
-/// * `json` - The JSON to add the type property to
-/// * `type_name` - The type name to add
-///
-/// # Returns
-///
-/// * `String` - The JSON with the type property added
-#[must_use]
-pub fn add_type_name_to_json(json: String, type_name: String) -> String
-{
-    let mut map:HashMap<String,String> = HashMap::new();
-    map.insert(String::from("adapted_dsc_type"), type_name);
-
-    let mut j = json;
-    if j.is_empty()
-    {
-        j = String::from("{}");
-    }
-
-    match add_fields_to_json(&j, &map) {
-        Ok(json) => json,
-        Err(err) => {
-            error!("JSON Error: {err}");
-            exit(EXIT_JSON_ERROR);
-        }
-    }
-}
-
 /// Get the JSON schema for requested type.
 ///
 /// # Arguments
@@ -267,6 +217,32 @@ pub fn write_output(json: &str, format: &Option<OutputFormat>) {
     }
 }
 
+/// Get the discovery settings given as arguments, they take precedence over the settings files.
+/// Nested `dsc` processes, like the ones of group resources, don't get them, `DSC_SETTINGS` is used for those.
+///
+/// # Arguments
+///
+/// * `args` - The arguments of `dsc`.
+#[must_use]
+pub fn get_discovery_settings(args: &Args) -> DiscoverySettings {
+    let mut search_paths = Vec::new();
+    for search_path in args.search_path.iter().flatten() {
+        // commands of resources run in their directory, so relative paths are made absolute
+        let Ok(full_path) = Path::new(search_path).absolutize() else {
+            error!("Error making search path '{search_path}' absolute");
+            exit(EXIT_INVALID_ARGS);
+        };
+        search_paths.push(full_path.to_string_lossy().to_string());
+    }
+    DiscoverySettings {
+        search_paths: if search_paths.is_empty() { None } else { Some(search_paths) },
+        recurse: args.recurse.then_some(true),
+        max_depth: args.max_depth,
+        exclude: args.exclude.clone(),
+        scan_path: args.no_path.then_some(false),
+    }
+}
+
 pub fn enable_tracing(trace_level: &Option<TraceLevel>, trace_format: &TraceFormat) {
     let tracing_level = match trace_level {
         Some(level) => level,
@@ -15,4 +15,65 @@ Describe 'exit code tests' {
         $result.actualState.exitCode | Should -Be 0
         $LASTEXITCODE | Should -Be 0
     }
+    It 'not found exit code returns instance as not existing for get' {
+        $result = dsc resource get -r Test/ExitCode --input "{ exitCode: 2 }" | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $result.actualState.exitCode | Should -Be 2
+        $result.actualState._exist | Should -BeFalse
+    }
+    It 'not found exit code is success for delete' {
+        dsc resource delete -r Test/ExitCode --input "{ exitCode: 2 }"
+        $LASTEXITCODE | Should -Be 0
+    }
+    It 'not found exit code is failure for set' {
+        dsc resource set -r Test/ExitCode --input "{ exitCode: 2 }" 2> $TestDrive/tracing.txt
+        $LASTEXITCODE | Should -Not -Be 0
+        "$TestDrive/tracing.txt" | Should -FileContentMatchExactly 'Instance not found'
+    }
+    It 'not in desired state exit code returns false for test' {
+        $result = dsc resource test -r Test/ExitCode --input "{ exitCode: 5 }" | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $result.inDesiredState | Should -BeFalse
+    }
+    It 'retryable exit code is retried before failing' {
+        dsc -l warn resource get -r Test/ExitCode --input "{ exitCode: 9 }" 2> $TestDrive/tracing.txt
+        $LASTEXITCODE | Should -Not -Be 0
+        "$TestDrive/tracing.txt" | Should -FileContentMatchExactly 'attempt 2 of 3 failed'
+        "$TestDrive/tracing.txt" | Should -FileContentMatchExactly 'Placeholder transient failure from manifest for exit code 9'
+    }
+    It 'reboot required exit code is reported in the metadata' {
+        $config_yaml = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Installer
+              type: Test/ExitCode
+              properties:
+                exitCode: 6
+            - name: Other
+              type: Test/ExitCode
+              properties:
+                exitCode: 0
+'@
+        $out = $config_yaml | dsc config set | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.metadata.'Microsoft.DSC'.rebootRequired | Should -BeTrue
+        $out.results[0].metadata.'Microsoft.DSC'.rebootRequired | Should -BeTrue
+        $out.results[1].metadata.'Microsoft.DSC'.psobject.properties.name | Should -Not -Contain 'rebootRequired'
+    }
+    It 'warning exit code is reported as a message' {
+        $config_yaml = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Warning
+              type: Test/ExitCode
+              properties:
+                exitCode: 7
+'@
+        $out = $config_yaml | dsc config get | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results[0].result.actualState.exitCode | Should -Be 7
+        $out.messages[0].name | Should -BeExactly 'Warning'
+        $out.messages[0].level | Should -BeExactly 'Warning'
+        $out.messages[0].message | Should -BeExactly 'Placeholder warning from manifest for exit code 7'
+    }
 }
@@ -0,0 +1,128 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'agent tests' {
+    BeforeAll {
+        $oldSettings = $env:DSC_SETTINGS
+        $config = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            parameters:
+              output:
+                type: string
+                defaultValue: hello
+            resources:
+            - name: Echo
+              type: Test/Echo
+              properties:
+                output: "[parameters('output')]"
+'@
+    }
+
+    BeforeEach {
+        $root = Join-Path $TestDrive ([System.IO.Path]::GetRandomFileName())
+        $statePath = Join-Path $root 'agent'
+        $historyPath = Join-Path $root 'history'
+        $configPath = Join-Path $root 'config.dsc.yaml'
+        $null = New-Item -ItemType Directory -Path $root
+        Set-Content -Path $configPath -Value $config
+        $env:DSC_SETTINGS = @{ agent = @{ statePath = $statePath }; history = @{ path = $historyPath } } | ConvertTo-Json -Compress
+    }
+
+    AfterAll {
+        $env:DSC_SETTINGS = $oldSettings
+    }
+
+    It 'first run applies the document and later runs monitor it' {
+        $out = dsc agent run -p $configPath --once | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.action | Should -BeExactly 'applied'
+        $out.mode | Should -BeExactly 'ApplyAndMonitor'
+        $out.hadErrors | Should -BeFalse
+        $out = dsc agent run -p $configPath --once | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.action | Should -BeExactly 'monitored'
+        $out.inDesiredState | Should -BeTrue
+        Test-Path (Join-Path $statePath 'agent.lock') | Should -BeFalse
+    }
+
+    It 'apply only mode does nothing once the document is applied' {
+        $null = dsc agent run -p $configPath --once --mode apply-only
+        $out = dsc agent run -p $configPath --once --mode apply-only | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.action | Should -BeExactly 'none'
+        $out.mode | Should -BeExactly 'ApplyOnly'
+    }
+
+    It 'changed parameters are applied again' {
+        $parametersPath = Join-Path $root 'parameters.json'
+        Set-Content -Path $parametersPath -Value '{"parameters": {"output": "hello"}}'
+        $null = dsc agent run -p $configPath --parameters-file $parametersPath --once
+        Set-Content -Path $parametersPath -Value '{"parameters": {"output": "world"}}'
+        $out = dsc agent run -p $configPath --parameters-file $parametersPath --once | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.action | Should -BeExactly 'applied'
+    }
+
+    It 'document and mode can be set in the settings' {
+        $env:DSC_SETTINGS = @{ agent = @{ statePath = $statePath; documentPath = $configPath; mode = 'ApplyAndAutoCorrect' }; history = @{ path = $historyPath } } | ConvertTo-Json -Compress
+        $out = dsc agent run --once | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.action | Should -BeExactly 'applied'
+        $out.mode | Should -BeExactly 'ApplyAndAutoCorrect'
+    }
+
+    It 'runs are recorded in the history' {
+        $null = dsc agent run -p $configPath --once
+        $null = dsc agent run -p $configPath --once
+        $out = dsc history list -f json | ConvertFrom-Json
+        $out.Count | Should -Be 2
+        $out[0].operation | Should -BeExactly 'Set'
+        $out[1].operation | Should -BeExactly 'Test'
+    }
+
+    It 'running agent lock prevents another run' {
+        $null = New-Item -ItemType Directory -Path $statePath
+        Set-Content -Path (Join-Path $statePath 'agent.lock') -Value $PID -NoNewline
+        $null = dsc agent run -p $configPath --once 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*Agent is already running*'
+    }
+
+    It 'stale lock is taken over' {
+        $null = New-Item -ItemType Directory -Path $statePath
+        $lockPath = Join-Path $statePath 'agent.lock'
+        Set-Content -Path $lockPath -Value ([int]::MaxValue) -NoNewline
+        $out = dsc agent run -p $configPath --once 2> $null | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.action | Should -BeExactly 'applied'
+        Test-Path $lockPath | Should -BeFalse
+    }
+
+    It 'missing document is an error' {
+        $null = dsc agent run --once 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 1
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*No configuration document specified*'
+    }
+
+    It 'interval of zero is rejected' {
+        $null = dsc agent run -p $configPath --interval 0 --once 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 1
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*interval of the agent must be at least 1 second*'
+    }
+
+    It 'termination stops the agent between runs' -Skip:$IsWindows {
+        $lockPath = Join-Path $statePath 'agent.lock'
+        $process = Start-Process -FilePath (Get-Command dsc).Source -ArgumentList 'agent', 'run', '-p', $configPath, '--interval', '1' -RedirectStandardOutput $TestDrive/agent.log -RedirectStandardError $TestDrive/error.log -PassThru
+        # the state is written once the first run applied the document
+        $stateFile = Join-Path $statePath 'agent.state.json'
+        for ($i = 0; $i -lt 100 -and !(Test-Path $stateFile); $i++) {
+            Start-Sleep -Milliseconds 100
+        }
+        Test-Path $stateFile | Should -BeTrue
+        kill -TERM $process.Id
+        $process.WaitForExit(10000) | Should -BeTrue
+        $process.ExitCode | Should -Be 6
+        Test-Path $lockPath | Should -BeFalse
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*Agent stops after the current run*'
+    }
+}
@@ -0,0 +1,92 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'audit log tests' {
+    BeforeAll {
+        $oldSettings = $env:DSC_SETTINGS
+        $config = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            parameters:
+              secret:
+                type: secureString
+            resources:
+            - name: Echo
+              type: Test/Echo
+              properties:
+                output: "[parameters('secret')]"
+            - name: Exist
+              type: Test/Exist
+              properties:
+                _exist: true
+'@
+    }
+
+    BeforeEach {
+        $auditPath = Join-Path $TestDrive ([System.IO.Path]::GetRandomFileName()) 'audit.jsonl'
+        $env:DSC_SETTINGS = @{ audit = @{ path = $auditPath } } | ConvertTo-Json -Compress
+    }
+
+    AfterAll {
+        $env:DSC_SETTINGS = $oldSettings
+    }
+
+    It 'config set records each instance with secrets redacted' {
+        $null = $config | dsc config -p '{"parameters": {"secret": "mySecret"}}' set
+        $LASTEXITCODE | Should -Be 0
+        $content = Get-Content $auditPath -Raw
+        $content | Should -Not -BeLike '*mySecret*'
+        $entries = Get-Content $auditPath | ConvertFrom-Json
+        $entries.Count | Should -Be 2
+        $entries[0].type | Should -BeExactly 'Test/Echo'
+        $entries[0].name | Should -BeExactly 'Echo'
+        $entries[0].operation | Should -BeExactly 'set'
+        $entries[0].outcome | Should -BeExactly 'success'
+        $entries[0].afterState.output | Should -BeExactly '<redacted>'
+        $entries[0].documentHash | Should -Not -BeNullOrEmpty
+        $entries[0].securityContext | Should -BeIn @('Elevated', 'Restricted')
+        $entries[0].timestamp | Should -Not -BeNullOrEmpty
+        $entries[1].name | Should -BeExactly 'Exist'
+        $entries[1].documentHash | Should -BeExactly $entries[0].documentHash
+    }
+
+    It 'what-if is not recorded' {
+        $null = $config | dsc config -p '{"parameters": {"secret": "mySecret"}}' set --what-if
+        $LASTEXITCODE | Should -Be 0
+        Test-Path $auditPath | Should -BeFalse
+    }
+
+    It 'resource set and failed delete are recorded' {
+        $null = dsc resource set -r Test/Exist --input '{"_exist": true}'
+        $LASTEXITCODE | Should -Be 0
+        $null = dsc resource delete -r Test/Exist --input '{"_exist": false}' 2> $null
+        $LASTEXITCODE | Should -Be 2
+        $entries = Get-Content $auditPath | ConvertFrom-Json
+        $entries.Count | Should -Be 2
+        $entries[0].operation | Should -BeExactly 'set'
+        $entries[0].outcome | Should -BeExactly 'success'
+        $entries[0].beforeState._exist | Should -BeTrue
+        $entries[0].documentHash | Should -BeNullOrEmpty
+        $entries[1].operation | Should -BeExactly 'delete'
+        $entries[1].outcome | Should -BeExactly 'failure'
+        $entries[1].error | Should -Not -BeNullOrEmpty
+    }
+
+    It 'log is rotated at the maximum size' {
+        $env:DSC_SETTINGS = @{ audit = @{ path = $auditPath; maxSize = 600; maxFiles = 2 } } | ConvertTo-Json -Compress
+        for ($i = 0; $i -lt 6; $i++) {
+            $null = dsc resource set -r Test/Exist --input '{"_exist": true}'
+            $LASTEXITCODE | Should -Be 0
+        }
+        Test-Path "$auditPath.1" | Should -BeTrue
+        Test-Path "$auditPath.2" | Should -BeTrue
+        Test-Path "$auditPath.3" | Should -BeFalse
+        (Get-Item $auditPath).Length | Should -BeLessOrEqual 600
+    }
+
+    It 'unwritable log fails before the resource is invoked' {
+        $null = New-Item -ItemType Directory -Path $auditPath -Force
+        $null = dsc resource set -r Test/Exist --input '{"_exist": true}' 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*Audit log*'
+    }
+}
@@ -0,0 +1,142 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'checkpoint tests' {
+    BeforeAll {
+        $oldSettings = $env:DSC_SETTINGS
+        # the second resource fails until the environment variable is set, without changing the document
+        $config = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: First
+              type: Test/Echo
+              properties:
+                output: first
+            - name: Second
+              type: Test/Echo
+              properties:
+                output: "[envvar('DSC_CHECKPOINT_TEST')]"
+              dependsOn:
+              - "[resourceId('Test/Echo','First')]"
+            - name: Third
+              type: Test/Echo
+              properties:
+                output: "[reference(resourceId('Test/Echo','First')).afterState.output]"
+              dependsOn:
+              - "[resourceId('Test/Echo','Second')]"
+'@
+    }
+
+    BeforeEach {
+        $checkpointPath = Join-Path $TestDrive ([System.IO.Path]::GetRandomFileName())
+        $env:DSC_SETTINGS = @{ checkpoint = @{ path = $checkpointPath } } | ConvertTo-Json -Compress
+        $env:DSC_CHECKPOINT_TEST = $null
+    }
+
+    AfterAll {
+        $env:DSC_SETTINGS = $oldSettings
+        $env:DSC_CHECKPOINT_TEST = $null
+    }
+
+    It 'interrupted run is resumed from its checkpoint' {
+        $null = $config | dsc config set 2> $null
+        $LASTEXITCODE | Should -Be 2
+        $checkpoint = Get-ChildItem $checkpointPath -Filter '*.json' | Get-Content -Raw | ConvertFrom-Json
+        $checkpoint.results.Count | Should -Be 1
+        $checkpoint.results[0].name | Should -BeExactly 'First'
+        $checkpoint.documentHash | Should -Not -BeNullOrEmpty
+
+        $env:DSC_CHECKPOINT_TEST = 'second'
+        $out = $config | dsc config set --resume | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results.Count | Should -Be 3
+        $out.results[0].name | Should -BeExactly 'First'
+        $out.results[0].metadata.'Microsoft.DSC'.fromCheckpoint | Should -BeTrue
+        $out.results[1].result.afterState.output | Should -BeExactly 'second'
+        $out.results[1].metadata.'Microsoft.DSC'.fromCheckpoint | Should -BeNullOrEmpty
+        $out.results[2].result.afterState.output | Should -BeExactly 'first'
+        Get-ChildItem $checkpointPath | Should -BeNullOrEmpty
+    }
+
+    It 'restart discards the checkpoint' {
+        $null = $config | dsc config set 2> $null
+        $env:DSC_CHECKPOINT_TEST = 'second'
+        $out = $config | dsc config set --restart 2> $TestDrive/error.log | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results.Count | Should -Be 3
+        $out.results[0].metadata.'Microsoft.DSC'.fromCheckpoint | Should -BeNullOrEmpty
+        (Get-Content $TestDrive/error.log -Raw) | Should -Not -BeLike '*Discarded the checkpoint*'
+    }
+
+    It 'run without resume discards the checkpoint with a warning' {
+        $null = $config | dsc config set 2> $null
+        $env:DSC_CHECKPOINT_TEST = 'second'
+        $out = $config | dsc config set 2> $TestDrive/error.log | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results[0].metadata.'Microsoft.DSC'.fromCheckpoint | Should -BeNullOrEmpty
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*Discarded the checkpoint of an interrupted run with 1 completed resources and started from the beginning*'
+    }
+
+    It 'document with secure parameters does not write a checkpoint' {
+        $secureConfig = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            parameters:
+              secret:
+                type: secureString
+            resources:
+            - name: First
+              type: Test/Echo
+              properties:
+                output: "[parameters('secret')]"
+            - name: Second
+              type: Test/Echo
+              properties:
+                output: "[envvar('DSC_CHECKPOINT_TEST')]"
+              dependsOn:
+              - "[resourceId('Test/Echo','First')]"
+'@
+        $null = $secureConfig | dsc config -p '{"parameters": {"secret": "hidden"}}' set 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        Get-ChildItem $checkpointPath -ErrorAction Ignore | Should -BeNullOrEmpty
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*Checkpoints aren''t written for documents with secure parameters*'
+    }
+
+    It 'checkpoint of other parameters is not resumed' {
+        $paramConfig = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            parameters:
+              output:
+                type: string
+            resources:
+            - name: First
+              type: Test/Echo
+              properties:
+                output: "[parameters('output')]"
+            - name: Second
+              type: Test/Echo
+              properties:
+                output: "[envvar('DSC_CHECKPOINT_TEST')]"
+              dependsOn:
+              - "[resourceId('Test/Echo','First')]"
+'@
+        $null = $paramConfig | dsc config -p '{"parameters": {"output": "one"}}' set 2> $null
+        $LASTEXITCODE | Should -Be 2
+        $env:DSC_CHECKPOINT_TEST = 'second'
+        $out = $paramConfig | dsc config -p '{"parameters": {"output": "two"}}' set --resume 2> $TestDrive/error.log | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results[0].result.afterState.output | Should -BeExactly 'two'
+        $out.results[0].metadata.'Microsoft.DSC'.fromCheckpoint | Should -BeNullOrEmpty
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*No checkpoint of an interrupted run*'
+    }
+
+    It 'what-if does not write a checkpoint' {
+        $null = $config | dsc config set --what-if 2> $null
+        Test-Path $checkpointPath | Should -BeFalse
+    }
+
+    It 'resume and restart can not be combined' {
+        $null = $config | dsc config set --resume --restart 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*'--resume' cannot be used with '--restart'*"
+    }
+}
@@ -96,4 +96,185 @@ Describe 'tests for resource discovery' {
             $env:DSC_RESOURCE_PATH = $oldPath
         }
     }
+
+    It 'diagnostics report invalid manifests, duplicate resources, and failing adapters' {
+        $manifest = @'
+        {
+            "$schema": "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json",
+            "type": "Test/Duplicate",
+            "version": "VERSION",
+            "get": {
+                "executable": "dsctest"
+            }
+        }
+'@
+        $adapter = @'
+        {
+            "$schema": "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json",
+            "type": "Test/FailingAdapter",
+            "version": "1.0.0",
+            "kind": "Adapter",
+            "get": {
+                "executable": "dsctest"
+            },
+            "adapter": {
+                "list": {
+                    "executable": "dsctest",
+                    "args": ["exit-code", "--input", "{\"exitCode\": 3}"]
+                },
+                "config": "full"
+            }
+        }
+'@
+        New-Item -ItemType Directory -Path "$testdrive/first", "$testdrive/second" -Force | Out-Null
+        Set-Content -Path "$testdrive/first/duplicate.dsc.resource.json" -Value $manifest.Replace('VERSION', '1.0.0')
+        Set-Content -Path "$testdrive/second/duplicate.dsc.resource.json" -Value $manifest.Replace('VERSION', '2.0.0')
+        Set-Content -Path "$testdrive/second/invalid.dsc.resource.json" -Value '{ "type": "Test/Invalid" }'
+        Set-Content -Path "$testdrive/second/adapter.dsc.resource.json" -Value $adapter
+        $oldPath = $env:DSC_RESOURCE_PATH
+        try {
+            $env:DSC_RESOURCE_PATH = "$testdrive/first" + [System.IO.Path]::PathSeparator + "$testdrive/second"
+            $out = dsc resource list --diagnostics -f json 2> $null | ConvertFrom-Json
+            $LASTEXITCODE | Should -Be 0
+            $out.usingResourcePath | Should -BeTrue
+            $out.searchPaths.Count | Should -Be 2
+            $out.invalidManifests.Count | Should -Be 1
+            $out.invalidManifests[0].path | Should -BeLike '*invalid.dsc.resource.json'
+            $out.invalidManifests[0].error | Should -Not -BeNullOrEmpty
+            $out.duplicateResources.Count | Should -Be 1
+            $out.duplicateResources[0].type | Should -BeExactly 'Test/Duplicate'
+            $out.duplicateResources[0].effective.version | Should -BeExactly '2.0.0'
+            $out.duplicateResources[0].effective.path | Should -BeLike '*second*'
+            $out.duplicateResources[0].shadowed[0].version | Should -BeExactly '1.0.0'
+            $out.failedAdapters.Count | Should -Be 1
+            $out.failedAdapters[0].type | Should -BeExactly 'Test/FailingAdapter'
+            $out.failedAdapters[0].exitCode | Should -Be 3
+            $out.failedAdapters[0].stderr | Should -BeLike '*Exiting with code: 3*'
+
+            $out = dsc resource list 'Other/*' --diagnostics -f json 2> $null | ConvertFrom-Json
+            $out.duplicateResources.Count | Should -Be 0
+            $out.invalidManifests.Count | Should -Be 1
+        }
+        finally {
+            $env:DSC_RESOURCE_PATH = $oldPath
+        }
+    }
+
+    It 'diagnostics include the stderr of adapters failing with an exit code described by the manifest' {
+        $adapter = @'
+        {
+            "$schema": "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json",
+            "type": "Test/DescribedFailingAdapter",
+            "version": "1.0.0",
+            "kind": "Adapter",
+            "get": {
+                "executable": "dsctest"
+            },
+            "adapter": {
+                "list": {
+                    "executable": "dsctest",
+                    "args": ["exit-code", "--input", "{\"exitCode\": 3}"]
+                },
+                "config": "full"
+            },
+            "exitCodes": {
+                "0": "Success",
+                "3": "Listing failed"
+            }
+        }
+'@
+        New-Item -ItemType Directory -Path "$testdrive/described" -Force | Out-Null
+        Set-Content -Path "$testdrive/described/adapter.dsc.resource.json" -Value $adapter
+        $oldPath = $env:DSC_RESOURCE_PATH
+        try {
+            $env:DSC_RESOURCE_PATH = "$testdrive/described"
+            $out = dsc resource list --diagnostics -f json 2> $null | ConvertFrom-Json
+            $LASTEXITCODE | Should -Be 0
+            $out.failedAdapters.Count | Should -Be 1
+            $out.failedAdapters[0].exitCode | Should -Be 3
+            $out.failedAdapters[0].error | Should -BeLike '*Listing failed*'
+            $out.failedAdapters[0].stderr | Should -BeLike '*Exiting with code: 3*'
+        }
+        finally {
+            $env:DSC_RESOURCE_PATH = $oldPath
+        }
+    }
+
+    Context 'search paths' {
+        BeforeAll {
+            $manifest = @'
+            {
+                "$schema": "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json",
+                "type": "Test/Packaged",
+                "version": "VERSION",
+                "get": {
+                    "executable": "dsctest"
+                }
+            }
+'@
+            $packages = Join-Path $testdrive 'packages'
+            New-Item -ItemType Directory -Path "$packages/current/bin", "$packages/old/resource" -Force | Out-Null
+            Set-Content -Path "$packages/current/bin/packaged.dsc.resource.json" -Value $manifest.Replace('VERSION', '2.0.0')
+            Set-Content -Path "$packages/old/resource/packaged.dsc.resource.json" -Value $manifest.Replace('VERSION', '1.0.0')
+            $oldResourcePath = $env:DSC_RESOURCE_PATH
+            $env:DSC_RESOURCE_PATH = $null
+        }
+
+        AfterAll {
+            $env:DSC_RESOURCE_PATH = $oldResourcePath
+        }
+
+        It 'subdirectories are only searched when recursing' {
+            $out = dsc --search-path $packages resource list 'Test/Packaged' | ConvertFrom-Json
+            $out | Should -BeNullOrEmpty
+
+            $out = dsc --search-path $packages --recurse resource list 'Test/Packaged' | ConvertFrom-Json
+            $out.Count | Should -Be 2
+            $out[0].version | Should -BeExactly '2.0.0'
+
+            $out = dsc --search-path $packages --recurse --max-depth 1 resource list 'Test/Packaged' | ConvertFrom-Json
+            $out | Should -BeNullOrEmpty
+        }
+
+        It 'excluded directories are not searched' {
+            $out = dsc --search-path $packages --recurse --exclude old resource list 'Test/Packaged' | ConvertFrom-Json
+            $out.Count | Should -Be 1
+            $out.version | Should -BeExactly '2.0.0'
+        }
+
+        It 'PATH is not searched with --no-path' {
+            $out = dsc --search-path $packages --recurse --no-path resource list --diagnostics -f json | ConvertFrom-Json
+            $out.searchPaths | Should -HaveCount 1
+            $out = dsc --search-path $packages --recurse --no-path resource list 'Test/Echo' | ConvertFrom-Json
+            $out | Should -BeNullOrEmpty
+        }
+
+        It 'discovery uses the user settings file' {
+            $settings = Join-Path $testdrive 'settings'
+            New-Item -ItemType Directory -Path "$settings/dsc" -Force | Out-Null
+            @{ discovery = @{ searchPaths = @($packages); recurse = $true; exclude = @('*/old/*') } } | ConvertTo-Json -Depth 3 |
+                Set-Content -Path "$settings/dsc/dsc.settings.json"
+            $oldAppData = $env:APPDATA
+            $oldConfigHome = $env:XDG_CONFIG_HOME
+            try {
+                $env:APPDATA = $settings
+                $env:XDG_CONFIG_HOME = $settings
+                $out = dsc resource list 'Test/Packaged' | ConvertFrom-Json
+                $out.Count | Should -Be 1
+                $out.version | Should -BeExactly '2.0.0'
+
+                Set-Content -Path "$settings/dsc/dsc.settings.json" -Value '{ "discovery": { "recursive": true } }'
+                $null = dsc resource list 'Test/Packaged' 2> $TestDrive/error.txt
+                "$TestDrive/error.txt" | Should -FileContentMatch 'unknown field `recursive`'
+                # invalid settings are reported, resources are still discovered with the ones of the command line
+                $out = dsc --search-path $packages --recurse resource list 'Test/Packaged' 2> $null | ConvertFrom-Json
+                $LASTEXITCODE | Should -Be 0
+                $out.Count | Should -Be 2
+            }
+            finally {
+                $env:APPDATA = $oldAppData
+                $env:XDG_CONFIG_HOME = $oldConfigHome
+            }
+        }
+    }
 }
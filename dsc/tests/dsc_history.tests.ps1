@@ -0,0 +1,101 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'run history tests' {
+    BeforeAll {
+        $oldSettings = $env:DSC_SETTINGS
+        function Get-Config([string]$output, [string]$secret = '') {
+            @"
+                `$schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+                parameters:
+                  secret:
+                    type: secureString
+                    defaultValue: '$secret'
+                resources:
+                - name: Echo
+                  type: Test/Echo
+                  properties:
+                    output: $output
+                - name: Secret
+                  type: Test/Echo
+                  properties:
+                    output: "[parameters('secret')]"
+"@
+        }
+    }
+
+    BeforeEach {
+        $historyPath = Join-Path $TestDrive ([System.IO.Path]::GetRandomFileName())
+        $env:DSC_SETTINGS = @{ history = @{ path = $historyPath } } | ConvertTo-Json -Compress
+    }
+
+    AfterAll {
+        $env:DSC_SETTINGS = $oldSettings
+    }
+
+    It 'config <operation> is recorded' -TestCases @(
+        @{ operation = 'get' }
+        @{ operation = 'test' }
+        @{ operation = 'set' }
+    ) {
+        param($operation)
+
+        $null = Get-Config 'hello' | dsc config $operation
+        $LASTEXITCODE | Should -Be 0
+        $out = dsc history list -f json | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.Count | Should -Be 1
+        $out[0].operation | Should -BeExactly (Get-Culture).TextInfo.ToTitleCase($operation)
+        $out[0].resources | Should -Be 2
+        $out[0].documentHash | Should -Not -BeNullOrEmpty
+    }
+
+    It 'show returns the stored result with secrets redacted' {
+        $null = Get-Config 'hello' 'mySecret' | dsc config get
+        $out = dsc history show latest | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.result.results[0].result.actualState.output | Should -BeExactly 'hello'
+        $out.result.results[1].result.actualState.output | Should -BeExactly '<redacted>'
+        (Get-ChildItem $historyPath | Get-Content -Raw) | Should -Not -BeLike '*mySecret*'
+    }
+
+    It 'diff reports drift between two runs' {
+        $null = Get-Config 'hello' | dsc config test
+        $null = Get-Config 'world' | dsc config test
+        $runs = dsc history list -f json | ConvertFrom-Json
+        $out = dsc history diff $runs[0].id $runs[1].id | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.from | Should -BeExactly $runs[0].id
+        $out.to | Should -BeExactly $runs[1].id
+        $echo = $out.resources | Where-Object { $_.name -eq 'Echo' }
+        $echo.status | Should -BeExactly 'changed'
+        $echo.changes[0].path | Should -BeExactly '/output'
+        $echo.changes[0].before | Should -BeExactly 'hello'
+        $echo.changes[0].after | Should -BeExactly 'world'
+        ($out.resources | Where-Object { $_.name -eq 'Secret' }).status | Should -BeExactly 'unchanged'
+    }
+
+    It 'retention limit removes the oldest runs' {
+        $env:DSC_SETTINGS = @{ history = @{ path = $historyPath; maxRuns = 2 } } | ConvertTo-Json -Compress
+        for ($i = 0; $i -lt 3; $i++) {
+            $null = Get-Config "run$i" | dsc config get
+        }
+        $out = dsc history list -f json | ConvertFrom-Json
+        $out.Count | Should -Be 2
+        $latest = dsc history show $out[1].id | ConvertFrom-Json
+        $latest.result.results[0].result.actualState.output | Should -BeExactly 'run2'
+    }
+
+    It 'disabled history records nothing' {
+        $env:DSC_SETTINGS = @{ history = @{ path = $historyPath; enabled = $false } } | ConvertTo-Json -Compress
+        $null = Get-Config 'hello' | dsc config get
+        $LASTEXITCODE | Should -Be 0
+        Test-Path $historyPath | Should -BeFalse
+    }
+
+    It 'unknown run is an error' {
+        $null = dsc history show 'doesNotExist' 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Run 'doesNotExist' not found*"
+    }
+}
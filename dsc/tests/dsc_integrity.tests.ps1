@@ -0,0 +1,101 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'manifest integrity tests' {
+    BeforeAll {
+        $oldResourcePath = $env:DSC_RESOURCE_PATH
+        $oldSettings = $env:DSC_SETTINGS
+        $oldProgramData = $env:ProgramData
+        $echoManifest = (Get-Command dscecho -CommandType Application | Select-Object -First 1).Source
+        $echoManifest = Join-Path (Split-Path $echoManifest) 'dscecho.dsc.resource.json'
+        $keyBytes = [byte[]](1..32 | ForEach-Object { Get-Random -Maximum 256 })
+        [Convert]::ToBase64String($keyBytes) | Set-Content -Path $TestDrive/signing.key
+
+        # trusted keys are only read from the system settings, which tests can only redirect on Windows
+        function Set-SystemSettings($settings) {
+            $env:ProgramData = Join-Path $TestDrive ([System.IO.Path]::GetRandomFileName())
+            $null = New-Item -ItemType Directory -Path "$env:ProgramData/dsc"
+            $settings | ConvertTo-Json -Depth 3 | Set-Content -Path "$env:ProgramData/dsc/dsc.settings.json"
+        }
+    }
+
+    BeforeEach {
+        $resources = Join-Path $TestDrive ([System.IO.Path]::GetRandomFileName())
+        $null = New-Item -ItemType Directory -Path $resources
+        Copy-Item -Path $echoManifest -Destination $resources
+        $manifest = Join-Path $resources 'dscecho.dsc.resource.json'
+        $env:DSC_RESOURCE_PATH = $resources
+    }
+
+    AfterEach {
+        $env:DSC_SETTINGS = $oldSettings
+        $env:ProgramData = $oldProgramData
+    }
+
+    AfterAll {
+        $env:DSC_RESOURCE_PATH = $oldResourcePath
+    }
+
+    It 'signature covers the manifest and its executables' {
+        $out = dsc resource sign $manifest --key $TestDrive/signing.key | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.publicKey | Should -Not -BeNullOrEmpty
+        $out.executables.dscecho | Should -Not -BeNullOrEmpty
+        Test-Path "$manifest.sig" | Should -BeTrue
+    }
+
+    It 'signed manifest is loaded in enforce mode' -Skip:(!$IsWindows) {
+        $out = dsc resource sign $manifest --key $TestDrive/signing.key | ConvertFrom-Json
+        Set-SystemSettings @{ integrity = @{ mode = 'enforce'; trustedKeys = @($out.publicKey) } }
+        $out = dsc resource list 'Test/Echo' | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.type | Should -BeExactly 'Test/Echo'
+    }
+
+    It 'unsigned manifest is not loaded in enforce mode' {
+        $env:DSC_SETTINGS = '{"integrity": {"mode": "enforce"}}'
+        $out = dsc resource list 'Test/Echo' 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 0
+        $out | Should -BeNullOrEmpty
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*Signature file*not found*'
+    }
+
+    It 'manifest signed by an untrusted key is only reported in warn mode' {
+        $null = dsc resource sign $manifest --key $TestDrive/signing.key
+        $env:DSC_SETTINGS = '{"integrity": {"mode": "warn"}}'
+        $out = dsc resource list 'Test/Echo' 2> $TestDrive/error.log | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.type | Should -BeExactly 'Test/Echo'
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*is not trusted*'
+    }
+
+    It 'trusted keys of the user settings are ignored' {
+        $out = dsc resource sign $manifest --key $TestDrive/signing.key | ConvertFrom-Json
+        $env:DSC_SETTINGS = @{ integrity = @{ mode = 'enforce'; trustedKeys = @($out.publicKey) } } | ConvertTo-Json -Compress
+        $out = dsc resource list 'Test/Echo' 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 0
+        $out | Should -BeNullOrEmpty
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*Ignoring*integrity.trustedKeys*'
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*is not trusted*'
+    }
+
+    It 'modified manifest is not loaded in enforce mode' -Skip:(!$IsWindows) {
+        $out = dsc resource sign $manifest --key $TestDrive/signing.key | ConvertFrom-Json
+        Add-Content -Path $manifest -Value ' '
+        Set-SystemSettings @{ integrity = @{ mode = 'enforce'; trustedKeys = @($out.publicKey) } }
+        $out = dsc resource list 'Test/Echo' 2> $TestDrive/error.log
+        $out | Should -BeNullOrEmpty
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*Manifest was modified after it was signed*'
+    }
+
+    It 'executable with a different hash is not loaded in enforce mode' -Skip:(!$IsWindows) {
+        $out = dsc resource sign $manifest --key $TestDrive/signing.key | ConvertFrom-Json
+        $signature = Get-Content "$manifest.sig" -Raw | ConvertFrom-Json
+        $signature.executables.dscecho = '0' * 64
+        $signature | ConvertTo-Json | Set-Content -Path "$manifest.sig"
+        Set-SystemSettings @{ integrity = @{ mode = 'enforce'; trustedKeys = @($out.publicKey) } }
+        $out = dsc resource list 'Test/Echo' 2> $TestDrive/error.log
+        $out | Should -BeNullOrEmpty
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Signature doesn't match*"
+    }
+}
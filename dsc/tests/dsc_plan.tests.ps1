@@ -0,0 +1,210 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'config plan and apply tests' {
+    BeforeAll {
+        $oldPath = $env:DSC_RESOURCE_PATH
+        # the current state of the resource is the environment variable so it can change between plan and apply
+        $manifest = @'
+        {
+            "$schema": "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json",
+            "type": "Test/PlanState",
+            "version": "0.1.0",
+            "get": {
+                "executable": "pwsh",
+                "args": [
+                    "-NoLogo",
+                    "-NonInteractive",
+                    "-NoProfile",
+                    "-Command",
+                    "@{ value = [string]$env:DSC_PLAN_TEST } | ConvertTo-Json -Compress"
+                ]
+            },
+            "set": {
+                "executable": "pwsh",
+                "input": "stdin",
+                "args": [
+                    "-NoLogo",
+                    "-NonInteractive",
+                    "-NoProfile",
+                    "-Command",
+                    "[Console]::In.ReadToEnd()"
+                ],
+                "return": "state"
+            },
+            "schema": {
+                "embedded": {
+                    "$schema": "http://json-schema.org/draft-07/schema#",
+                    "$id": "https://test",
+                    "title": "test",
+                    "description": "test",
+                    "type": "object",
+                    "required": [],
+                    "additionalProperties": false,
+                    "properties": {
+                        "value": {
+                            "type": "string",
+                            "description": "test"
+                        }
+                    }
+                }
+            }
+        }
+'@
+        Set-Content -Path "$TestDrive/PlanState.dsc.resource.json" -Value $manifest
+        $env:DSC_RESOURCE_PATH = $TestDrive + [System.IO.Path]::PathSeparator + $env:PATH
+
+        $config = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            parameters:
+              value:
+                type: string
+                defaultValue: desired
+            resources:
+            - name: State
+              type: Test/PlanState
+              properties:
+                value: "[parameters('value')]"
+'@
+        $secureConfig = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            parameters:
+              secret:
+                type: secureString
+            resources:
+            - name: State
+              type: Test/PlanState
+              properties:
+                value: "[parameters('secret')]"
+'@
+    }
+
+    BeforeEach {
+        $planPath = Join-Path $TestDrive ([System.IO.Path]::GetRandomFileName())
+        $env:DSC_PLAN_TEST = 'current'
+    }
+
+    AfterAll {
+        $env:DSC_RESOURCE_PATH = $oldPath
+        $env:DSC_PLAN_TEST = $null
+    }
+
+    It 'plan captures the current state and predicted changes' {
+        $out = $config | dsc config plan | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.documentHash | Should -Not -BeNullOrEmpty
+        $out.parameters.value | Should -BeExactly 'desired'
+        $out.results.Count | Should -Be 1
+        $out.results[0].result.beforeState.value | Should -BeExactly 'current'
+        $out.results[0].result.afterState.value | Should -BeExactly 'desired'
+        $out.results[0].result.changedProperties | Should -Be @('value')
+    }
+
+    It 'plan is written to the output file and applied' {
+        $null = $config | dsc config plan -o $planPath
+        $LASTEXITCODE | Should -Be 0
+        Test-Path $planPath | Should -BeTrue
+        $out = dsc config apply $planPath | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.hadErrors | Should -BeFalse
+        $out.results[0].result.afterState.value | Should -BeExactly 'desired'
+        $out.messages | Should -BeNullOrEmpty
+    }
+
+    It 'apply refuses when the state changed since the plan' {
+        $null = $config | dsc config plan -o $planPath
+        $env:DSC_PLAN_TEST = 'other'
+        $null = dsc config apply $planPath 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*State of resources 'State' changed since the plan, nothing was applied*"
+    }
+
+    It 'apply reports the drift and applies the plan' {
+        $null = $config | dsc config plan -o $planPath
+        $env:DSC_PLAN_TEST = 'other'
+        $out = dsc config apply $planPath --on-drift report 2> $null | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results[0].result.afterState.value | Should -BeExactly 'desired'
+        $out.messages[0].level | Should -BeExactly 'Warning'
+        $out.messages[0].message | Should -BeLike 'State changed since the plan*'
+    }
+
+    It 'resources without planned changes are not set' {
+        $env:DSC_PLAN_TEST = 'desired'
+        $null = $config | dsc config plan -o $planPath
+        $out = dsc config apply $planPath | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results[0].result.changedProperties | Should -BeNullOrEmpty
+    }
+
+    It 'secure parameters are redacted in the plan and passed to apply' {
+        $null = $secureConfig | dsc config -p '{"parameters": {"secret": "hidden"}}' plan -o $planPath
+        $LASTEXITCODE | Should -Be 0
+        $plan = Get-Content $planPath -Raw
+        $plan | Should -Not -BeLike '*hidden*'
+        ($plan | ConvertFrom-Json).parameters.secret | Should -BeExactly '<redacted>'
+
+        $null = dsc config apply $planPath 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 4
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Secure parameter 'secret' is redacted in the plan and must be passed to apply*"
+
+        $out = dsc config -p '{"parameters": {"secret": "hidden"}}' apply $planPath | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.hadErrors | Should -BeFalse
+    }
+
+    It 'apply refuses parameters that differ from the plan' {
+        $null = $config | dsc config plan -o $planPath
+        $null = dsc config -p '{"parameters": {"value": "other"}}' apply $planPath 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 4
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Parameter 'value' differs from the plan*"
+    }
+
+    It 'apply refuses plans with group resources' {
+        $group = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Group
+              type: Microsoft.DSC/Group
+              properties:
+                $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+                resources:
+                - name: State
+                  type: Test/PlanState
+                  properties:
+                    value: desired
+'@
+        $null = $group | dsc config plan -o $planPath
+        $LASTEXITCODE | Should -Be 0
+        $null = dsc config apply $planPath 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Resource 'Group' is a group whose state can''t be compared to the plan, nothing was applied*"
+    }
+
+    It 'apply exits with the restart required exit code for the exit policy' {
+        $restart = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Installer
+              type: Test/RestartRequired
+              properties:
+                name: installer
+                _restartRequired: true
+'@
+        $null = $restart | dsc config plan -o $planPath
+        $LASTEXITCODE | Should -Be 0
+        $out = dsc config apply $planPath --restart-policy exit | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 7
+        $out.metadata.'Microsoft.DSC'.rebootRequired | Should -BeTrue
+    }
+
+    It 'edited plan is rejected' {
+        $null = $config | dsc config plan -o $planPath
+        $plan = Get-Content $planPath -Raw | ConvertFrom-Json
+        $plan.document.resources[0].name = 'Edited'
+        $plan | ConvertTo-Json -Depth 20 | Set-Content -Path $planPath
+        $null = dsc config apply $planPath 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 4
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*edited after the plan was created*'
+    }
+}
@@ -0,0 +1,104 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'execution policy tests' {
+    BeforeAll {
+        $oldPolicyPath = $env:DSC_POLICY_PATH
+        $policyPath = Join-Path $TestDrive 'dsc.policy.json'
+        $config = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Exist
+              type: Test/Exist
+              properties:
+                _exist: true
+            - name: Echo
+              type: Test/Echo
+              properties:
+                output: hello
+'@
+    }
+
+    BeforeEach {
+        $env:DSC_POLICY_PATH = $policyPath
+    }
+
+    AfterAll {
+        $env:DSC_POLICY_PATH = $oldPolicyPath
+    }
+
+    It 'denied resource type fails the configuration before any resource runs' {
+        @{ denyResources = @('test/ech*') } | ConvertTo-Json | Set-Content -Path $policyPath
+        $out = $config | dsc config get 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        $out | Should -BeNullOrEmpty
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Execution policy: Resource 'Test/Echo' is denied*"
+    }
+
+    It 'resource type not in the allow list is not invoked' {
+        @{ allowResources = @('Test/Exist') } | ConvertTo-Json | Set-Content -Path $policyPath
+        $out = dsc resource get -r Test/Exist --input '{"_exist": true}' | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.actualState._exist | Should -BeTrue
+        $null = dsc resource get -r Test/Echo --input '{"output": "hello"}' 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Resource 'Test/Echo' is not allowed*"
+    }
+
+    It 'denied set still allows get and what-if' {
+        @{ denySet = @('Test/Exist') } | ConvertTo-Json | Set-Content -Path $policyPath
+        $null = $config | dsc config get
+        $LASTEXITCODE | Should -Be 0
+        $null = $config | dsc config set --what-if
+        $LASTEXITCODE | Should -Be 0
+        $null = $config | dsc config set 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Changing resource 'Test/Exist' is denied*"
+        $null = dsc resource delete -r Test/Exist --input '{"_exist": false}' 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+    }
+
+    It 'executables not in the allow list are not launched' {
+        @{ allowExecutables = @('dscecho') } | ConvertTo-Json | Set-Content -Path $policyPath
+        $out = dsc resource get -r Test/Echo --input '{"output": "hello"}' | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.actualState.output | Should -BeExactly 'hello'
+        $null = dsc resource get -r Test/Exist --input '{"_exist": true}' 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Executable 'dsctest' is not allowed*"
+    }
+
+    It 'denied executables are matched by name' {
+        @{ denyExecutables = @('dsctest*') } | ConvertTo-Json | Set-Content -Path $policyPath
+        $null = dsc resource get -r Test/Exist --input '{"_exist": true}' 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Executable 'dsctest' is denied*"
+    }
+
+    It 'links are matched by the executable they point to' -Skip:$IsWindows {
+        $dsctest = (Get-Command dsctest -CommandType Application | Select-Object -First 1).Source
+        $linked = Join-Path $TestDrive 'linked'
+        $null = New-Item -ItemType Directory -Path $linked -Force
+        Copy-Item -Path (Join-Path (Split-Path $dsctest) 'dscecho.dsc.resource.json') -Destination $linked
+        # the link has the allowed name but launches another executable
+        $null = New-Item -ItemType SymbolicLink -Path "$linked/dsctest" -Target (Get-Command dsc -CommandType Application | Select-Object -First 1).Source -Force
+        @{ allowExecutables = @('dsctest') } | ConvertTo-Json | Set-Content -Path $policyPath
+        $oldResourcePath = $env:DSC_RESOURCE_PATH
+        try {
+            $env:DSC_RESOURCE_PATH = $linked
+            $null = dsc resource get -r Test/Echo --input '{"output": "hello"}' 2> $TestDrive/error.log
+            $LASTEXITCODE | Should -Be 2
+            (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Executable 'dsctest' is not allowed*"
+        }
+        finally {
+            $env:DSC_RESOURCE_PATH = $oldResourcePath
+        }
+    }
+
+    It 'invalid policy file is an error' {
+        @{ allowTypes = @('*') } | ConvertTo-Json | Set-Content -Path $policyPath
+        $null = dsc resource get -r Test/Exist --input '{"_exist": true}' 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*unknown field `allowTypes`*'
+    }
+}
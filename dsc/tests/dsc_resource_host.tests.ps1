@@ -0,0 +1,52 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'Resource host tests' {
+    It 'Operations in a configuration use the same host process' {
+        $yaml = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: First
+              type: Test/Host
+              properties:
+                output: first
+            - name: Second
+              type: Test/Host
+              properties:
+                output: second
+'@
+        $out = $yaml | dsc -l trace config get 2> "$TestDrive/tracing.txt" | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results.Count | Should -Be 2
+        $out.results[0].result.actualState.output | Should -BeExactly 'first'
+        $out.results[1].result.actualState.output | Should -BeExactly 'second'
+        $out.results[0].result.actualState.processId | Should -Be $out.results[1].result.actualState.processId
+        "$TestDrive/tracing.txt" | Should -FileContentMatchExactly "Host received 'shutdown'"
+    }
+
+    It 'Test compares the state returned by the host' {
+        $out = '{"output":"hello"}' | dsc resource test -r Test/Host | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.inDesiredState | Should -BeTrue
+        $out.actualState.output | Should -BeExactly 'hello'
+        $out.actualState.processId | Should -Not -BeNullOrEmpty
+    }
+
+    It 'Host that does not respond within the timeout fails the operation' {
+        $null = '{"output":"hello","sleep":10}' | dsc resource get -r Test/HostTimeout 2> "$TestDrive/error.txt"
+        $LASTEXITCODE | Should -Be 2
+        "$TestDrive/error.txt" | Should -FileContentMatch 'did not respond within 1 seconds'
+    }
+
+    It 'Environment variables of the method are passed with the request' {
+        $out = '{"output":"hello"}' | dsc resource get -r Test/Host | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.actualState.env | Should -BeExactly 'from manifest'
+    }
+
+    It 'Stderr of the host during a failed operation is part of the error' {
+        $null = '{"output":"hello","error":"resource is locked"}' | dsc resource get -r Test/Host 2> "$TestDrive/error.txt"
+        $LASTEXITCODE | Should -Be 2
+        "$TestDrive/error.txt" | Should -FileContentMatch 'resource is locked'
+    }
+}
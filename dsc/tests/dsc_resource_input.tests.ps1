@@ -118,3 +118,184 @@ Describe 'tests for resource input' {
         $result.$member.NumberArray | Should -BeExactly '1,2,3'
     }
 }
+
+Describe 'tests for resource input as arguments' {
+    It 'Properties are rendered using the argument templates' {
+        $json = @'
+        {
+            "name": "foo",
+            "version": "1.2",
+            "force": true,
+            "tags": ["a", "b"]
+        }
+'@
+
+        $result = $json | dsc resource get -r Test/Arguments | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $result.actualState.name | Should -BeExactly 'foo'
+        $result.actualState.version | Should -BeExactly '1.2'
+        $result.actualState.force | Should -BeTrue
+        $result.actualState.tags | Should -BeExactly @('a', 'b')
+    }
+
+    It 'Unset properties and false flags are omitted' {
+        $result = '{"name":"foo","force":false}' | dsc resource get -r Test/Arguments | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $result.actualState.name | Should -BeExactly 'foo'
+        $result.actualState.psobject.properties.name | Should -Not -Contain 'version'
+        $result.actualState.psobject.properties.name | Should -Not -Contain 'force'
+        $result.actualState.psobject.properties.name | Should -Not -Contain 'tags'
+    }
+
+    It 'Input kind args passes properties as named arguments' {
+        $result = '{"name":"foo","version":"1.2","force":true,"tags":["a","b"]}' | dsc resource set -r Test/Arguments | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $result.afterState.name | Should -BeExactly 'foo'
+        $result.afterState.version | Should -BeExactly '1.2'
+        $result.afterState.force | Should -BeTrue
+        $result.afterState.tags | Should -BeExactly @('a', 'b')
+    }
+}
+
+Describe 'tests for structured resource input as environment variables' {
+    BeforeAll {
+        $manifest = @'
+    {
+        "$schema": "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json",
+        "type": "Test/StructuredEnvVarInput",
+        "version": "0.1.0",
+        "get": {
+            "executable": "pwsh",
+            "input": "env",
+            "args": [
+                "-NoLogo",
+                "-NonInteractive",
+                "-NoProfile",
+                "-Command",
+                "@{ settings = @{ child = $env:DSC_settings__child; nested = $env:DSC_settings__grandchild__value }; items = $env:DSC_items } | ConvertTo-Json -Compress"
+            ]
+        },
+        "envInput": {
+            "prefix": "DSC_",
+            "arrayEncoding": "newline"
+        },
+        "schema": {
+            "embedded": {
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "$id": "https://test",
+                "title": "test",
+                "description": "test",
+                "type": "object",
+                "required": [],
+                "additionalProperties": false,
+                "properties": {
+                    "settings": {
+                        "type": "object"
+                    },
+                    "items": {
+                        "type": "array",
+                        "items": {
+                            "type": "string"
+                        }
+                    }
+                }
+            }
+        }
+    }
+'@
+        $oldPath = $env:DSC_RESOURCE_PATH
+        $env:DSC_RESOURCE_PATH = $TestDrive
+        Set-Content $TestDrive/StructuredEnvVarInput.dsc.resource.json -Value $manifest
+    }
+
+    AfterAll {
+        $env:DSC_RESOURCE_PATH = $oldPath
+    }
+
+    It 'Nested objects are flattened and arrays use the declared encoding' {
+        $json = @'
+        {
+            "settings": {
+                "child": "foo",
+                "grandchild": {
+                    "value": "bar"
+                }
+            },
+            "items": ["a,b", "c"]
+        }
+'@
+
+        $result = $json | dsc resource get -r Test/StructuredEnvVarInput | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $result.actualState.settings.child | Should -BeExactly 'foo'
+        $result.actualState.settings.nested | Should -BeExactly 'bar'
+        $result.actualState.items | Should -BeExactly "a,b`nc"
+    }
+}
+
+Describe 'tests for resource command environment' {
+    BeforeAll {
+        $manifest = @'
+    {
+        "$schema": "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json",
+        "type": "Test/CommandEnvironment",
+        "version": "0.1.0",
+        "get": {
+            "executable": "pwsh",
+            "args": [
+                "-NoLogo",
+                "-NonInteractive",
+                "-NoProfile",
+                "-Command",
+                "@{ directory = (Split-Path -Leaf (Get-Location).Path); declared = $env:DECLARED; secret = $env:DSC_TEST_SECRET } | ConvertTo-Json -Compress"
+            ],
+            "env": {
+                "DECLARED": "declared value"
+            },
+            "workingDirectory": "workdir"
+        },
+        "cleanEnvironment": true,
+        "schema": {
+            "embedded": {
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "$id": "https://test",
+                "title": "test",
+                "description": "test",
+                "type": "object",
+                "required": [],
+                "additionalProperties": false,
+                "properties": {
+                    "directory": {
+                        "type": "string"
+                    },
+                    "declared": {
+                        "type": "string"
+                    },
+                    "secret": {
+                        "type": ["string", "null"]
+                    }
+                }
+            }
+        }
+    }
+'@
+        $oldPath = $env:DSC_RESOURCE_PATH
+        $env:DSC_RESOURCE_PATH = $TestDrive
+        $env:DSC_TEST_SECRET = 'secret value'
+        New-Item -ItemType Directory -Path $TestDrive/workdir -Force | Out-Null
+        Set-Content $TestDrive/CommandEnvironment.dsc.resource.json -Value $manifest
+    }
+
+    AfterAll {
+        $env:DSC_RESOURCE_PATH = $oldPath
+        $env:DSC_TEST_SECRET = $null
+    }
+
+    It 'Declared variables and working directory are used in a clean environment' {
+        $result = dsc resource get -r Test/CommandEnvironment | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $result.actualState.directory | Should -BeExactly 'workdir'
+        $result.actualState.declared | Should -BeExactly 'declared value'
+        $result.actualState.secret | Should -BeNullOrEmpty
+    }
+}
@@ -0,0 +1,84 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'resource lint tests' {
+    It 'valid manifests pass the lint' {
+        $manifest = (Get-Command dscecho -CommandType Application | Select-Object -First 1).Source
+        $manifest = Join-Path (Split-Path $manifest) 'dscecho.dsc.resource.json'
+        $out = dsc resource lint $manifest | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.type | Should -BeExactly 'Test/Echo'
+        $out.valid | Should -BeTrue
+        $out.diagnostics.Count | Should -Be 0
+    }
+
+    It 'unsupported schema version is reported' {
+        @'
+        {
+            "$schema": "https://example.com/manifest.json",
+            "type": "Test/Unsupported",
+            "version": "0.1.0",
+            "get": { "executable": "dscecho" }
+        }
+'@ | Set-Content -Path $TestDrive/unsupported.dsc.resource.json
+        $out = dsc resource lint $TestDrive/unsupported.dsc.resource.json | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 5
+        $out.valid | Should -BeFalse
+        $out.diagnostics[0].level | Should -BeExactly 'error'
+        $out.diagnostics[0].rule | Should -BeExactly 'schemaVersion'
+        $out.diagnostics[0].location | Should -BeExactly '/$schema'
+    }
+
+    It 'invalid capabilities and missing executables are reported' {
+        @'
+        {
+            "$schema": "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json",
+            "type": "Test/Invalid",
+            "version": "1.0",
+            "get": { "executable": "doesNotExist" },
+            "whatIf": { "executable": "doesNotExist" }
+        }
+'@ | Set-Content -Path $TestDrive/invalid.dsc.resource.json
+        $out = dsc resource lint $TestDrive/invalid.dsc.resource.json | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 5
+        $out.valid | Should -BeFalse
+        $out.diagnostics.rule | Should -Contain 'version'
+        $out.diagnostics.rule | Should -Contain 'capabilities'
+        $out.diagnostics.rule | Should -Contain 'executable'
+    }
+
+    It 'manifests are validated against the published schema of their version' {
+        @'
+        {
+            "$schema": "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json",
+            "type": "Test/Published",
+            "version": "0.1.0",
+            "get": { "executable": "dscecho", "input": "unknown" },
+            "exitCodes": { "0": "Success", "1": { "description": "Reboot required", "semantic": "unknown" } }
+        }
+'@ | Set-Content -Path $TestDrive/published.dsc.resource.json
+        $out = dsc resource lint $TestDrive/published.dsc.resource.json | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 5
+        $out.valid | Should -BeFalse
+        $schemaDiagnostics = $out.diagnostics | Where-Object { $_.rule -eq 'manifestSchema' }
+        $schemaDiagnostics.location | Should -Contain '/get/input'
+        $schemaDiagnostics.location | Should -Contain '/exitCodes/1'
+    }
+
+    It 'directories are linted manifest by manifest' {
+        New-Item -ItemType Directory -Path $TestDrive/manifests | Out-Null
+        '{ "type": "Test/NoSchema", "version": "1.0.0", "get": { "executable": "dscecho" }, "schema": { "embedded": { "$schema": "http://json-schema.org/draft-07/schema#", "type": "object", "properties": { "name": { "type": "string" } } } } }' |
+            Set-Content -Path $TestDrive/manifests/noschema.dsc.resource.json
+        'not json' | Set-Content -Path $TestDrive/manifests/broken.dsc.resource.json
+        $out = dsc resource lint $TestDrive/manifests | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 5
+        $out.Count | Should -Be 2
+        $broken = $out | Where-Object { $_.path -like '*broken*' }
+        $broken.valid | Should -BeFalse
+        $broken.diagnostics[0].rule | Should -BeExactly 'manifest'
+        $noschema = $out | Where-Object { $_.path -like '*noschema*' }
+        $noschema.valid | Should -BeTrue
+        $noschema.diagnostics[0].level | Should -BeExactly 'warning'
+        $noschema.diagnostics[0].rule | Should -BeExactly 'schemaVersion'
+    }
+}
@@ -0,0 +1,125 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'resource package tests' {
+    BeforeAll {
+        $oldDataHome = $env:XDG_DATA_HOME
+        $oldLocalAppData = $env:LOCALAPPDATA
+        $oldResourcePath = $env:DSC_RESOURCE_PATH
+        $env:DSC_RESOURCE_PATH = $null
+
+        function New-TestPackage([string]$Path, [string]$Version) {
+            $null = New-Item -ItemType Directory -Path $Path -Force
+            @"
+            {
+                "name": "Test.Package",
+                "version": "$Version",
+                "description": "Package for tests"
+            }
+"@ | Set-Content -Path (Join-Path $Path 'dsc.package.json')
+            @'
+            {
+                "$schema": "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json",
+                "type": "Test/Packaged",
+                "version": "0.1.0",
+                "get": {
+                    "executable": "dsctest",
+                    "args": ["echo", "--input", "{\"output\": \"packaged\"}"]
+                }
+            }
+'@ | Set-Content -Path (Join-Path $Path 'packaged.dsc.resource.json')
+            $checksums = Get-ChildItem -Path $Path -File | ForEach-Object {
+                "$((Get-FileHash -Path $_.FullName -Algorithm SHA256).Hash.ToLower())  $($_.Name)"
+            }
+            $checksums | Set-Content -Path (Join-Path $Path 'checksums.sha256')
+        }
+    }
+
+    BeforeEach {
+        $store = Join-Path $TestDrive ([System.IO.Path]::GetRandomFileName())
+        $env:XDG_DATA_HOME = $store
+        $env:LOCALAPPDATA = $store
+    }
+
+    AfterAll {
+        $env:XDG_DATA_HOME = $oldDataHome
+        $env:LOCALAPPDATA = $oldLocalAppData
+        $env:DSC_RESOURCE_PATH = $oldResourcePath
+    }
+
+    It 'package directory can be installed, listed, and uninstalled' {
+        New-TestPackage -Path $TestDrive/package1 -Version '1.0.0'
+        $out = dsc resource install $TestDrive/package1 | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.name | Should -BeExactly 'Test.Package'
+        $out.version | Should -BeExactly '1.0.0'
+        $out.scope | Should -BeExactly 'user'
+        $out.resources | Should -Be @('Test/Packaged')
+
+        $out = dsc resource list --installed 'test.*' | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.name | Should -BeExactly 'Test.Package'
+
+        $out = dsc resource list 'Test/Packaged' | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.directory | Should -BeLike "$($store)*"
+
+        $null = dsc resource uninstall Test.Package
+        $LASTEXITCODE | Should -Be 0
+        dsc resource list --installed | Should -BeNullOrEmpty
+        dsc resource list 'Test/Packaged' | Should -BeNullOrEmpty
+    }
+
+    It 'versions are installed side by side from archives' {
+        New-TestPackage -Path $TestDrive/package1 -Version '1.0.0'
+        New-TestPackage -Path $TestDrive/package2 -Version '2.0.0'
+        tar -czf $TestDrive/package2.tar.gz -C $TestDrive package2
+        $null = dsc resource install $TestDrive/package1
+        $LASTEXITCODE | Should -Be 0
+        $null = dsc resource install $TestDrive/package2.tar.gz
+        $LASTEXITCODE | Should -Be 0
+
+        $out = dsc resource list --installed | ConvertFrom-Json
+        $out.Count | Should -Be 2
+        $out.version | Should -Be @('1.0.0', '2.0.0')
+
+        $null = dsc resource uninstall Test.Package --version 1.0.0
+        $LASTEXITCODE | Should -Be 0
+        $out = dsc resource list --installed | ConvertFrom-Json
+        $out.version | Should -BeExactly '2.0.0'
+    }
+
+    It 'installing an installed version requires force' {
+        New-TestPackage -Path $TestDrive/package1 -Version '1.0.0'
+        $null = dsc resource install $TestDrive/package1
+        $LASTEXITCODE | Should -Be 0
+        $null = dsc resource install $TestDrive/package1 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike '*already installed*'
+        $null = dsc resource install $TestDrive/package1 --force
+        $LASTEXITCODE | Should -Be 0
+    }
+
+    It 'package with a modified file is rejected' {
+        New-TestPackage -Path $TestDrive/modified -Version '1.0.0'
+        Add-Content -Path $TestDrive/modified/packaged.dsc.resource.json -Value ' '
+        $null = dsc resource install $TestDrive/modified 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*Checksum of file 'packaged.dsc.resource.json' doesn't match*"
+        dsc resource list --installed | Should -BeNullOrEmpty
+    }
+
+    It 'package with an unlisted file is rejected' {
+        New-TestPackage -Path $TestDrive/unlisted -Version '1.0.0'
+        Set-Content -Path $TestDrive/unlisted/extra.txt -Value 'extra'
+        $null = dsc resource install $TestDrive/unlisted 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*File 'extra.txt' is not listed*"
+    }
+
+    It 'uninstalling a package that is not installed fails' {
+        $null = dsc resource uninstall Test.Missing 2> $TestDrive/error.log
+        $LASTEXITCODE | Should -Be 2
+        (Get-Content $TestDrive/error.log -Raw) | Should -BeLike "*not installed*"
+    }
+}
@@ -0,0 +1,115 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'restart required tests' {
+    BeforeAll {
+        $oldSettings = $env:DSC_SETTINGS
+        $config = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Installer
+              type: Test/RestartRequired
+              properties:
+                name: installer
+                _restartRequired: true
+            - name: Other
+              type: Test/RestartRequired
+              properties:
+                name: other
+'@
+    }
+
+    BeforeEach {
+        $checkpointPath = Join-Path $TestDrive ([System.IO.Path]::GetRandomFileName())
+        $env:DSC_SETTINGS = @{ checkpoint = @{ path = $checkpointPath } } | ConvertTo-Json -Compress
+    }
+
+    AfterAll {
+        $env:DSC_SETTINGS = $oldSettings
+    }
+
+    It 'restart is reported in the metadata and the run continues by default' {
+        $out = $config | dsc config set | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.metadata.'Microsoft.DSC'.rebootRequired | Should -BeTrue
+        $out.results.Count | Should -Be 2
+        $out.results[0].metadata.'Microsoft.DSC'.rebootRequired | Should -BeTrue
+        $out.results[0].result.afterState._restartRequired | Should -BeTrue
+        $out.results[1].metadata.'Microsoft.DSC'.psobject.properties.name | Should -Not -Contain 'rebootRequired'
+    }
+
+    It 'exit policy completes the run and exits with the restart required exit code' {
+        $out = $config | dsc config set --restart-policy exit | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 7
+        $out.metadata.'Microsoft.DSC'.rebootRequired | Should -BeTrue
+        $out.results.Count | Should -Be 2
+    }
+
+    It 'stop policy skips the remaining resources and keeps the checkpoint' {
+        $out = $config | dsc config set --restart-policy stop 2> $TestDrive/error.log | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 7
+        $out.metadata.'Microsoft.DSC'.rebootRequired | Should -BeTrue
+        $out.results.Count | Should -Be 1
+        $out.results[0].name | Should -BeExactly 'Installer'
+        $out.messages[0].name | Should -BeExactly 'Installer'
+        $out.messages[0].level | Should -BeExactly 'Warning'
+        $out.messages[0].message | Should -BeLike '*requires a restart, 1 resources remaining*'
+        (Get-ChildItem $checkpointPath -Filter '*.json').Count | Should -Be 1
+
+        $out = $config | dsc config set --resume | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results.Count | Should -Be 2
+        $out.results[0].metadata.'Microsoft.DSC'.fromCheckpoint | Should -BeTrue
+        $out.results[1].name | Should -BeExactly 'Other'
+        $out.metadata.'Microsoft.DSC'.psobject.properties.name | Should -Not -Contain 'rebootRequired'
+    }
+
+    It 'restart of the last resource does not stop the run' {
+        $last = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Installer
+              type: Test/RestartRequired
+              properties:
+                name: installer
+                _restartRequired: true
+'@
+        $out = $last | dsc config set --restart-policy stop | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 7
+        $out.messages | Should -BeNullOrEmpty
+        Test-Path $checkpointPath | Should -BeTrue
+        Get-ChildItem $checkpointPath | Should -BeNullOrEmpty
+    }
+
+    It 'what-if does not stop or exit' {
+        $out = $config | dsc config set --what-if --restart-policy stop | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results.Count | Should -Be 2
+    }
+
+    It 'restart of a resource in a group is reported by the group' {
+        $group = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Group
+              type: Microsoft.DSC/Group
+              properties:
+                $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+                resources:
+                - name: Installer
+                  type: Test/RestartRequired
+                  properties:
+                    name: installer
+                    _restartRequired: true
+            - name: Other
+              type: Test/RestartRequired
+              properties:
+                name: other
+'@
+        $out = $group | dsc config set --restart-policy stop | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 7
+        $out.results.Count | Should -Be 1
+        $out.results[0].metadata.'Microsoft.DSC'.rebootRequired | Should -BeTrue
+        $out.results[0].result[0].metadata.'Microsoft.DSC'.rebootRequired | Should -BeTrue
+    }
+}
@@ -0,0 +1,74 @@
+# Copyright (c) Microsoft Corporation.
+# Licensed under the MIT License.
+
+Describe 'resource retry policy tests' {
+    It 'retryable exit codes enable retries recorded in the metadata' {
+        $config_yaml = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Retried
+              type: Test/ExitCode
+              properties:
+                exitCode: 0
+'@
+        $out = $config_yaml | dsc config get | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results[0].metadata.'Microsoft.DSC'.attempts | Should -Be 1
+    }
+
+    It 'instance metadata overrides the retry policy of the manifest' {
+        $config_yaml = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Overridden
+              type: Test/ExitCode
+              properties:
+                exitCode: 8
+              metadata:
+                Microsoft.DSC:
+                  retry:
+                    maxAttempts: 2
+                    delayMilliseconds: 0
+                    exitCodes: [8]
+'@
+        $config_yaml | dsc config get 2> $TestDrive/tracing.txt
+        $LASTEXITCODE | Should -Not -Be 0
+        "$TestDrive/tracing.txt" | Should -FileContentMatchExactly 'attempt 1 of 2 failed'
+        "$TestDrive/tracing.txt" | Should -Not -FileContentMatchExactly 'attempt 2 of 2 failed'
+    }
+
+    It 'failures matching a stderr pattern are retried' {
+        $config_yaml = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Pattern
+              type: Test/ExitCode
+              properties:
+                exitCode: 1
+              metadata:
+                Microsoft.DSC:
+                  retry:
+                    maxAttempts: 3
+                    backoff: exponential
+                    delayMilliseconds: 10
+                    stderrPatterns: ['Exiting with code: \d']
+'@
+        $config_yaml | dsc config test 2> $TestDrive/tracing.txt
+        $LASTEXITCODE | Should -Not -Be 0
+        "$TestDrive/tracing.txt" | Should -FileContentMatchExactly 'attempt 2 of 3 failed, retrying in 20 ms'
+    }
+
+    It 'resources without a retry policy do not record attempts' {
+        $config_yaml = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Echo
+              type: Test/Echo
+              properties:
+                output: hello
+'@
+        $out = $config_yaml | dsc config get | ConvertFrom-Json
+        $LASTEXITCODE | Should -Be 0
+        $out.results[0].metadata.'Microsoft.DSC'.psobject.properties.name | Should -Not -Contain 'attempts'
+    }
+}
@@ -33,4 +33,91 @@ Describe 'Tests for configuration security context metadata' {
             $out | Should -Not -BeNullOrEmpty
         }
     }
+
+    Context 'resource security context' {
+        BeforeAll {
+            $oldSettings = $env:DSC_SETTINGS
+            # the context the process doesn't run in, which requires a wrapper
+            $otherContext = if ($isAdmin) { 'Restricted' } else { 'Elevated' }
+            $wrapperSetting = if ($isAdmin) { 'restrictWith' } else { 'elevateWith' }
+        }
+
+        AfterEach {
+            $env:DSC_SETTINGS = $oldSettings
+        }
+
+        It 'instances requiring another context fail validation together' {
+            $config = @"
+                `$schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+                resources:
+                - name: First
+                  type: Test/Echo
+                  properties:
+                    output: first
+                  metadata:
+                    Microsoft.DSC:
+                      securityContext: $otherContext
+                - name: Second
+                  type: Test/Echo
+                  properties:
+                    output: second
+                  metadata:
+                    Microsoft.DSC:
+                      securityContext: $otherContext
+                - name: Third
+                  type: Test/Echo
+                  properties:
+                    output: third
+"@
+            $out = $config | dsc config get 2> $TestDrive/error.log
+            $LASTEXITCODE | Should -Be 2
+            $out | Should -BeNullOrEmpty
+            $errorLog = Get-Content $TestDrive/error.log -Raw
+            $errorLog | Should -BeLike "*'First'*securityContext.$wrapperSetting*"
+            $errorLog | Should -BeLike "*'Second'*securityContext.$wrapperSetting*"
+            $errorLog | Should -Not -BeLike "*'Third'*"
+        }
+
+        It 'instances requiring another context run through the configured wrapper' -Skip:$IsWindows {
+            $env:DSC_SETTINGS = @{ securityContext = @{ $wrapperSetting = @('env', 'DSC_WRAPPED=1') } } | ConvertTo-Json -Compress
+            $config = @"
+                `$schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+                resources:
+                - name: Wrapped
+                  type: Test/Echo
+                  properties:
+                    output: wrapped
+                  metadata:
+                    Microsoft.DSC:
+                      securityContext: $otherContext
+                - name: Direct
+                  type: Test/Echo
+                  properties:
+                    output: direct
+"@
+            $out = $config | dsc -l debug config get 2> $TestDrive/trace.log | ConvertFrom-Json
+            $LASTEXITCODE | Should -Be 0
+            $out.results[0].result.actualState.output | Should -BeExactly 'wrapped'
+            $out.results[1].result.actualState.output | Should -BeExactly 'direct'
+            (Get-Content $TestDrive/trace.log -Raw) | Should -BeLike "*Invoking command 'env' with args*DSC_WRAPPED=1*dscecho*"
+        }
+
+        It 'instances requiring the current context run directly' {
+            $currentContext = if ($isAdmin) { 'Elevated' } else { 'Restricted' }
+            $config = @"
+                `$schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+                resources:
+                - name: Direct
+                  type: Test/Echo
+                  properties:
+                    output: direct
+                  metadata:
+                    Microsoft.DSC:
+                      securityContext: $currentContext
+"@
+            $out = $config | dsc config get | ConvertFrom-Json
+            $LASTEXITCODE | Should -Be 0
+            $out.results[0].result.actualState.output | Should -BeExactly 'direct'
+        }
+    }
 }
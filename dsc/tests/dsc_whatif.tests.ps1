@@ -59,15 +59,61 @@ Describe 'whatif tests' {
               properties:
                 _exist: false
 "@
-        $result = $config_yaml | dsc config set -w 2>&1
-        $result | Should -Match 'ERROR.*?Not supported.*?what-if'
-        $LASTEXITCODE | Should -Be 2
+        $result = $config_yaml | dsc config set -w | ConvertFrom-Json
+        $result.metadata.'Microsoft.DSC'.executionType | Should -BeExactly 'WhatIf'
+        $result.results[0].metadata.'Microsoft.DSC'.syntheticWhatIf | Should -BeTrue
+        $result.results[0].result.afterState._exist | Should -BeFalse
+        $result.results[0].result.beforeState.deleteCalled | Should -BeTrue
+        $result.hadErrors | Should -BeFalse
+        $LASTEXITCODE | Should -Be 0
     }
 
     It 'config set whatif for group resource' {
-        $result = dsc config set -p $PSScriptRoot/../examples/groups.dsc.yaml -w 2>&1
-        $result | Should -Match 'ERROR.*?Not implemented.*?what-if'
-        $LASTEXITCODE | Should -Be 2
+        $result = dsc config set -p $PSScriptRoot/../examples/groups.dsc.yaml -w | ConvertFrom-Json
+        $result.metadata.'Microsoft.DSC'.executionType | Should -BeExactly 'WhatIf'
+        $result.results[0].metadata.'Microsoft.DSC'.syntheticWhatIf | Should -BeTrue
+        $result.results[0].result[0].name | Should -BeExactly 'First'
+        $result.results[0].result[0].result.afterState.output | Should -BeExactly 'First'
+        $result.hadErrors | Should -BeFalse
+        $LASTEXITCODE | Should -Be 0
+    }
+
+    It 'config set whatif is synthesized for resource without whatIf' {
+        $config_yaml = @"
+            `$schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2023/10/config/document.json
+            resources:
+            - name: Hello
+              type: Test/Echo
+              properties:
+                output: hello
+"@
+        $result = $config_yaml | dsc config set -w | ConvertFrom-Json
+        $result.results[0].metadata.'Microsoft.DSC'.syntheticWhatIf | Should -BeTrue
+        $result.results[0].result.afterState.output | Should -BeExactly 'hello'
+        $LASTEXITCODE | Should -Be 0
+    }
+
+    It 'config set whatif reports resource that can not be predicted' {
+        $config_yaml = @"
+            `$schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2023/10/config/document.json
+            resources:
+            - name: Absent
+              type: Test/Echo
+              properties:
+                output: absent
+                _exist: false
+            - name: Hello
+              type: Test/Echo
+              properties:
+                output: hello
+"@
+        $result = $config_yaml | dsc config set -w | ConvertFrom-Json
+        $result.results.Count | Should -Be 1
+        $result.results[0].name | Should -BeExactly 'Hello'
+        $result.messages[0].name | Should -BeExactly 'Absent'
+        $result.messages[0].level | Should -BeExactly 'Warning'
+        $result.messages[0].message | Should -Match 'What-if result could not be predicted'
+        $LASTEXITCODE | Should -Be 0
     }
 
     It 'actual execution of WhatIf resource' {
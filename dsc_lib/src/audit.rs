@@ -0,0 +1,370 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::{env, fs::{self, OpenOptions}, io::Write, path::{Path, PathBuf}};
+use tracing::{debug, trace};
+
+use crate::configure::config_doc::SecurityContextKind;
+use crate::dscerror::DscError;
+use crate::dscresources::invoke_result::SetResult;
+use crate::settings::get_settings;
+
+/// The size in bytes the audit log is rotated at when not configured.
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+/// The number of rotated audit logs kept when not configured.
+pub const DEFAULT_MAX_FILES: usize = 5;
+/// The value replacing secrets in the states written to the audit log.
+pub const REDACTED_VALUE: &str = "<redacted>";
+/// The minimum length of a secure string redacted within other strings, shorter ones are only redacted as whole values.
+pub const MIN_EMBEDDED_SECRET_LENGTH: usize = 8;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditOperation {
+    Set,
+    Delete,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// An entry of the audit log, written as a single line of JSON.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// When the operation completed, in RFC 3339 format.
+    pub timestamp: String,
+    /// The user running DSC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// The security context DSC was running in.
+    pub security_context: SecurityContextKind,
+    /// The SHA-256 of the configuration document the resource is an instance of.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document_hash: Option<String>,
+    pub operation: AuditOperation,
+    #[serde(rename = "type")]
+    pub resource_type: String,
+    /// The name of the instance in the configuration document.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_properties: Option<Vec<String>>,
+    /// The state before the operation with secrets redacted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_state: Option<Value>,
+    /// The state after the operation with secrets redacted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_state: Option<Value>,
+    pub outcome: AuditOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    /// Create a successful entry for an operation run by the current user.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The operation run.
+    /// * `resource_type` - The type of the resource the operation is run on.
+    /// * `security_context` - The security context of the process, like the one of the configuration context.
+    #[must_use]
+    pub fn new(operation: AuditOperation, resource_type: &str, security_context: SecurityContextKind) -> Self {
+        Self {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            user: ["USER", "USERNAME", "LOGNAME"].iter().find_map(|name| env::var(name).ok().filter(|user| !user.is_empty())),
+            security_context,
+            document_hash: None,
+            operation,
+            resource_type: resource_type.to_string(),
+            name: None,
+            changed_properties: None,
+            before_state: None,
+            after_state: None,
+            outcome: AuditOutcome::Success,
+            error: None,
+        }
+    }
+
+    /// Set the configuration document and instance name of the entry.
+    #[must_use]
+    pub fn with_instance(mut self, document: &str, name: &str) -> Self {
+        self.document_hash = Some(get_document_hash(document));
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Set the states and changed properties of the entry from the result of the operation.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The result of the operation.
+    /// * `secrets` - Values redacted from the states in addition to secure strings and objects.
+    #[must_use]
+    pub fn with_result(mut self, result: &SetResult, secrets: &Secrets) -> Self {
+        match result {
+            SetResult::Resource(response) => {
+                self.changed_properties.clone_from(&response.changed_properties);
+                self.before_state = Some(redact(&response.before_state, secrets));
+                self.after_state = Some(redact(&response.after_state, secrets));
+            },
+            // the nested configuration of a group audits its own resources
+            SetResult::Group(_) => {},
+        }
+        self
+    }
+
+    /// Mark the entry as failed with the error of the operation.
+    #[must_use]
+    pub fn with_error(mut self, error: &DscError) -> Self {
+        self.outcome = AuditOutcome::Failure;
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+/// Get the SHA-256 of a configuration document as used in audit entries.
+#[must_use]
+pub fn get_document_hash(document: &str) -> String {
+    format!("{:x}", Sha256::digest(document.as_bytes()))
+}
+
+/// The values of secure parameters redacted from states, like a secure parameter returned as is by a resource.
+#[derive(Debug, Default)]
+pub struct Secrets {
+    // matched against whole values only
+    values: Vec<Value>,
+    // matched within strings as well, like a password in a connection string
+    strings: Vec<String>,
+}
+
+impl Secrets {
+    /// Add the value of a secure string parameter.
+    ///
+    /// Values of at least `MIN_EMBEDDED_SECRET_LENGTH` characters are also redacted within other strings.
+    pub fn add_secure_string(&mut self, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        self.values.push(Value::String(value.to_string()));
+        if value.chars().count() >= MIN_EMBEDDED_SECRET_LENGTH {
+            self.strings.push(value.to_string());
+        }
+    }
+
+    /// Add the value of a secure object parameter, the object and each of its members are redacted as whole values.
+    pub fn add_secure_object(&mut self, value: &Value) {
+        match value {
+            Value::String(string) if string.is_empty() => {},
+            Value::Object(object) => {
+                self.values.push(value.clone());
+                for value in object.values() {
+                    self.add_secure_object(value);
+                }
+            },
+            Value::Array(array) => {
+                self.values.push(value.clone());
+                for value in array {
+                    self.add_secure_object(value);
+                }
+            },
+            Value::String(_) => self.values.push(value.clone()),
+            _ => {},
+        }
+    }
+}
+
+/// Get a copy of a state with secrets replaced by `<redacted>`.
+///
+/// Secure strings and objects passed as `{"secureString": ...}` or `{"secureObject": ...}` are
+/// redacted along with any value equal to one of the secrets and the secure strings embedded in other strings.
+#[must_use]
+pub fn redact(value: &Value, secrets: &Secrets) -> Value {
+    if secrets.values.contains(value) {
+        return Value::String(REDACTED_VALUE.to_string());
+    }
+    match value {
+        Value::Object(object) => {
+            if object.len() == 1 && (object.contains_key("secureString") || object.contains_key("secureObject")) {
+                return Value::String(REDACTED_VALUE.to_string());
+            }
+            Value::Object(object.iter().map(|(key, value)| (key.clone(), redact(value, secrets))).collect::<Map<String, Value>>())
+        },
+        Value::Array(array) => Value::Array(array.iter().map(|value| redact(value, secrets)).collect()),
+        // secrets can be embedded in strings like connection strings
+        Value::String(string) => Value::String(secrets.strings.iter().fold(string.clone(), |string, secret| string.replace(secret, REDACTED_VALUE))),
+        _ => value.clone(),
+    }
+}
+
+/// The append-only audit log of the changes made by DSC, a JSON Lines file rotated by size.
+pub struct AuditLog {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl AuditLog {
+    /// Get the audit log configured by the `audit` settings.
+    ///
+    /// The log is opened so a log that can't be written fails the operation before any change is made.
+    ///
+    /// # Returns
+    ///
+    /// `None` if no audit log is configured.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the settings are invalid or the log can't be opened.
+    pub fn from_settings() -> Result<Option<AuditLog>, DscError> {
+        let Some(settings) = get_settings()?.audit else {
+            return Ok(None);
+        };
+        let Some(path) = settings.path.filter(|path| !path.is_empty()) else {
+            return Ok(None);
+        };
+        let audit_log = AuditLog {
+            path: PathBuf::from(path),
+            max_size: settings.max_size.unwrap_or(DEFAULT_MAX_SIZE),
+            max_files: settings.max_files.unwrap_or(DEFAULT_MAX_FILES),
+        };
+        if let Some(directory) = audit_log.path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+            fs::create_dir_all(directory).map_err(|err| audit_log.error(&err))?;
+        }
+        audit_log.open()?;
+        Ok(Some(audit_log))
+    }
+
+    /// Append an entry to the audit log, rotating it first if the entry would exceed the maximum size.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the log can't be rotated or written.
+    pub fn write(&self, entry: &AuditEntry) -> Result<(), DscError> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let size = fs::metadata(&self.path).map(|metadata| metadata.len()).unwrap_or_default();
+        if size > 0 && size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        trace!("Audit entry: {line}");
+        // the entry is written with a single call so entries of concurrent processes aren't interleaved
+        self.open()?.write_all(line.as_bytes()).map_err(|err| self.error(&err))
+    }
+
+    fn open(&self) -> Result<fs::File, DscError> {
+        OpenOptions::new().create(true).append(true).open(&self.path).map_err(|err| self.error(&err))
+    }
+
+    // `audit.jsonl` becomes `audit.jsonl.1`, `audit.jsonl.1` becomes `audit.jsonl.2` and so on
+    fn rotate(&self) -> Result<(), DscError> {
+        debug!("Rotating audit log {:?}", self.path);
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path).map_err(|err| self.error(&err));
+        }
+        let oldest = get_rotated_path(&self.path, self.max_files);
+        if oldest.exists() {
+            fs::remove_file(&oldest).map_err(|err| self.error(&err))?;
+        }
+        for index in (1..self.max_files).rev() {
+            let rotated = get_rotated_path(&self.path, index);
+            if rotated.exists() {
+                fs::rename(&rotated, get_rotated_path(&self.path, index + 1)).map_err(|err| self.error(&err))?;
+            }
+        }
+        fs::rename(&self.path, get_rotated_path(&self.path, 1)).map_err(|err| self.error(&err))
+    }
+
+    fn error(&self, err: &std::io::Error) -> DscError {
+        DscError::Audit(self.path.to_string_lossy().to_string(), err.to_string())
+    }
+}
+
+fn get_rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{index}"));
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn secure_values_are_redacted() {
+        let state = json!({
+            "user": "admin",
+            "password": {"secureString": "hunter2"},
+            "token": "abc123",
+            "nested": [{"key": "abc123"}],
+            "connection": "server=db;password=s3cr3t-value",
+            "pin": "1234",
+            "code": "pin=1234",
+        });
+        let mut secrets = Secrets::default();
+        secrets.add_secure_object(&json!({"token": "abc123", "port": 443}));
+        secrets.add_secure_string("s3cr3t-value");
+        secrets.add_secure_string("1234");
+        let redacted = redact(&state, &secrets);
+        assert_eq!(redacted, json!({
+            "user": "admin",
+            "password": REDACTED_VALUE,
+            "token": REDACTED_VALUE,
+            "nested": [{"key": REDACTED_VALUE}],
+            "connection": "server=db;password=<redacted>",
+            "pin": REDACTED_VALUE,
+            "code": "pin=1234",
+        }));
+    }
+
+    #[test]
+    fn members_of_secure_objects_are_only_redacted_as_whole_values() {
+        let state = json!({
+            "port": "443",
+            "url": "https://localhost:443",
+            "enabled": "true",
+            "description": "true to its name",
+        });
+        let mut secrets = Secrets::default();
+        secrets.add_secure_object(&json!({"port": "443", "enabled": "true"}));
+        assert_eq!(redact(&state, &secrets), json!({
+            "port": REDACTED_VALUE,
+            "url": "https://localhost:443",
+            "enabled": REDACTED_VALUE,
+            "description": "true to its name",
+        }));
+    }
+
+    #[test]
+    fn log_is_rotated_at_max_size() {
+        let directory = env::temp_dir().join(format!("dsc_audit_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let audit_log = AuditLog { path: directory.join("audit.jsonl"), max_size: 400, max_files: 2 };
+        let entry = AuditEntry::new(AuditOperation::Set, "Test/Echo", SecurityContextKind::Restricted);
+        for _ in 0..8 {
+            audit_log.write(&entry).unwrap();
+        }
+        assert!(get_rotated_path(&audit_log.path, 1).is_file());
+        assert!(get_rotated_path(&audit_log.path, 2).is_file());
+        assert!(!get_rotated_path(&audit_log.path, 3).exists());
+        for path in [audit_log.path.clone(), get_rotated_path(&audit_log.path, 1)] {
+            let content = fs::read_to_string(path).unwrap();
+            assert!(content.len() <= 400);
+            for line in content.lines() {
+                assert_eq!(serde_json::from_str::<AuditEntry>(line).unwrap(), entry);
+            }
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{fs::{self, OpenOptions}, io::Write, path::{Path, PathBuf}};
+use tracing::{debug, trace};
+
+use crate::configure::config_result::ResourceSetResult;
+use crate::dscerror::DscError;
+use crate::settings::{get_settings, get_user_state_directory};
+
+/// The progress of a `set` run of a configuration document, written after each resource so an
+/// interrupted run can be resumed.
+///
+/// The outputs are kept as is so `reference()` of the remaining resources still works, the
+/// checkpoint is removed when the run completes. As the outputs and results may contain secrets,
+/// the checkpoint is only readable by its owner.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    /// The SHA-256 of the configuration document and its parameters.
+    pub document_hash: String,
+    /// When the checkpoint was last written, in RFC 3339 format.
+    pub timestamp: String,
+    /// The results of the resources completed, in the order they completed.
+    pub results: Vec<ResourceSetResult>,
+    /// The outputs of the completed resources used by `reference()`.
+    pub outputs: Map<String, Value>,
+}
+
+impl Checkpoint {
+    #[must_use]
+    pub fn new(document_hash: &str) -> Self {
+        Self {
+            document_hash: document_hash.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Get the result of a completed resource.
+    #[must_use]
+    pub fn get_result(&self, resource_type: &str, name: &str) -> Option<&ResourceSetResult> {
+        self.results.iter().find(|result| result.name == name && result.resource_type == resource_type)
+    }
+}
+
+/// The directory of the checkpoints, one per configuration document and parameters.
+pub struct CheckpointStore {
+    directory: PathBuf,
+}
+
+impl CheckpointStore {
+    /// Get the checkpoint store configured by the `checkpoint` settings.
+    ///
+    /// The store defaults to `%LOCALAPPDATA%\dsc\checkpoints` on Windows and `$XDG_STATE_HOME/dsc/checkpoints`
+    /// or `~/.local/state/dsc/checkpoints` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the settings are invalid or the store can't be located.
+    pub fn from_settings() -> Result<CheckpointStore, DscError> {
+        let settings = get_settings()?.checkpoint.unwrap_or_default();
+        let directory = if let Some(path) = settings.path.filter(|path| !path.is_empty()) {
+            PathBuf::from(path)
+        } else {
+            let Some(directory) = get_user_state_directory() else {
+                return Err(DscError::Checkpoint("Can't locate the checkpoint store".to_string()));
+            };
+            directory.join("checkpoints")
+        };
+        Ok(CheckpointStore { directory })
+    }
+
+    /// Get the checkpoint of a configuration document and its parameters.
+    ///
+    /// # Returns
+    ///
+    /// `None` if no run of the document and parameters was interrupted.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the checkpoint can't be read.
+    pub fn get(&self, document_hash: &str) -> Result<Option<Checkpoint>, DscError> {
+        let path = self.get_path(document_hash);
+        if !path.is_file() {
+            trace!("Checkpoint {path:?} not found");
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path).map_err(|err| self.error(&err))?;
+        let checkpoint: Checkpoint = serde_json::from_str(&content)
+            .map_err(|err| DscError::Checkpoint(format!("{}: {err}", path.to_string_lossy())))?;
+        Ok(Some(checkpoint))
+    }
+
+    /// Write a checkpoint, replacing the previous one of the same document and parameters.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the checkpoint can't be written.
+    pub fn save(&self, checkpoint: &Checkpoint) -> Result<(), DscError> {
+        fs::create_dir_all(&self.directory).map_err(|err| self.error(&err))?;
+        let path = self.get_path(&checkpoint.document_hash);
+        // the checkpoint is replaced by a rename so an interruption while writing doesn't corrupt it
+        let temp_path = path.with_extension("json.tmp");
+        write_private_file(&temp_path, &serde_json::to_string(checkpoint)?).map_err(|err| self.error(&err))?;
+        fs::rename(&temp_path, &path).map_err(|err| self.error(&err))?;
+        trace!("Checkpoint written with {} completed resources", checkpoint.results.len());
+        Ok(())
+    }
+
+    /// Remove the checkpoint of a configuration document and its parameters if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the checkpoint can't be removed.
+    pub fn remove(&self, document_hash: &str) -> Result<(), DscError> {
+        let path = self.get_path(document_hash);
+        if path.exists() {
+            debug!("Removing checkpoint {path:?}");
+            fs::remove_file(&path).map_err(|err| self.error(&err))?;
+        }
+        Ok(())
+    }
+
+    fn get_path(&self, document_hash: &str) -> PathBuf {
+        self.directory.join(format!("{document_hash}.json"))
+    }
+
+    fn error(&self, err: &std::io::Error) -> DscError {
+        DscError::Checkpoint(format!("{}: {err}", self.directory.to_string_lossy()))
+    }
+}
+
+// the file is created readable only by its owner, on Windows it inherits the permissions of the user state directory
+fn write_private_file(path: &Path, content: &str) -> std::io::Result<()> {
+    // a stale file would keep its permissions, so it's replaced
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(content.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dscresources::invoke_result::{ResourceSetResponse, SetResult};
+    use serde_json::json;
+
+    #[test]
+    fn checkpoint_is_saved_and_removed() {
+        let directory = std::env::temp_dir().join(format!("dsc_checkpoint_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let store = CheckpointStore { directory: directory.clone() };
+        assert_eq!(store.get("abc").unwrap(), None);
+
+        let mut checkpoint = Checkpoint::new("abc");
+        checkpoint.results.push(ResourceSetResult {
+            metadata: None,
+            name: "First".to_string(),
+            resource_type: "Test/Echo".to_string(),
+            result: SetResult::Resource(ResourceSetResponse {
+                before_state: json!({"output": "before"}),
+                after_state: json!({"output": "after"}),
+                changed_properties: Some(vec!["output".to_string()]),
+                changes: None,
+            }),
+        });
+        checkpoint.outputs.insert("Test/Echo:First".to_string(), json!({"afterState": {"output": "after"}}));
+        store.save(&checkpoint).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(store.get_path("abc")).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let saved = store.get("abc").unwrap().unwrap();
+        assert_eq!(saved, checkpoint);
+        assert!(saved.get_result("Test/Echo", "First").is_some());
+        assert!(saved.get_result("Test/Echo", "Second").is_none());
+        assert_eq!(store.get("def").unwrap(), None);
+
+        store.remove("abc").unwrap();
+        assert_eq!(store.get("abc").unwrap(), None);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -33,6 +33,18 @@ pub enum ExecutionKind {
     WhatIf,
 }
 
+/// What `set` does when a resource requires a restart to complete its changes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub enum RestartPolicy {
+    /// The remaining resources are set and the restart is only reported.
+    #[default]
+    Continue,
+    /// The remaining resources are skipped so they are set after the restart.
+    Stop,
+    /// The remaining resources are set and `dsc` exits with the restart required exit code.
+    Exit,
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 pub struct MicrosoftDscMetadata {
     /// Version of DSC
@@ -59,6 +71,18 @@ pub struct MicrosoftDscMetadata {
     /// Identifies if the operation is part of a configuration
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context: Option<ContextKind>,
+    /// Indicates the what-if result was predicted by DSC as the resource does not implement what-if
+    #[serde(rename = "syntheticWhatIf", skip_serializing_if = "Option::is_none")]
+    pub synthetic_what_if: Option<bool>,
+    /// Indicates a resource returned an exit code signalling a reboot is required to complete the operation
+    #[serde(rename = "rebootRequired", skip_serializing_if = "Option::is_none")]
+    pub reboot_required: Option<bool>,
+    /// The number of attempts of the operation when the resource has a retry policy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u32>,
+    /// Indicates the resource was completed by an interrupted run and the result is the one of its checkpoint
+    #[serde(rename = "fromCheckpoint", skip_serializing_if = "Option::is_none")]
+    pub from_checkpoint: Option<bool>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
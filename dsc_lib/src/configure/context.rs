@@ -7,12 +7,14 @@ use security_context_lib::{get_security_context, SecurityContext};
 use serde_json::Value;
 use std::collections::HashMap;
 
-use super::config_doc::{DataType, SecurityContextKind};
+use super::config_doc::{DataType, RestartPolicy, SecurityContextKind};
 
 pub struct Context {
     pub execution_type: ExecutionKind,
     pub outputs: HashMap<String, Value>, // this is used by the `reference()` function to retrieve output
     pub parameters: HashMap<String, (Value, DataType)>,
+    pub reboot_required: bool,
+    pub restart_policy: RestartPolicy,
     pub security_context: SecurityContextKind,
     _variables: HashMap<String, Value>,
     pub start_datetime: DateTime<Local>,
@@ -25,6 +27,8 @@ impl Context {
             execution_type: ExecutionKind::Actual,
             outputs: HashMap::new(),
             parameters: HashMap::new(),
+            reboot_required: false,
+            restart_policy: RestartPolicy::default(),
             security_context: match get_security_context() {
                 SecurityContext::Admin => SecurityContextKind::Elevated,
                 SecurityContext::User => SecurityContextKind::Restricted,
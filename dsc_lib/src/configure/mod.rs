@@ -1,27 +1,39 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
+use crate::audit::{get_document_hash, redact, AuditEntry, AuditLog, AuditOperation, Secrets, REDACTED_VALUE};
+use crate::checkpoint::{Checkpoint, CheckpointStore};
 use crate::configure::config_doc::{ExecutionKind, Metadata};
 use crate::configure::parameters::Input;
 use crate::dscerror::DscError;
 use crate::dscresources::{
-    {dscresource::{Capability, Invoke, get_diff}, invoke_result::{SetResult, ResourceSetResponse}},
-    invoke_result::GetResult,
-    resource_manifest::Kind,
+    {dscresource::{Capability, Invoke}, invoke_result::{SetResult, ResourceSetResponse}},
+    adapter_batch::{get_batch_adapter, invoke_batch_get, invoke_batch_set, invoke_batch_test, BatchInstance},
+    property_diff::{get_changes_using_schema, get_differences, get_differences_using_schema, get_property_names, PropertyChange},
+    command_resource::{take_exit_code_signals, ExitCodeSignals},
+    elevation::{get_security_context_wrapper, InstanceSecurityContext},
+    invoke_result::{GetResult, ResourceGetResponse, ResourceTestResponse, TestResult},
+    resource_manifest::{import_manifest, Kind, RetryPolicy},
+    retry::{get_retry_policy, invoke_with_retry},
 };
 use crate::DscResource;
 use crate::discovery::Discovery;
+use crate::history::HistoryStore;
 use crate::parser::Statement;
+use crate::policy::{check_resource_policy, check_set_policy};
+use crate::settings::{get_settings, DiscoverySettings, SecurityContextSettings};
 use self::context::Context;
-use self::config_doc::{Configuration, DataType, MicrosoftDscMetadata, Operation, SecurityContextKind};
+use self::config_doc::{Configuration, DataType, MicrosoftDscMetadata, Operation, Resource, RestartPolicy, SecurityContextKind};
 use self::depends_on::get_resource_invocation_order;
-use self::config_result::{ConfigurationExportResult, ConfigurationGetResult, ConfigurationSetResult, ConfigurationTestResult};
+use self::config_result::{ConfigurationExportResult, ConfigurationGetResult, ConfigurationSetResult, ConfigurationTestResult, MessageLevel, ResourceGetResult, ResourceMessage, ResourceSetResult, ResourceTestResult};
+use self::plan::{has_planned_changes, ConfigurationPlan, DriftAction};
 use self::contraints::{check_length, check_number_limits, check_allowed_values};
 use indicatif::ProgressStyle;
 use security_context_lib::{SecurityContext, get_security_context};
+use serde::Serialize;
 use serde_json::{Map, Value};
-use std::{collections::HashMap, mem};
-use tracing::{debug, info, trace, warn_span, Span};
+use std::{collections::{BTreeMap, HashMap}, mem};
+use tracing::{debug, info, trace, warn, warn_span, Span};
 use tracing_indicatif::span_ext::IndicatifSpanExt;
 pub mod context;
 pub mod config_doc;
@@ -29,6 +41,7 @@ pub mod config_result;
 pub mod contraints;
 pub mod depends_on;
 pub mod parameters;
+pub mod plan;
 
 pub struct Configurator {
     json: String,
@@ -36,6 +49,15 @@ pub struct Configurator {
     pub context: Context,
     discovery: Discovery,
     statement_parser: Statement,
+    checkpoint: Option<(CheckpointStore, Checkpoint)>,
+    security_context_settings: SecurityContextSettings,
+}
+
+// the result of setting an instance before it is recorded
+struct SetOutcome {
+    result: SetResult,
+    operation: AuditOperation,
+    metadata: MicrosoftDscMetadata,
 }
 
 /// Add the results of an export operation to a configuration.
@@ -44,20 +66,14 @@ pub struct Configurator {
 ///
 /// * `resource` - The resource to export.
 /// * `conf` - The configuration to add the results to.
-///
-/// # Panics
-///
-/// Doesn't panic because there is a match/Some check before `unwrap()`; false positive.
+/// * `input` - The input to the export operation.
 ///
 /// # Errors
 ///
 /// This function will return an error if the underlying resource fails.
-pub fn add_resource_export_results_to_configuration(resource: &DscResource, adapter_resource: Option<&DscResource>, conf: &mut Configuration, input: &str) -> Result<(), DscError> {
+pub fn add_resource_export_results_to_configuration(resource: &DscResource, conf: &mut Configuration, input: &str) -> Result<(), DscError> {
 
-    let export_result = match adapter_resource {
-        Some(_) => adapter_resource.unwrap().export(input)?,
-        _ => resource.export(input)?
-    };
+    let export_result = resource.export(input)?;
 
     for (i, instance) in export_result.actual_state.iter().enumerate() {
         let mut r = config_doc::Resource::new();
@@ -161,6 +177,103 @@ fn add_metadata(kind: &Kind, mut properties: Option<Map<String, Value>> ) -> Res
     Ok(serde_json::to_string(&properties)?)
 }
 
+// an instance is deleted if its `_exist` property is false
+fn is_exist(properties: Option<&Map<String, Value>>) -> bool {
+    properties.and_then(|properties| properties.get("_exist")).is_none_or(|exist| !matches!(exist, Value::Bool(false)))
+}
+
+// predict the result of deleting a resource instance from its current state without invoking `delete`
+fn predict_delete(dsc_resource: &DscResource, desired: &str) -> Result<SetResult, DscError> {
+    if !dsc_resource.capabilities.contains(&Capability::Delete) {
+        return Err(DscError::NotImplemented(format!("Resource '{}' does not support `delete` and does not handle `_exist` as false", dsc_resource.type_name)));
+    }
+
+    let GetResult::Resource(before_response) = dsc_resource.get(desired)? else {
+        return Err(DscError::NotSupported("Group resources not supported for delete".to_string()));
+    };
+    let after_state: Value = serde_json::from_str(desired)?;
+    let differences = get_differences(&after_state, &before_response.actual_state, None);
+    let changed_properties = get_property_names(differences.iter().map(|difference| difference.path.as_str()));
+    Ok(SetResult::Resource(ResourceSetResponse {
+        before_state: before_response.actual_state,
+        after_state,
+        changed_properties: if changed_properties.is_empty() { None } else { Some(changed_properties) },
+        changes: Some(differences.into_iter().map(PropertyChange::from).collect()),
+    }))
+}
+
+/// The well-known property of the state returned by `set` a resource requests a restart with.
+///
+/// Only the state returned by `set` itself is read, the state of a resource whose `set` returns nothing
+/// comes from a follow-up `get` that can't request a restart, such a resource returns an exit code
+/// with the `rebootRequired` semantic instead.
+pub const RESTART_REQUIRED_PROPERTY: &str = "_restartRequired";
+
+// the results of a group are the ones of its nested configuration, which report their own restarts
+fn is_restart_required(set_result: &SetResult) -> bool {
+    match set_result {
+        SetResult::Resource(response) => response.after_state.get(RESTART_REQUIRED_PROPERTY).and_then(Value::as_bool) == Some(true),
+        SetResult::Group(results) => results.iter().any(|result| {
+            result.metadata.as_ref().and_then(|metadata| metadata.microsoft.as_ref()).and_then(|microsoft| microsoft.reboot_required) == Some(true)
+        }),
+    }
+}
+
+// the properties of a state set by the document, other properties can change without affecting the plan
+fn select_properties(state: &Value, names: &[String]) -> Value {
+    match state {
+        Value::Object(object) => Value::Object(object.iter().filter(|(name, _)| names.contains(name)).map(|(name, value)| (name.clone(), value.clone())).collect()),
+        _ => state.clone(),
+    }
+}
+
+// the retry policy of the manifest is overridden by the `retry` in the `Microsoft.DSC` metadata of the instance
+fn get_instance_retry_policy(dsc_resource: &DscResource, resource: &Resource) -> Result<RetryPolicy, DscError> {
+    let overrides = match resource.metadata.as_ref().and_then(|metadata| metadata.get("Microsoft.DSC")).and_then(|microsoft_dsc| microsoft_dsc.get("retry")) {
+        Some(retry) => Some(serde_json::from_value::<RetryPolicy>(retry.clone())?),
+        None => None,
+    };
+    get_retry_policy(dsc_resource, overrides)
+}
+
+// the security context in the `Microsoft.DSC` metadata of an instance overrides the one of the manifest
+fn get_instance_security_context(resource: &Resource) -> Result<Option<SecurityContextKind>, DscError> {
+    match resource.metadata.as_ref().and_then(|metadata| metadata.get("Microsoft.DSC")).and_then(|microsoft_dsc| microsoft_dsc.get("securityContext")) {
+        Some(security_context) => Ok(Some(serde_json::from_value::<SecurityContextKind>(security_context.clone())?)),
+        None => Ok(None),
+    }
+}
+
+// the instance is invoked using a copy of its resource carrying its security context and the wrappers of the settings
+fn with_security_context(dsc_resource: &DscResource, resource: &Resource, settings: &SecurityContextSettings) -> Result<DscResource, DscError> {
+    let mut dsc_resource = dsc_resource.clone();
+    dsc_resource.security_context = Some(InstanceSecurityContext {
+        required: get_instance_security_context(resource)?,
+        settings: settings.clone(),
+    });
+    Ok(dsc_resource)
+}
+
+// adapted resources run the commands of their adapter, so the security context of its manifest applies
+fn check_instance_security_context(dsc_resource: &DscResource, resource: &Resource, settings: &SecurityContextSettings) -> Result<(), DscError> {
+    let manifest = match &dsc_resource.adapter.as_deref().unwrap_or(dsc_resource).manifest {
+        Some(manifest) => Some(import_manifest(manifest.clone())?),
+        None => None,
+    };
+    let required = match get_instance_security_context(resource)? {
+        Some(security_context) => security_context,
+        None => manifest.as_ref().and_then(|manifest| manifest.security_context.clone()).unwrap_or(SecurityContextKind::Current),
+    };
+    if get_security_context_wrapper(&resource.resource_type, &required, Some(settings))?.is_some() {
+        match &manifest {
+            None => return Err(DscError::SecurityContext(format!("Resource '{}' can't be run in the {required:?} security context as it doesn't run commands", resource.resource_type))),
+            Some(manifest) if manifest.host.is_some() => return Err(DscError::SecurityContext(format!("The host of resource '{}' can't be run through a security context wrapper", resource.resource_type))),
+            Some(_) => {},
+        }
+    }
+    Ok(())
+}
+
 fn check_security_context(metadata: &Option<Metadata>) -> Result<(), DscError> {
     if metadata.is_none() {
         return Ok(());
@@ -202,13 +315,29 @@ impl Configurator {
     ///
     /// This function will return an error if the configuration is invalid or the underlying discovery fails.
     pub fn new(json: &str) -> Result<Configurator, DscError> {
-        let discovery = Discovery::new()?;
+        Self::with_discovery_settings(json, DiscoverySettings::default())
+    }
+
+    /// Create a new `Configurator` instance with discovery settings taking precedence over the ones of the settings files.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration to use in JSON.
+    /// * `settings` - The discovery settings, like the ones of the command line.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the configuration is invalid or the underlying discovery fails.
+    pub fn with_discovery_settings(json: &str, settings: DiscoverySettings) -> Result<Configurator, DscError> {
+        let discovery = Discovery::with_settings(settings)?;
         let mut config = Configurator {
             json: json.to_owned(),
             config: Configuration::new(),
             context: Context::new(),
             discovery,
             statement_parser: Statement::new()?,
+            checkpoint: None,
+            security_context_settings: get_settings()?.security_context.unwrap_or_default(),
         };
         config.validate_config()?;
         Ok(config)
@@ -224,6 +353,71 @@ impl Configurator {
         &self.config
     }
 
+    /// Record the result of an operation in the history store, secure parameters are redacted.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The operation the result is of.
+    /// * `result` - The result of the operation.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<String>` - The identifier of the run, `None` if the history is disabled.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the history settings are invalid or the run can't be written.
+    pub fn record_history<T: Serialize>(&self, operation: Operation, result: &T) -> Result<Option<String>, DscError> {
+        let store = HistoryStore::from_settings()?;
+        if !store.is_enabled() {
+            return Ok(None);
+        }
+        let record = store.add(operation, &self.json, &serde_json::to_value(result)?, &self.get_secure_parameter_values())?;
+        Ok(Some(record.id))
+    }
+
+    /// Write a checkpoint after each resource of `invoke_set` so an interrupted run can be resumed.
+    ///
+    /// The checkpoint is of the document and its parameters, so this is called after the parameters are set.
+    /// It contains the outputs and results of the completed resources as is, so no checkpoint is written
+    /// for documents with secure parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `resume` - Whether the resources completed by an interrupted run are skipped, otherwise its checkpoint is discarded.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<usize>` - The number of resources completed by the interrupted run, `None` if there is none.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the checkpoint settings are invalid or the checkpoint can't be read.
+    pub fn enable_checkpoints(&mut self, resume: bool) -> Result<Option<usize>, DscError> {
+        if self.context.parameters.values().any(|(_, data_type)| matches!(data_type, DataType::SecureString | DataType::SecureObject)) {
+            warn!("Checkpoints aren't written for documents with secure parameters");
+            return Ok(None);
+        }
+        let store = CheckpointStore::from_settings()?;
+        let document_hash = self.get_checkpoint_hash()?;
+        let existing = store.get(&document_hash)?;
+        let completed = existing.as_ref().map(|checkpoint| checkpoint.results.len());
+        let checkpoint = match existing {
+            Some(checkpoint) if resume => {
+                info!("Resuming from checkpoint with {} completed resources", checkpoint.results.len());
+                self.context.outputs.extend(checkpoint.outputs.clone());
+                checkpoint
+            },
+            Some(_) => {
+                store.remove(&document_hash)?;
+                Checkpoint::new(&document_hash)
+            },
+            None => Checkpoint::new(&document_hash),
+        };
+        self.checkpoint = Some((store, checkpoint));
+        Ok(completed)
+    }
+
     /// Invoke the get operation on a resource.
     ///
     /// # Returns
@@ -235,10 +429,21 @@ impl Configurator {
     /// This function will return an error if the underlying resource fails.
     pub fn invoke_get(&mut self) -> Result<ConfigurationGetResult, DscError> {
         let mut result = ConfigurationGetResult::new();
+        // discard signals of commands invoked before the operation, like validation
+        let _ = take_exit_code_signals();
         let resources = get_resource_invocation_order(&self.config, &mut self.statement_parser, &self.context)?;
         let pb_span = get_progress_bar_span(resources.len() as u64)?;
         let pb_span_enter = pb_span.enter();
-        for resource in resources {
+        for mut batch in self.get_adapter_batches(resources)? {
+            if batch.len() > 1 || self.is_batch_adapted(&batch[0]) {
+                for resource in &batch {
+                    Span::current().pb_inc(1);
+                    pb_span.pb_set_message(format!("Get '{}'", resource.name).as_str());
+                }
+                result.results.append(&mut self.invoke_get_batch(&batch, &mut result.messages)?);
+                continue;
+            }
+            let resource = batch.remove(0);
             Span::current().pb_inc(1);
             pb_span.pb_set_message(format!("Get '{}'", resource.name).as_str());
             let properties = self.invoke_property_expressions(&resource.properties)?;
@@ -248,9 +453,13 @@ impl Configurator {
             debug!("resource_type {}", &resource.resource_type);
             let filter = add_metadata(&dsc_resource.kind, properties)?;
             trace!("filter: {filter}");
+            let retry_policy = get_instance_retry_policy(dsc_resource, &resource)?;
+            let dsc_resource = &with_security_context(dsc_resource, &resource, &self.security_context_settings)?;
             let start_datetime = chrono::Local::now();
-            let get_result = dsc_resource.get(&filter)?;
+            let (get_result, attempts) = invoke_with_retry(&retry_policy, &resource.resource_type, "get", || dsc_resource.get(&filter))?;
             let end_datetime = chrono::Local::now();
+            let signals = take_exit_code_signals();
+            let reboot_required = self.record_exit_code_signals(&signals, &resource, &mut result.messages);
             self.context.outputs.insert(format!("{}:{}", resource.resource_type, resource.name), serde_json::to_value(&get_result)?);
             let resource_result = config_result::ResourceGetResult {
                 metadata: Some(
@@ -258,6 +467,8 @@ impl Configurator {
                         microsoft: Some(
                             MicrosoftDscMetadata {
                                 duration: Some(end_datetime.signed_duration_since(start_datetime).to_string()),
+                                reboot_required,
+                                attempts: retry_policy.is_enabled().then_some(attempts),
                                 ..Default::default()
                             }
                         )
@@ -292,96 +503,84 @@ impl Configurator {
     ///
     /// This function will return an error if the underlying resource fails.
     pub fn invoke_set(&mut self, skip_test: bool) -> Result<ConfigurationSetResult, DscError> {
+        self.set_resources(skip_test, HashMap::new())
+    }
+
+    // the instances with a result in `unplanned` aren't set, their result is used as is
+    fn set_resources(&mut self, skip_test: bool, mut unplanned: HashMap<String, ResourceSetResult>) -> Result<ConfigurationSetResult, DscError> {
         let mut result = ConfigurationSetResult::new();
+        let mut audit_log = None;
+        if self.context.execution_type == ExecutionKind::Actual {
+            for resource in &self.config.resources {
+                check_set_policy(&resource.resource_type)?;
+            }
+            audit_log = AuditLog::from_settings()?;
+        }
+        // discard signals of commands invoked before the operation, like validation
+        let _ = take_exit_code_signals();
         let resources = get_resource_invocation_order(&self.config, &mut self.statement_parser, &self.context)?;
-        let pb_span = get_progress_bar_span(resources.len() as u64)?;
+        let resource_count = resources.len();
+        let pb_span = get_progress_bar_span(resource_count as u64)?;
         let pb_span_enter = pb_span.enter();
-        for resource in resources {
-            Span::current().pb_inc(1);
-            pb_span.pb_set_message(format!("Set '{}'", resource.name).as_str());
-            let properties = self.invoke_property_expressions(&resource.properties)?;
-            let Some(dsc_resource) = self.discovery.find_resource(&resource.resource_type) else {
-                return Err(DscError::ResourceNotFound(resource.resource_type));
-            };
-            debug!("resource_type {}", &resource.resource_type);
-
-            // see if the properties contains `_exist` and is false
-            let exist = match &properties {
-                Some(property_map) => {
-                    if let Some(exist) = property_map.get("_exist") {
-                        !matches!(exist, Value::Bool(false))
-                    } else {
-                        true
-                    }
-                },
-                _ => {
-                    true
+        let mut processed = 0;
+        let mut stopped = false;
+        'batches: for batch in self.get_adapter_batches(resources)? {
+            let mut pending = Vec::new();
+            for resource in batch {
+                Span::current().pb_inc(1);
+                pb_span.pb_set_message(format!("Set '{}'", resource.name).as_str());
+                if let Some(completed) = self.get_completed_result(&resource) {
+                    debug!("Resource '{}' completed by the interrupted run", resource.name);
+                    result.results.push(completed);
+                    processed += 1;
+                    continue;
                 }
-            };
+                if let Some(unplanned) = unplanned.remove(&format!("{}:{}", resource.resource_type, resource.name)) {
+                    debug!("Resource '{}' has no planned changes", resource.name);
+                    self.context.outputs.insert(format!("{}:{}", resource.resource_type, resource.name), serde_json::to_value(&unplanned.result)?);
+                    result.results.push(unplanned);
+                    processed += 1;
+                    continue;
+                }
+                pending.push(resource);
+            }
+            if pending.is_empty() {
+                continue;
+            }
 
-            let desired = add_metadata(&dsc_resource.kind, properties)?;
-            trace!("desired: {desired}");
-
-            let start_datetime;
-            let end_datetime;
-            let set_result;
-            if exist || dsc_resource.capabilities.contains(&Capability::SetHandlesExist) {
-                debug!("Resource handles _exist or _exist is true");
-                start_datetime = chrono::Local::now();
-                set_result = dsc_resource.set(&desired, skip_test, &self.context.execution_type)?;
-                end_datetime = chrono::Local::now();
-            } else if dsc_resource.capabilities.contains(&Capability::Delete) {
-                if self.context.execution_type == ExecutionKind::WhatIf {
-                    // TODO: add delete what-if support
-                    return Err(DscError::NotSupported("What-if execution not supported for delete".to_string()));
+            if let Some(instances) = self.get_set_batch_instances(&pending)? {
+                let outcomes = self.invoke_set_batch(&pending, &instances, skip_test, audit_log.as_ref(), &mut result.messages)?;
+                let mut reboot_required = false;
+                for (resource, outcome) in pending.iter().zip(outcomes) {
+                    reboot_required |= self.complete_set(resource, outcome, audit_log.as_ref(), &mut result)?;
                 }
-                debug!("Resource implements delete and _exist is false");
-                let before_result = dsc_resource.get(&desired)?;
-                start_datetime = chrono::Local::now();
-                dsc_resource.delete(&desired)?;
-                let after_result = dsc_resource.get(&desired)?;
-                // convert get result to set result
-                set_result = match before_result {
-                    GetResult::Resource(before_response) => {
-                        let GetResult::Resource(after_result) = after_result else {
-                            return Err(DscError::NotSupported("Group resources not supported for delete".to_string()))
-                        };
-                        let before_value = serde_json::to_value(&before_response.actual_state)?;
-                        let after_value = serde_json::to_value(&after_result.actual_state)?;
-                        SetResult::Resource(ResourceSetResponse {
-                            before_state: before_response.actual_state,
-                            after_state: after_result.actual_state,
-                            changed_properties: Some(get_diff(&before_value, &after_value)),
-                        })
-                    },
-                    GetResult::Group(_) => {
-                        return Err(DscError::NotSupported("Group resources not supported for delete".to_string()));
-                    },
-                };
-                end_datetime = chrono::Local::now();
-            } else {
-                return Err(DscError::NotImplemented(format!("Resource '{}' does not support `delete` and does not handle `_exist` as false", resource.resource_type)));
+                processed += pending.len();
+                if reboot_required && self.stop_for_restart(&pending[pending.len() - 1], resource_count - processed, &mut result.messages) {
+                    stopped = true;
+                    break;
+                }
+                continue;
             }
 
-            self.context.outputs.insert(format!("{}:{}", resource.resource_type, resource.name), serde_json::to_value(&set_result)?);
-            let resource_result = config_result::ResourceSetResult {
-                metadata: Some(
-                    Metadata {
-                        microsoft: Some(
-                            MicrosoftDscMetadata {
-                                duration: Some(end_datetime.signed_duration_since(start_datetime).to_string()),
-                                ..Default::default()
-                            }
-                        )
-                    }
-                ),
-                name: resource.name.clone(),
-                resource_type: resource.resource_type.clone(),
-                result: set_result,
-            };
-            result.results.push(resource_result);
+            for resource in pending {
+                processed += 1;
+                let Some(outcome) = self.invoke_set_instance(&resource, skip_test, audit_log.as_ref(), &mut result.messages)? else {
+                    continue;
+                };
+                let reboot_required = self.complete_set(&resource, outcome, audit_log.as_ref(), &mut result)?;
+                if reboot_required && self.stop_for_restart(&resource, resource_count - processed, &mut result.messages) {
+                    stopped = true;
+                    break 'batches;
+                }
+            }
         }
 
+        // the checkpoint of a stopped run is kept so it is resumed after the restart
+        if let Some((store, checkpoint)) = self.checkpoint.take() {
+            if !stopped {
+                store.remove(&checkpoint.document_hash)?;
+            }
+        }
         result.metadata = Some(
             self.get_result_metadata(Operation::Set)
         );
@@ -401,10 +600,21 @@ impl Configurator {
     /// This function will return an error if the underlying resource fails.
     pub fn invoke_test(&mut self) -> Result<ConfigurationTestResult, DscError> {
         let mut result = ConfigurationTestResult::new();
+        // discard signals of commands invoked before the operation, like validation
+        let _ = take_exit_code_signals();
         let resources = get_resource_invocation_order(&self.config, &mut self.statement_parser, &self.context)?;
         let pb_span = get_progress_bar_span(resources.len() as u64)?;
         let pb_span_enter = pb_span.enter();
-        for resource in resources {
+        for mut batch in self.get_adapter_batches(resources)? {
+            if batch.len() > 1 || self.is_batch_adapted(&batch[0]) {
+                for resource in &batch {
+                    Span::current().pb_inc(1);
+                    pb_span.pb_set_message(format!("Test '{}'", resource.name).as_str());
+                }
+                result.results.append(&mut self.invoke_test_batch(&batch, &mut result.messages)?);
+                continue;
+            }
+            let resource = batch.remove(0);
             Span::current().pb_inc(1);
             pb_span.pb_set_message(format!("Test '{}'", resource.name).as_str());
             let properties = self.invoke_property_expressions(&resource.properties)?;
@@ -414,9 +624,13 @@ impl Configurator {
             debug!("resource_type {}", &resource.resource_type);
             let expected = add_metadata(&dsc_resource.kind, properties)?;
             trace!("expected: {expected}");
+            let retry_policy = get_instance_retry_policy(dsc_resource, &resource)?;
+            let dsc_resource = &with_security_context(dsc_resource, &resource, &self.security_context_settings)?;
             let start_datetime = chrono::Local::now();
-            let test_result = dsc_resource.test(&expected)?;
+            let (test_result, attempts) = invoke_with_retry(&retry_policy, &resource.resource_type, "test", || dsc_resource.test(&expected))?;
             let end_datetime = chrono::Local::now();
+            let signals = take_exit_code_signals();
+            let reboot_required = self.record_exit_code_signals(&signals, &resource, &mut result.messages);
             self.context.outputs.insert(format!("{}:{}", resource.resource_type, resource.name), serde_json::to_value(&test_result)?);
             let resource_result = config_result::ResourceTestResult {
                 metadata: Some(
@@ -424,6 +638,8 @@ impl Configurator {
                         microsoft: Some(
                             MicrosoftDscMetadata {
                                 duration: Some(end_datetime.signed_duration_since(start_datetime).to_string()),
+                                reboot_required,
+                                attempts: retry_policy.is_enabled().then_some(attempts),
                                 ..Default::default()
                             }
                         )
@@ -444,6 +660,124 @@ impl Configurator {
         Ok(result)
     }
 
+    /// Predict the changes of the set operation, the parameters and the state of each instance are
+    /// captured so the plan can be applied later with `invoke_apply`.
+    ///
+    /// # Returns
+    ///
+    /// * `ConfigurationPlan` - The plan with secrets redacted.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying resource fails.
+    pub fn invoke_plan(&mut self) -> Result<ConfigurationPlan, DscError> {
+        self.context.execution_type = ExecutionKind::WhatIf;
+        let result = self.invoke_set(false)?;
+        let secrets = self.get_secure_parameter_values();
+        let document: Value = serde_json::from_str(&self.json)?;
+        let parameters = self.context.parameters.iter().collect::<BTreeMap<_, _>>().into_iter().map(|(name, (value, data_type))| {
+            let value = if matches!(data_type, DataType::SecureString | DataType::SecureObject) {
+                Value::String(REDACTED_VALUE.to_string())
+            } else {
+                value.clone()
+            };
+            (name.clone(), value)
+        }).collect();
+        Ok(ConfigurationPlan {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp: chrono::Local::now().to_rfc3339(),
+            document_hash: get_document_hash(&serde_json::to_string(&document)?),
+            document,
+            parameters,
+            results: serde_json::from_value(redact(&serde_json::to_value(&result.results)?, &secrets))?,
+            messages: result.messages,
+        })
+    }
+
+    /// Apply the changes of a plan created by `invoke_plan` from the same document and parameters.
+    ///
+    /// Each instance is tested first, an instance whose state changed since the plan is drift.  Only the
+    /// instances the plan changes are set.  Plans with group resources are refused as the state of their
+    /// nested instances can't be compared to the plan.
+    ///
+    /// # Arguments
+    ///
+    /// * `plan` - The plan to apply.
+    /// * `drift_action` - Whether drift refuses the whole plan or is reported.
+    ///
+    /// # Returns
+    ///
+    /// * `ConfigurationSetResult` - The result of the set operation.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the plan doesn't match the document, drift is refused or the underlying resource fails.
+    pub fn invoke_apply(&mut self, plan: &ConfigurationPlan, drift_action: DriftAction) -> Result<ConfigurationSetResult, DscError> {
+        let planned = plan.results.iter().map(|result| (format!("{}:{}", result.resource_type, result.name), result)).collect::<HashMap<_, _>>();
+        let secrets = self.get_secure_parameter_values();
+        let mut drift = Vec::new();
+        let mut unplanned = HashMap::new();
+        let resources = get_resource_invocation_order(&self.config, &mut self.statement_parser, &self.context)?;
+        for resource in &resources {
+            let key = format!("{}:{}", resource.resource_type, resource.name);
+            let Some(planned_result) = planned.get(&key) else {
+                return Err(DscError::Plan(format!("Resource '{}' is not in the plan", resource.name)));
+            };
+            let properties = self.invoke_property_expressions(&resource.properties)?;
+            // references resolve to the planned results like they did when planning
+            self.context.outputs.insert(key.clone(), serde_json::to_value(&planned_result.result)?);
+            // the drift of the nested instances of a group can't be checked, so the group isn't applied
+            let SetResult::Resource(planned_response) = &planned_result.result else {
+                return Err(DscError::Plan(format!("Resource '{}' is a group whose state can't be compared to the plan, nothing was applied", resource.name)));
+            };
+            let Some(dsc_resource) = self.discovery.find_resource(&resource.resource_type) else {
+                return Err(DscError::ResourceNotFound(resource.resource_type.clone()));
+            };
+            let names = properties.as_ref().map(|properties| properties.keys().cloned().collect::<Vec<_>>()).unwrap_or_default();
+            let desired = add_metadata(&dsc_resource.kind, properties)?;
+            let dsc_resource = &with_security_context(dsc_resource, resource, &self.security_context_settings)?;
+            let GetResult::Resource(current) = dsc_resource.get(&desired)? else {
+                return Err(DscError::NotSupported("Group resources not supported for drift".to_string()));
+            };
+            // only the properties of the document are compared, and secrets are redacted like in the plan
+            let changes = get_changes_using_schema(
+                &select_properties(&planned_response.before_state, &names),
+                &select_properties(&redact(&current.actual_state, &secrets), &names),
+                || dsc_resource.schema().ok().and_then(|schema| serde_json::from_str(&schema).ok()),
+            );
+            if !changes.is_empty() {
+                let paths = changes.iter().map(|change| change.path.as_str()).collect::<Vec<_>>().join(", ");
+                warn!("Resource '{}' changed since the plan: {paths}", resource.name);
+                drift.push(ResourceMessage {
+                    name: resource.name.clone(),
+                    resource_type: resource.resource_type.clone(),
+                    message: format!("State changed since the plan: {paths}"),
+                    level: MessageLevel::Warning,
+                });
+            }
+            if !has_planned_changes(&planned_result.result) {
+                let state = current.actual_state;
+                unplanned.insert(key, ResourceSetResult {
+                    metadata: None,
+                    name: resource.name.clone(),
+                    resource_type: resource.resource_type.clone(),
+                    result: SetResult::Resource(ResourceSetResponse::from_changes(state.clone(), state, Vec::new())),
+                });
+            }
+        }
+        if !drift.is_empty() && drift_action == DriftAction::Refuse {
+            let names = drift.iter().map(|message| format!("'{}'", message.name)).collect::<Vec<_>>().join(", ");
+            return Err(DscError::Plan(format!("State of resources {names} changed since the plan, nothing was applied")));
+        }
+
+        self.context.outputs.clear();
+        self.context.execution_type = ExecutionKind::Actual;
+        let mut result = self.set_resources(false, unplanned)?;
+        drift.append(&mut result.messages);
+        result.messages = drift;
+        Ok(result)
+    }
+
     /// Invoke the export operation on a configuration.
     ///
     /// # Returns
@@ -469,7 +803,8 @@ impl Configurator {
             };
             let input = add_metadata(&dsc_resource.kind, properties)?;
             trace!("input: {input}");
-            add_resource_export_results_to_configuration(dsc_resource, Some(dsc_resource), &mut conf, input.as_str())?;
+            let dsc_resource = &with_security_context(dsc_resource, resource, &self.security_context_settings)?;
+            add_resource_export_results_to_configuration(dsc_resource, &mut conf, input.as_str())?;
         }
 
         conf.metadata = Some(self.get_result_metadata(Operation::Export));
@@ -558,6 +893,378 @@ impl Configurator {
         Ok(())
     }
 
+    fn is_batch_adapted(&self, resource: &Resource) -> bool {
+        self.discovery.find_resource(&resource.resource_type).and_then(get_batch_adapter).is_some()
+    }
+
+    // group consecutive resources using the same adapter that accepts full configuration so they are sent
+    // to the adapter together, a resource with dependencies starts a new group so its references resolve,
+    // and so does one with another retry policy or security context as a group is a single command
+    fn get_adapter_batches(&self, resources: Vec<Resource>) -> Result<Vec<Vec<Resource>>, DscError> {
+        let mut batches: Vec<Vec<Resource>> = Vec::new();
+        let mut current = None;
+        for resource in resources {
+            let batch_key = match self.discovery.find_resource(&resource.resource_type) {
+                Some(dsc_resource) => match get_batch_adapter(dsc_resource) {
+                    Some(adapter) => Some((
+                        adapter.type_name.to_lowercase(),
+                        get_instance_retry_policy(dsc_resource, &resource)?,
+                        get_instance_security_context(&resource)?,
+                    )),
+                    None => None,
+                },
+                None => None,
+            };
+            let has_dependencies = resource.depends_on.as_ref().is_some_and(|depends_on| !depends_on.is_empty());
+            match batches.last_mut() {
+                Some(batch) if batch_key.is_some() && batch_key == current && !has_dependencies => {
+                    batch.push(resource);
+                },
+                _ => {
+                    batches.push(vec![resource]);
+                },
+            }
+            current = batch_key;
+        }
+        Ok(batches)
+    }
+
+    fn get_batch_instances(&mut self, resources: &[Resource]) -> Result<Vec<BatchInstance>, DscError> {
+        let mut instances = Vec::new();
+        for resource in resources {
+            let properties = self.invoke_property_expressions(&resource.properties)?;
+            instances.push(BatchInstance {
+                name: resource.name.clone(),
+                resource_type: resource.resource_type.clone(),
+                properties: properties.unwrap_or_default(),
+            });
+        }
+        Ok(instances)
+    }
+
+    // the instances of a batch share a single command of their adapter, so its duration, its attempts and the
+    // signals of its exit code are the ones of each instance
+    fn invoke_batch<T>(&mut self, resources: &[Resource], operation: &str, messages: &mut Vec<ResourceMessage>, mut invoke: impl FnMut(&DscResource) -> Result<T, DscError>) -> Result<(T, MicrosoftDscMetadata), DscError> {
+        let Some(dsc_resource) = self.discovery.find_resource(&resources[0].resource_type) else {
+            return Err(DscError::ResourceNotFound(resources[0].resource_type.clone()));
+        };
+        let Some(adapter) = get_batch_adapter(dsc_resource) else {
+            return Err(DscError::AdapterNotFound(dsc_resource.require_adapter.clone().unwrap_or_default()));
+        };
+        // the instances of a batch share their retry policy and security context
+        let retry_policy = get_instance_retry_policy(dsc_resource, &resources[0])?;
+        let adapter = &with_security_context(adapter, &resources[0], &self.security_context_settings)?;
+        let start_datetime = chrono::Local::now();
+        let (output, attempts) = invoke_with_retry(&retry_policy, &resources[0].resource_type, operation, || invoke(adapter))?;
+        let end_datetime = chrono::Local::now();
+        let signals = take_exit_code_signals();
+        let mut reboot_required = None;
+        for resource in resources {
+            reboot_required = self.record_exit_code_signals(&signals, resource, messages);
+        }
+        let metadata = MicrosoftDscMetadata {
+            duration: Some(end_datetime.signed_duration_since(start_datetime).to_string()),
+            reboot_required,
+            attempts: retry_policy.is_enabled().then_some(attempts),
+            ..Default::default()
+        };
+        Ok((output, metadata))
+    }
+
+    fn invoke_get_batch(&mut self, resources: &[Resource], messages: &mut Vec<ResourceMessage>) -> Result<Vec<ResourceGetResult>, DscError> {
+        let instances = self.get_batch_instances(resources)?;
+        let (states, metadata) = self.invoke_batch(resources, "get", messages, |adapter| invoke_batch_get(adapter, &instances))?;
+        let mut results = Vec::new();
+        for (resource, actual_state) in resources.iter().zip(states) {
+            let get_result = GetResult::Resource(ResourceGetResponse { actual_state });
+            self.context.outputs.insert(format!("{}:{}", resource.resource_type, resource.name), serde_json::to_value(&get_result)?);
+            results.push(ResourceGetResult {
+                metadata: Some(Metadata { microsoft: Some(metadata.clone()) }),
+                name: resource.name.clone(),
+                resource_type: resource.resource_type.clone(),
+                result: get_result,
+            });
+        }
+        Ok(results)
+    }
+
+    // the adapter returns the actual state of each instance, which is compared to its desired state using
+    // the schema of the adapted resource like a synthetic test
+    fn invoke_test_batch(&mut self, resources: &[Resource], messages: &mut Vec<ResourceMessage>) -> Result<Vec<ResourceTestResult>, DscError> {
+        let instances = self.get_batch_instances(resources)?;
+        let (states, metadata) = self.invoke_batch(resources, "test", messages, |adapter| invoke_batch_test(adapter, &instances))?;
+        let mut results = Vec::new();
+        for ((resource, instance), actual_state) in resources.iter().zip(instances).zip(states) {
+            let desired_state = Value::Object(instance.properties);
+            let differences = get_differences_using_schema(&desired_state, &actual_state, || {
+                self.discovery.find_resource(&resource.resource_type).and_then(|dsc_resource| dsc_resource.schema().ok()).and_then(|schema| serde_json::from_str(&schema).ok())
+            });
+            let test_result = TestResult::Resource(ResourceTestResponse::from_differences(desired_state, actual_state, differences));
+            self.context.outputs.insert(format!("{}:{}", resource.resource_type, resource.name), serde_json::to_value(&test_result)?);
+            results.push(ResourceTestResult {
+                metadata: Some(Metadata { microsoft: Some(metadata.clone()) }),
+                name: resource.name.clone(),
+                resource_type: resource.resource_type.clone(),
+                result: test_result,
+            });
+        }
+        Ok(results)
+    }
+
+    // set an instance on its own, `None` if its what-if result can't be predicted
+    fn invoke_set_instance(&mut self, resource: &Resource, skip_test: bool, audit_log: Option<&AuditLog>, messages: &mut Vec<ResourceMessage>) -> Result<Option<SetOutcome>, DscError> {
+        let properties = self.invoke_property_expressions(&resource.properties)?;
+        let Some(dsc_resource) = self.discovery.find_resource(&resource.resource_type) else {
+            return Err(DscError::ResourceNotFound(resource.resource_type.clone()));
+        };
+        debug!("resource_type {}", &resource.resource_type);
+
+        // see if the properties contains `_exist` and is false
+        let exist = is_exist(properties.as_ref());
+        let desired = add_metadata(&dsc_resource.kind, properties)?;
+        trace!("desired: {desired}");
+
+        let uses_set = exist || dsc_resource.capabilities.contains(&Capability::SetHandlesExist);
+        // the `whatIf` method of a resource only simulates `set`, so delete is always predicted
+        let synthetic_what_if = self.context.execution_type == ExecutionKind::WhatIf
+            && !(uses_set && dsc_resource.capabilities.contains(&Capability::WhatIf));
+
+        let retry_policy = get_instance_retry_policy(dsc_resource, resource)?;
+        let dsc_resource = &with_security_context(dsc_resource, resource, &self.security_context_settings)?;
+        let mut attempts = 1;
+        let mut operation = AuditOperation::Set;
+        let start_datetime;
+        let set_result;
+        if synthetic_what_if {
+            debug!("Synthesizing what-if result for resource '{}'", resource.name);
+            start_datetime = chrono::Local::now();
+            let prediction = if uses_set {
+                dsc_resource.set(&desired, false, &ExecutionKind::WhatIf)
+            } else {
+                predict_delete(dsc_resource, &desired)
+            };
+            set_result = match prediction {
+                Ok(set_result) => set_result,
+                Err(err) => {
+                    let message = format!("What-if result could not be predicted: {err}");
+                    warn!("Resource '{}': {message}", resource.name);
+                    messages.push(ResourceMessage {
+                        name: resource.name.clone(),
+                        resource_type: resource.resource_type.clone(),
+                        message,
+                        level: MessageLevel::Warning,
+                    });
+                    return Ok(None);
+                }
+            };
+        } else if uses_set {
+            debug!("Resource handles _exist or _exist is true");
+            start_datetime = chrono::Local::now();
+            let set = invoke_with_retry(&retry_policy, &resource.resource_type, "set", || dsc_resource.set(&desired, skip_test, &self.context.execution_type));
+            (set_result, attempts) = self.audit_failure(audit_log, resource, AuditOperation::Set, set)?;
+        } else if dsc_resource.capabilities.contains(&Capability::Delete) {
+            debug!("Resource implements delete and _exist is false");
+            let (before_result, _) = invoke_with_retry(&retry_policy, &resource.resource_type, "get", || dsc_resource.get(&desired))?;
+            start_datetime = chrono::Local::now();
+            operation = AuditOperation::Delete;
+            let delete = invoke_with_retry(&retry_policy, &resource.resource_type, "delete", || dsc_resource.delete(&desired));
+            ((), attempts) = self.audit_failure(audit_log, resource, AuditOperation::Delete, delete)?;
+            let (after_result, _) = invoke_with_retry(&retry_policy, &resource.resource_type, "get", || dsc_resource.get(&desired))?;
+            // convert get result to set result
+            set_result = match before_result {
+                GetResult::Resource(before_response) => {
+                    let GetResult::Resource(after_result) = after_result else {
+                        return Err(DscError::NotSupported("Group resources not supported for delete".to_string()))
+                    };
+                    let changes = get_changes_using_schema(&before_response.actual_state, &after_result.actual_state, || {
+                        dsc_resource.schema().ok().and_then(|schema| serde_json::from_str(&schema).ok())
+                    });
+                    SetResult::Resource(ResourceSetResponse::from_changes(before_response.actual_state, after_result.actual_state, changes))
+                },
+                GetResult::Group(_) => {
+                    return Err(DscError::NotSupported("Group resources not supported for delete".to_string()));
+                },
+            };
+        } else {
+            return Err(DscError::NotImplemented(format!("Resource '{}' does not support `delete` and does not handle `_exist` as false", resource.resource_type)));
+        }
+        let end_datetime = chrono::Local::now();
+        let retry_enabled = retry_policy.is_enabled();
+
+        let signals = take_exit_code_signals();
+        let reboot_required = self.record_exit_code_signals(&signals, resource, messages);
+        Ok(Some(SetOutcome {
+            result: set_result,
+            operation,
+            metadata: MicrosoftDscMetadata {
+                duration: Some(end_datetime.signed_duration_since(start_datetime).to_string()),
+                synthetic_what_if: if synthetic_what_if { Some(true) } else { None },
+                reboot_required,
+                attempts: (retry_enabled && !synthetic_what_if).then_some(attempts),
+                ..Default::default()
+            },
+        }))
+    }
+
+    // the instances of a batch are set together unless one is deleted or the adapter can't predict their changes
+    fn get_set_batch_instances(&mut self, resources: &[Resource]) -> Result<Option<Vec<BatchInstance>>, DscError> {
+        if !self.is_batch_adapted(&resources[0]) {
+            return Ok(None);
+        }
+        if self.context.execution_type == ExecutionKind::WhatIf
+            && !self.discovery.find_resource(&resources[0].resource_type).is_some_and(|dsc_resource| dsc_resource.capabilities.contains(&Capability::WhatIf)) {
+            debug!("Adapter of resource '{}' doesn't implement what-if, its instances are predicted one at a time", resources[0].resource_type);
+            return Ok(None);
+        }
+        let instances = self.get_batch_instances(resources)?;
+        if !instances.iter().all(|instance| is_exist(Some(&instance.properties))) {
+            debug!("Instances of resource '{}' are deleted one at a time", resources[0].resource_type);
+            return Ok(None);
+        }
+        Ok(Some(instances))
+    }
+
+    fn invoke_set_batch(&mut self, resources: &[Resource], instances: &[BatchInstance], skip_test: bool, audit_log: Option<&AuditLog>, messages: &mut Vec<ResourceMessage>) -> Result<Vec<SetOutcome>, DscError> {
+        let execution_type = self.context.execution_type.clone();
+        let set = self.invoke_batch(resources, "set", messages, |adapter| invoke_batch_set(adapter, instances, skip_test, &execution_type));
+        if let (Some(audit_log), Err(err)) = (audit_log, &set) {
+            for resource in resources {
+                self.write_audit_failure(audit_log, resource, AuditOperation::Set, err);
+            }
+        }
+        let (states, metadata) = set?;
+        let mut outcomes = Vec::new();
+        for (resource, (before_state, after_state)) in resources.iter().zip(states) {
+            let changes = get_changes_using_schema(&before_state, &after_state, || {
+                self.discovery.find_resource(&resource.resource_type).and_then(|dsc_resource| dsc_resource.schema().ok()).and_then(|schema| serde_json::from_str(&schema).ok())
+            });
+            outcomes.push(SetOutcome {
+                result: SetResult::Resource(ResourceSetResponse::from_changes(before_state, after_state, changes)),
+                operation: AuditOperation::Set,
+                metadata: metadata.clone(),
+            });
+        }
+        Ok(outcomes)
+    }
+
+    // record the result of setting an instance, returns whether the instance requires a restart
+    fn complete_set(&mut self, resource: &Resource, outcome: SetOutcome, audit_log: Option<&AuditLog>, result: &mut ConfigurationSetResult) -> Result<bool, DscError> {
+        let SetOutcome { result: set_result, operation, mut metadata } = outcome;
+        if let Some(audit_log) = audit_log {
+            let entry = AuditEntry::new(operation, &resource.resource_type, self.context.security_context.clone())
+                .with_instance(&self.json, &resource.name)
+                .with_result(&set_result, &self.get_secure_parameter_values());
+            audit_log.write(&entry)?;
+        }
+
+        if is_restart_required(&set_result) {
+            self.context.reboot_required = true;
+            metadata.reboot_required = Some(true);
+        }
+        let reboot_required = metadata.reboot_required == Some(true);
+        self.context.outputs.insert(format!("{}:{}", resource.resource_type, resource.name), serde_json::to_value(&set_result)?);
+        let resource_result = config_result::ResourceSetResult {
+            metadata: Some(
+                Metadata {
+                    microsoft: Some(metadata)
+                }
+            ),
+            name: resource.name.clone(),
+            resource_type: resource.resource_type.clone(),
+            result: set_result,
+        };
+        self.write_checkpoint(&resource_result)?;
+        result.results.push(resource_result);
+        Ok(reboot_required)
+    }
+
+    // with the stop policy a run stops after an instance requiring a restart
+    fn stop_for_restart(&self, resource: &Resource, remaining: usize, messages: &mut Vec<ResourceMessage>) -> bool {
+        if remaining == 0 || self.context.restart_policy != RestartPolicy::Stop || self.context.execution_type != ExecutionKind::Actual {
+            return false;
+        }
+        let message = format!("Run stopped as the resource requires a restart, {remaining} resources remaining");
+        warn!("Resource '{}': {message}", resource.name);
+        messages.push(ResourceMessage {
+            name: resource.name.clone(),
+            resource_type: resource.resource_type.clone(),
+            message,
+            level: MessageLevel::Warning,
+        });
+        true
+    }
+
+    // the checkpoint is of the parameters with their defaults, sorted so the hash is stable
+    fn get_checkpoint_hash(&self) -> Result<String, DscError> {
+        let parameters = self.context.parameters.iter().map(|(name, (value, _))| (name, value)).collect::<BTreeMap<_, _>>();
+        Ok(get_document_hash(&format!("{}\n{}", self.json, serde_json::to_string(&parameters)?)))
+    }
+
+    fn get_completed_result(&self, resource: &Resource) -> Option<config_result::ResourceSetResult> {
+        let (_, checkpoint) = self.checkpoint.as_ref()?;
+        let mut completed = checkpoint.get_result(&resource.resource_type, &resource.name)?.clone();
+        let metadata = completed.metadata.get_or_insert(Metadata { microsoft: None });
+        metadata.microsoft.get_or_insert_with(MicrosoftDscMetadata::default).from_checkpoint = Some(true);
+        Some(completed)
+    }
+
+    fn write_checkpoint(&mut self, resource_result: &config_result::ResourceSetResult) -> Result<(), DscError> {
+        let Some((store, checkpoint)) = &mut self.checkpoint else {
+            return Ok(());
+        };
+        checkpoint.timestamp = chrono::Local::now().to_rfc3339();
+        checkpoint.results.push(resource_result.clone());
+        checkpoint.outputs = self.context.outputs.iter().map(|(key, value)| (key.clone(), value.clone())).collect();
+        store.save(checkpoint)
+    }
+
+    // a failed change is recorded in the audit log before its error is returned
+    fn audit_failure<T>(&self, audit_log: Option<&AuditLog>, resource: &Resource, operation: AuditOperation, result: Result<T, DscError>) -> Result<T, DscError> {
+        if let (Some(audit_log), Err(err)) = (audit_log, &result) {
+            self.write_audit_failure(audit_log, resource, operation, err);
+        }
+        result
+    }
+
+    fn write_audit_failure(&self, audit_log: &AuditLog, resource: &Resource, operation: AuditOperation, err: &DscError) {
+        let entry = AuditEntry::new(operation, &resource.resource_type, self.context.security_context.clone())
+            .with_instance(&self.json, &resource.name)
+            .with_error(err);
+        if let Err(audit_err) = audit_log.write(&entry) {
+            warn!("{audit_err}");
+        }
+    }
+
+    // the values of secure parameters are redacted from the audit log even if a resource returns them as plain values
+    fn get_secure_parameter_values(&self) -> Secrets {
+        let mut secrets = Secrets::default();
+        for (value, data_type) in self.context.parameters.values() {
+            match (data_type, value) {
+                (DataType::SecureString, Value::String(value)) => secrets.add_secure_string(value),
+                (DataType::SecureString | DataType::SecureObject, value) => secrets.add_secure_object(value),
+                _ => {},
+            }
+        }
+        secrets
+    }
+
+    // the signals of the exit codes returned by the commands of a resource are added to its result
+    fn record_exit_code_signals(&mut self, signals: &ExitCodeSignals, resource: &Resource, messages: &mut Vec<ResourceMessage>) -> Option<bool> {
+        for warning in &signals.warnings {
+            messages.push(ResourceMessage {
+                name: resource.name.clone(),
+                resource_type: resource.resource_type.clone(),
+                message: warning.clone(),
+                level: MessageLevel::Warning,
+            });
+        }
+        if signals.reboot_required {
+            self.context.reboot_required = true;
+            return Some(true);
+        }
+        None
+    }
+
     fn get_result_metadata(&self, operation: Operation) -> Metadata {
         let end_datetime = chrono::Local::now();
         Metadata {
@@ -571,6 +1278,10 @@ impl Configurator {
                     end_datetime: Some(end_datetime.to_rfc3339()),
                     duration: Some(end_datetime.signed_duration_since(self.context.start_datetime).to_string()),
                     security_context: Some(self.context.security_context.clone()),
+                    synthetic_what_if: None,
+                    reboot_required: if self.context.reboot_required { Some(true) } else { None },
+                    attempts: None,
+                    from_checkpoint: None,
                 }
             )
         }
@@ -615,6 +1326,20 @@ impl Configurator {
         // Perform discovery of resources used in config
         let required_resources = config.resources.iter().map(|p| p.resource_type.clone()).collect::<Vec<String>>();
         self.discovery.find_resources(&required_resources);
+
+        // resources not allowed by the execution policy fail the configuration before any of them is invoked
+        let mut security_context_errors = Vec::new();
+        for resource in &config.resources {
+            if let Some(dsc_resource) = self.discovery.find_resource(&resource.resource_type) {
+                check_resource_policy(dsc_resource)?;
+                if let Err(err) = check_instance_security_context(dsc_resource, resource, &self.security_context_settings) {
+                    security_context_errors.push(format!("'{}': {err}", resource.name));
+                }
+            }
+        }
+        if !security_context_errors.is_empty() {
+            return Err(DscError::SecurityContext(format!("Instances can't be run in their required security context:\n{}", security_context_errors.join("\n"))));
+        }
         self.config = config;
         Ok(())
     }
@@ -0,0 +1,149 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::audit::{get_document_hash, REDACTED_VALUE};
+use crate::configure::config_doc::{Configuration, DataType};
+use crate::configure::config_result::{ResourceMessage, ResourceSetResult};
+use crate::dscerror::DscError;
+use crate::dscresources::invoke_result::SetResult;
+
+/// The changes `set` would make to a configuration document, captured by `config plan` and applied by `config apply`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ConfigurationPlan {
+    /// The version of DSC that created the plan.
+    pub version: String,
+    /// When the plan was created, in RFC 3339 format.
+    pub timestamp: String,
+    /// The SHA-256 of the document, a check against a plan corrupted or edited by mistake after it was created.
+    /// The plan isn't signed, so anyone able to write it can change the document and its hash together.
+    pub document_hash: String,
+    /// The configuration document.
+    pub document: Value,
+    /// The values of the parameters with their defaults, secure parameters are redacted.
+    pub parameters: Map<String, Value>,
+    /// The state of each instance when planned and its predicted state after `set`, secrets are redacted.
+    pub results: Vec<ResourceSetResult>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub messages: Vec<ResourceMessage>,
+}
+
+/// What `config apply` does when the state of an instance changed since the plan was created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub enum DriftAction {
+    /// Nothing is applied.
+    #[default]
+    Refuse,
+    /// The planned changes are applied and the drift is reported as warnings.
+    Report,
+}
+
+impl ConfigurationPlan {
+    /// Get the configuration document of the plan as JSON, checking it wasn't edited.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the document doesn't match the hash of the plan.
+    pub fn get_document(&self) -> Result<String, DscError> {
+        let document = serde_json::to_string(&self.document)?;
+        if get_document_hash(&document) != self.document_hash {
+            return Err(DscError::Plan("Document doesn't match the hash of the plan, it was edited after the plan was created".to_string()));
+        }
+        Ok(document)
+    }
+
+    /// Get the parameters input of the plan with the values of its redacted secure parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `parameters_input` - The parameters passed to apply, the secure parameters redacted in the plan and
+    ///   optionally other parameters with the values of the plan.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the parameters input is invalid, changes a parameter of the plan, or a secure parameter
+    /// redacted in the plan isn't passed.
+    pub fn get_parameters(&self, parameters_input: &Option<Value>) -> Result<Option<Value>, DscError> {
+        let config: Configuration = serde_json::from_value(self.document.clone())?;
+        let mut redacted = Vec::new();
+        for (name, parameter) in config.parameters.unwrap_or_default() {
+            let is_secure = matches!(parameter.parameter_type, DataType::SecureString | DataType::SecureObject);
+            if is_secure && self.parameters.get(&name).and_then(Value::as_str) == Some(REDACTED_VALUE) {
+                redacted.push(name);
+            }
+        }
+        let mut parameters = self.parameters.clone();
+        if let Some(parameters_input) = parameters_input {
+            let Some(input) = parameters_input.get("parameters").and_then(Value::as_object) else {
+                return Err(DscError::Validation("Parameters input must have a `parameters` object".to_string()));
+            };
+            // the plan was created with its parameters, so only the values it couldn't keep are taken
+            for (name, value) in input {
+                if redacted.contains(name) {
+                    parameters.insert(name.clone(), value.clone());
+                } else if self.parameters.get(name) != Some(value) {
+                    return Err(DscError::Plan(format!("Parameter '{name}' differs from the plan, only secure parameters redacted in the plan are passed to apply")));
+                }
+            }
+        }
+        for name in &redacted {
+            if parameters.get(name).and_then(Value::as_str) == Some(REDACTED_VALUE) {
+                return Err(DscError::Plan(format!("Secure parameter '{name}' is redacted in the plan and must be passed to apply")));
+            }
+        }
+        if parameters.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Value::Object(Map::from_iter([("parameters".to_string(), Value::Object(parameters))]))))
+    }
+}
+
+/// Whether the plan changes an instance, for groups whether it changes any of their instances.
+#[must_use]
+pub fn has_planned_changes(result: &SetResult) -> bool {
+    match result {
+        SetResult::Resource(response) => response.changed_properties.as_ref().is_some_and(|changed| !changed.is_empty()),
+        SetResult::Group(results) => results.iter().any(|result| has_planned_changes(&result.result)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn plan() -> ConfigurationPlan {
+        ConfigurationPlan {
+            version: String::new(),
+            timestamp: String::new(),
+            document_hash: String::new(),
+            document: json!({
+                "$schema": "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json",
+                "parameters": { "value": { "type": "string" }, "secret": { "type": "secureString" } },
+                "resources": [],
+            }),
+            parameters: Map::from_iter([
+                ("value".to_string(), json!("planned")),
+                ("secret".to_string(), json!(REDACTED_VALUE)),
+            ]),
+            results: Vec::new(),
+            messages: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn only_redacted_secure_parameters_are_passed_to_apply() {
+        let plan = plan();
+        assert!(plan.get_parameters(&None).is_err());
+
+        let parameters = plan.get_parameters(&Some(json!({ "parameters": { "secret": "hidden", "value": "planned" } }))).unwrap();
+        assert_eq!(parameters, Some(json!({ "parameters": { "value": "planned", "secret": "hidden" } })));
+
+        let err = plan.get_parameters(&Some(json!({ "parameters": { "secret": "hidden", "value": "other" } }))).unwrap_err();
+        assert!(err.to_string().contains("Parameter 'value' differs from the plan"));
+    }
+}
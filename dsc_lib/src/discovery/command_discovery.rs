@@ -3,90 +3,118 @@
 
 use crate::discovery::discovery_trait::ResourceDiscovery;
 use crate::discovery::convert_wildcard_to_regex;
+use crate::discovery::diagnostics::{DiscoveryDiagnostics, DuplicateResource, FailedAdapter, InvalidManifest, ResourceLocation};
 use crate::dscresources::dscresource::{Capability, DscResource, ImplementedAs};
+use crate::dscresources::manifest_lint::is_manifest_file_name;
 use crate::dscresources::resource_manifest::{import_manifest, validate_semver, Kind, ResourceManifest};
+use crate::integrity::verify_manifest_integrity;
+use crate::package::get_package_directories;
+use crate::policy::check_resource_policy;
+use crate::settings::{get_settings, DiscoverySettings, DscSettings, IntegritySettings};
+use serde_json::Value;
 use crate::dscresources::command_resource::invoke_command;
 use crate::dscerror::DscError;
 use indicatif::ProgressStyle;
-use regex::RegexBuilder;
+use regex::{Regex, RegexBuilder};
 use semver::Version;
 use std::collections::{BTreeMap, HashSet};
 use std::env;
 use std::ffi::OsStr;
-use std::fs::File;
-use std::io::BufReader;
+use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::{debug, info, trace, warn, warn_span};
 use tracing_indicatif::span_ext::IndicatifSpanExt;
 
+const DEFAULT_MAX_DEPTH: usize = 3;
+
 pub struct CommandDiscovery {
     // use BTreeMap so that the results are sorted by the typename, the Vec is sorted by version
     resources: BTreeMap<String, Vec<DscResource>>,
     adapters: BTreeMap<String, Vec<DscResource>>,
     adapted_resources: BTreeMap<String, Vec<DscResource>>,
+    diagnostics: DiscoveryDiagnostics,
+    // settings taking precedence over the ones of the settings files, like the ones of the command line
+    settings: DiscoverySettings,
 }
 
 impl CommandDiscovery {
-    pub fn new() -> CommandDiscovery {
+    pub fn new(settings: DiscoverySettings) -> CommandDiscovery {
         CommandDiscovery {
             resources: BTreeMap::new(),
             adapters: BTreeMap::new(),
             adapted_resources: BTreeMap::new(),
+            diagnostics: DiscoveryDiagnostics::default(),
+            settings,
         }
     }
 
-    fn get_resource_paths() -> Result<Vec<PathBuf>, DscError>
+    // invalid settings files are reported but don't prevent discovery, like invalid manifests
+    fn get_settings(&self) -> (DiscoverySettings, IntegritySettings) {
+        let settings = match get_settings() {
+            Ok(settings) => settings,
+            Err(err) => {
+                warn!("{err}");
+                DscSettings::default()
+            }
+        };
+        let discovery = settings.discovery.unwrap_or_default().merge(self.settings.clone());
+        (discovery, settings.integrity.unwrap_or_default())
+    }
+
+    // returns the directories to search and whether DSC_RESOURCE_PATH is used instead of PATH
+    fn get_resource_paths(settings: &DiscoverySettings) -> Result<(Vec<SearchPath>, bool), DscError>
     {
         let mut using_custom_path = false;
+        let max_depth = if settings.recurse == Some(true) { settings.max_depth.unwrap_or(DEFAULT_MAX_DEPTH) } else { 0 };
+
+        // search paths from the settings come first
+        let mut paths = settings.search_paths.iter().flatten().map(|path| SearchPath { path: PathBuf::from(path), max_depth }).collect::<Vec<_>>();
 
         // try DSC_RESOURCE_PATH env var first otherwise use PATH
-        let path_env = if let Some(value) = env::var_os("DSC_RESOURCE_PATH") {
+        if let Some(value) = env::var_os("DSC_RESOURCE_PATH") {
             debug!("Using DSC_RESOURCE_PATH: {:?}", value.to_string_lossy());
             using_custom_path = true;
-            value
+            paths.extend(env::split_paths(&value).map(|path| SearchPath { path, max_depth }));
         } else {
-            trace!("DSC_RESOURCE_PATH not set, trying PATH");
-            match env::var_os("PATH") {
-                Some(value) => {
-                    trace!("Original PATH: {:?}", value.to_string_lossy());
-                    value
-                },
-                None => {
+            // installed packages are searched before PATH, each version has its own directory
+            paths.extend(get_package_directories().into_iter().map(|path| SearchPath { path, max_depth: DEFAULT_MAX_DEPTH }));
+
+            if settings.scan_path == Some(false) {
+                debug!("DSC_RESOURCE_PATH not set and searching PATH is disabled by the settings");
+            } else {
+                trace!("DSC_RESOURCE_PATH not set, trying PATH");
+                let Some(value) = env::var_os("PATH") else {
                     return Err(DscError::Operation("Failed to get PATH environment variable".to_string()));
+                };
+                trace!("Original PATH: {:?}", value.to_string_lossy());
+                // directories of PATH are never searched recursively as they can be large
+                paths.extend(env::split_paths(&value).map(|path| SearchPath { path, max_depth: 0 }));
+
+                // exe home is searched even if not in PATH, commands find its executables through `get_command_path()`
+                if let Some(exe_home) = env::current_exe()?.parent() {
+                    trace!("Adding exe home to search paths: {}", exe_home.to_string_lossy());
+                    paths.push(SearchPath { path: exe_home.to_path_buf(), max_depth: 0 });
                 }
             }
-        };
+        }
 
-        let mut paths = env::split_paths(&path_env).collect::<Vec<_>>();
         // remove duplicate entries
         let mut uniques = HashSet::new();
-        paths.retain(|e|uniques.insert((*e).clone()));
-
-        // if exe home is not already in PATH env var then add it to env var and list of searched paths
-        if !using_custom_path {
-            if let Some(exe_home) = env::current_exe()?.parent() {
-                let exe_home_pb = exe_home.to_path_buf();
-                if paths.contains(&exe_home_pb) {
-                    trace!("Exe home is already in path: {}", exe_home.to_string_lossy());
-                } else {
-                    trace!("Adding exe home to path: {}", exe_home.to_string_lossy());
-                    paths.push(exe_home_pb);
-
-                    if let Ok(new_path) = env::join_paths(paths.clone()) {
-                        debug!("Using PATH: {:?}", new_path.to_string_lossy());
-                        env::set_var("PATH", &new_path);
-                    }
-                }
-            }
-        };
+        paths.retain(|e| uniques.insert(e.path.clone()));
 
-        Ok(paths)
+        Ok((paths, using_custom_path))
     }
 }
 
+// a directory searched for manifests, and how many levels of its subdirectories are searched
+struct SearchPath {
+    path: PathBuf,
+    max_depth: usize,
+}
+
 impl Default for CommandDiscovery {
     fn default() -> Self {
-        Self::new()
+        Self::new(DiscoverySettings::default())
     }
 }
 
@@ -112,59 +140,54 @@ impl ResourceDiscovery for CommandDiscovery {
 
         let mut resources = BTreeMap::<String, Vec<DscResource>>::new();
         let mut adapters = BTreeMap::<String, Vec<DscResource>>::new();
+        let mut invalid_manifests = Vec::<InvalidManifest>::new();
 
-        if let Ok(paths) = CommandDiscovery::get_resource_paths() {
-            for path in paths {
-                if path.exists() && path.is_dir() {
-                    for entry in path.read_dir().unwrap() {
-                        let entry = entry.unwrap();
-                        let path = entry.path();
-                        if path.is_file() {
-                            let Some(os_file_name) = path.file_name() else {
-                                // skip if not a file
-                                continue;
-                            };
-                            let Some(file_name) = os_file_name.to_str() else {
-                                // skip if not a valid file name
-                                continue;
-                            };
-                            let file_name_lowercase = file_name.to_lowercase();
-                            if file_name_lowercase.ends_with(".dsc.resource.json") ||
-                                file_name_lowercase.ends_with(".dsc.resource.yaml") ||
-                                file_name_lowercase.ends_with(".dsc.resource.yml") {
-                                trace!("Found resource manifest: {path:?}");
-                                let resource = match load_manifest(&path)
-                                {
-                                    Ok(r) => r,
-                                    Err(e) => {
-                                        // At this point we can't determine whether or not the bad manifest contains
-                                        // resource that is requested by resource/config operation
-                                        // if it is, then "ResouceNotFound" error will be issued later
-                                        // and here we just write as warning
-                                        warn!("{e}");
-                                        continue;
-                                    },
-                                };
-
-                                if regex.is_match(&resource.type_name) {
-                                    if let Some(ref manifest) = resource.manifest {
-                                        let manifest = import_manifest(manifest.clone())?;
-                                        if manifest.kind == Some(Kind::Adapter) {
-                                            trace!("Resource adapter '{}' found", resource.type_name);
-                                            insert_resource(&mut adapters, &resource, true);
-                                        } else {
-                                            trace!("Resource '{}' found", resource.type_name);
-                                            insert_resource(&mut resources, &resource, true);
-                                        }
-                                    }
-                                }
-                            }
-                        }
+        let (settings, integrity) = self.get_settings();
+        let exclude = get_exclude_patterns(&settings)?;
+        let (paths, using_custom_path) = CommandDiscovery::get_resource_paths(&settings)?;
+        self.diagnostics.search_paths = paths.iter().map(|path| path.path.to_string_lossy().to_string()).collect();
+        self.diagnostics.using_resource_path = using_custom_path;
+
+        let mut manifest_paths = Vec::<PathBuf>::new();
+        for path in &paths {
+            find_manifests(&path.path, path.max_depth, &exclude, &mut manifest_paths);
+        }
+
+        for path in manifest_paths {
+            trace!("Found resource manifest: {path:?}");
+            let resource = match load_manifest(&path, &integrity)
+            {
+                Ok(r) => r,
+                Err(e) => {
+                    // At this point we can't determine whether or not the bad manifest contains
+                    // resource that is requested by resource/config operation
+                    // if it is, then "ResouceNotFound" error will be issued later
+                    // and here we just write as warning
+                    warn!("{e}");
+                    invalid_manifests.push(InvalidManifest {
+                        path: path.to_string_lossy().to_string(),
+                        error: e.to_string(),
+                    });
+                    continue;
+                },
+            };
+
+            if regex.is_match(&resource.type_name) {
+                if let Some(ref manifest) = resource.manifest {
+                    let manifest = import_manifest(manifest.clone())?;
+                    if manifest.kind == Some(Kind::Adapter) {
+                        trace!("Resource adapter '{}' found", resource.type_name);
+                        insert_resource(&mut adapters, &resource, true);
+                    } else {
+                        trace!("Resource '{}' found", resource.type_name);
+                        insert_resource(&mut resources, &resource, true);
                     }
                 }
             }
         }
         debug!("Found {} matching non-adapter-based resources", resources.len());
+        self.diagnostics.invalid_manifests = invalid_manifests;
+        self.diagnostics.duplicate_resources = get_duplicate_resources(&resources, &adapters);
         self.resources = resources;
         self.adapters = adapters;
         Ok(())
@@ -203,6 +226,7 @@ impl ResourceDiscovery for CommandDiscovery {
         let _ = pb_span.enter();
 
         let mut adapted_resources = BTreeMap::<String, Vec<DscResource>>::new();
+        let mut failed_adapters = Vec::<FailedAdapter>::new();
 
         let mut found_adapter: bool = false;
         for (adapter_name, adapters) in &self.adapters {
@@ -212,6 +236,10 @@ impl ResourceDiscovery for CommandDiscovery {
                 }
 
                 found_adapter = true;
+                if let Err(err) = check_resource_policy(adapter) {
+                    info!("Skipping adapter '{adapter_name}': {err}");
+                    continue;
+                }
                 info!("Enumerating resources for adapter '{}'", adapter_name);
                 let pb_adapter_span = warn_span!("");
                 pb_adapter_span.pb_set_style(&ProgressStyle::with_template(
@@ -230,33 +258,24 @@ impl ResourceDiscovery for CommandDiscovery {
                 };
 
                 let mut adapter_resources_count = 0;
-                // invoke the list command
-                let list_command = manifest.adapter.unwrap().list;
-                let (exit_code, stdout, stderr) = match invoke_command(&list_command.executable, list_command.args, None, Some(&adapter.directory), None, &manifest.exit_codes)
-                {
-                    Ok((exit_code, stdout, stderr)) => (exit_code, stdout, stderr),
-                    Err(e) => {
-                        // In case of error, log and continue
-                        warn!("{e}");
+                let stdout = match invoke_adapter_list(adapter_name, adapter, manifest) {
+                    Ok(stdout) => stdout,
+                    Err(failed_adapter) => {
+                        failed_adapters.push(failed_adapter);
                         continue;
-                    },
+                    }
                 };
 
-                if exit_code != 0 {
-                    // in case of failure, log and continue
-                    warn!("Adapter failed to list resources with exit code {exit_code}: {stderr}");
-                    continue;
-                }
-
                 for line in stdout.lines() {
                     match serde_json::from_str::<DscResource>(line){
-                        Result::Ok(resource) => {
+                        Result::Ok(mut resource) => {
                             if resource.require_adapter.is_none() {
                                 warn!("{}", DscError::MissingRequires(adapter_name.clone(), resource.type_name.clone()).to_string());
                                 continue;
                             }
 
                             if name_regex.is_match(&resource.type_name) {
+                                resource.adapter = Some(Box::new(adapter.clone()));
                                 // we allow duplicate versions since it can come from different adapters
                                 // like PowerShell vs WindowsPowerShell
                                 insert_resource(&mut adapted_resources, &resource, false);
@@ -274,6 +293,7 @@ impl ResourceDiscovery for CommandDiscovery {
             }
         }
 
+        self.diagnostics.failed_adapters = failed_adapters;
         if !found_adapter {
             return Err(DscError::AdapterNotFound(adapter_filter.to_string()));
         }
@@ -377,6 +397,144 @@ impl ResourceDiscovery for CommandDiscovery {
         }
         Ok(found_resources)
     }
+
+    fn get_diagnostics(&self) -> DiscoveryDiagnostics {
+        self.diagnostics.clone()
+    }
+}
+
+// invokes the list command of an adapter, a failure is logged and returned so it is reported by the diagnostics
+fn invoke_adapter_list(adapter_name: &str, adapter: &DscResource, manifest: ResourceManifest) -> Result<String, FailedAdapter> {
+    let list_command = manifest.adapter.unwrap().list;
+    let (exit_code, stdout, stderr) = match invoke_command(&list_command.executable, list_command.args, None, Some(&adapter.directory), None, &manifest.exit_codes)
+    {
+        Ok((exit_code, stdout, stderr)) => (exit_code, stdout, stderr),
+        Err(e) => {
+            // In case of error, log and continue
+            warn!("{e}");
+            let (exit_code, stderr) = match &e {
+                DscError::Command(_, exit_code, stderr) | DscError::CommandExitFromManifest(_, exit_code, _, stderr) => (Some(*exit_code), Some(stderr.clone())),
+                _ => (None, None),
+            };
+            return Err(FailedAdapter {
+                type_name: adapter_name.to_string(),
+                path: adapter.path.clone(),
+                exit_code,
+                error: e.to_string(),
+                stderr,
+            });
+        },
+    };
+
+    if exit_code != 0 {
+        // in case of failure, log and continue
+        warn!("Adapter failed to list resources with exit code {exit_code}: {stderr}");
+        return Err(FailedAdapter {
+            type_name: adapter_name.to_string(),
+            path: adapter.path.clone(),
+            exit_code: Some(exit_code),
+            error: format!("Adapter failed to list resources with exit code {exit_code}"),
+            stderr: Some(stderr),
+        });
+    }
+    Ok(stdout)
+}
+
+// adds the manifests of the directory, then the ones of its subdirectories while the depth allows it
+fn find_manifests(directory: &Path, depth: usize, exclude: &[ExcludePattern], manifests: &mut Vec<PathBuf>) {
+    let entries = match directory.read_dir() {
+        Ok(entries) => entries,
+        Err(err) => {
+            trace!("Skipping directory {directory:?}: {err}");
+            return;
+        }
+    };
+    let mut paths = entries.filter_map(Result::ok).map(|entry| entry.path()).collect::<Vec<PathBuf>>();
+    paths.sort();
+    paths.retain(|path| {
+        let excluded = exclude.iter().any(|pattern| pattern.is_match(path));
+        if excluded {
+            trace!("Excluding {path:?}");
+        }
+        !excluded
+    });
+
+    for path in &paths {
+        if path.is_file() && path.file_name().and_then(OsStr::to_str).is_some_and(is_manifest_file_name) {
+            manifests.push(path.clone());
+        }
+    }
+    if depth == 0 {
+        return;
+    }
+    for path in &paths {
+        if path.is_dir() {
+            find_manifests(path, depth - 1, exclude, manifests);
+        }
+    }
+}
+
+// patterns with a path separator match the whole path, others match the name of the file or directory
+struct ExcludePattern {
+    regex: Regex,
+    match_path: bool,
+}
+
+impl ExcludePattern {
+    fn is_match(&self, path: &Path) -> bool {
+        if self.match_path {
+            self.regex.is_match(&path.to_string_lossy().replace('\\', "/"))
+        } else {
+            path.file_name().is_some_and(|name| self.regex.is_match(&name.to_string_lossy()))
+        }
+    }
+}
+
+fn get_exclude_patterns(settings: &DiscoverySettings) -> Result<Vec<ExcludePattern>, DscError> {
+    let mut patterns = Vec::new();
+    for pattern in settings.exclude.iter().flatten() {
+        let normalized = pattern.replace('\\', "/");
+        let regex_str = format!("^{}$", regex::escape(&normalized).replace("\\*", ".*").replace("\\?", "."));
+        let mut regex_builder = RegexBuilder::new(&regex_str);
+        // paths are case-insensitive on Windows
+        regex_builder.case_insensitive(cfg!(windows));
+        let Ok(regex) = regex_builder.build() else {
+            return Err(DscError::Operation(format!("Could not build Regex for exclude pattern '{pattern}'")));
+        };
+        patterns.push(ExcludePattern { regex, match_path: normalized.contains('/') });
+    }
+    Ok(patterns)
+}
+
+// types are matched case-insensitively, so the first resource found in the order `find_resources` uses is the effective one
+fn get_duplicate_resources(resources: &BTreeMap<String, Vec<DscResource>>, adapters: &BTreeMap<String, Vec<DscResource>>) -> Vec<DuplicateResource> {
+    let mut found = BTreeMap::<String, Vec<&DscResource>>::new();
+    for resource in resources.values().chain(adapters.values()).flatten() {
+        found.entry(resource.type_name.to_lowercase()).or_default().push(resource);
+    }
+
+    let mut duplicates = Vec::new();
+    for found_resources in found.into_values() {
+        let mut locations = found_resources.iter().map(|resource| ResourceLocation {
+            type_name: resource.type_name.clone(),
+            version: resource.version.clone(),
+            path: resource.path.clone(),
+        });
+        let Some(effective) = locations.next() else {
+            continue;
+        };
+        let shadowed = locations.collect::<Vec<ResourceLocation>>();
+        if shadowed.is_empty() {
+            continue;
+        }
+        debug!("Resource '{}' found {} times, using {}", effective.type_name, shadowed.len() + 1, effective.path);
+        duplicates.push(DuplicateResource {
+            type_name: effective.type_name.clone(),
+            effective,
+            shadowed,
+        });
+    }
+    duplicates
 }
 
 // helper to insert a resource into a vector of resources in order of newest to oldest
@@ -421,11 +579,11 @@ fn insert_resource(resources: &mut BTreeMap<String, Vec<DscResource>>, resource:
     }
 }
 
-fn load_manifest(path: &Path) -> Result<DscResource, DscError> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let manifest: ResourceManifest = if path.extension() == Some(OsStr::new("json")) {
-        match serde_json::from_reader(reader) {
+fn load_manifest(path: &Path, integrity: &IntegritySettings) -> Result<DscResource, DscError> {
+    // the content is read once so the verified signature covers what is loaded
+    let content = fs::read(path)?;
+    let manifest: Value = if path.extension() == Some(OsStr::new("json")) {
+        match serde_json::from_slice(&content) {
             Ok(manifest) => manifest,
             Err(err) => {
                 return Err(DscError::Manifest(path.to_string_lossy().to_string(), err));
@@ -433,18 +591,29 @@ fn load_manifest(path: &Path) -> Result<DscResource, DscError> {
         }
     }
     else {
-        match serde_yaml::from_reader(reader) {
+        match serde_yaml::from_slice(&content) {
             Ok(manifest) => manifest,
             Err(err) => {
                 return Err(DscError::ManifestYaml(path.to_string_lossy().to_string(), err));
             }
         }
     };
+    let manifest = match import_manifest(manifest) {
+        Ok(manifest) => manifest,
+        Err(DscError::Json(err)) => {
+            return Err(DscError::Manifest(path.to_string_lossy().to_string(), err));
+        },
+        Err(err) => {
+            return Err(DscError::Validation(format!("Invalid manifest {path:?}: {err}")));
+        }
+    };
 
     if let Err(err) = validate_semver(&manifest.version) {
         return Err(DscError::Validation(format!("Invalid manifest {path:?} version value: {err}")));
     }
 
+    verify_manifest_integrity(path, &content, &manifest, integrity)?;
+
     let kind = if let Some(kind) = manifest.kind.clone() {
         kind
     } else if manifest.adapter.is_some() {
@@ -0,0 +1,87 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use crate::discovery::discovery_trait::ResourceDiscovery;
+use crate::discovery::convert_wildcard_to_regex;
+use crate::discovery::diagnostics::DiscoveryDiagnostics;
+use crate::dscresources::custom_resource::get_custom_resources;
+use crate::dscresources::dscresource::DscResource;
+use crate::dscerror::DscError;
+use regex::RegexBuilder;
+use std::collections::BTreeMap;
+use tracing::debug;
+
+pub struct CustomDiscovery {
+    resources: BTreeMap<String, Vec<DscResource>>,
+}
+
+impl CustomDiscovery {
+    pub fn new() -> CustomDiscovery {
+        CustomDiscovery {
+            resources: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for CustomDiscovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResourceDiscovery for CustomDiscovery {
+
+    fn discover_resources(&mut self, filter: &str) -> Result<(), DscError> {
+        let regex_str = convert_wildcard_to_regex(filter);
+        let mut regex_builder = RegexBuilder::new(&regex_str);
+        regex_builder.case_insensitive(true);
+        let Ok(regex) = regex_builder.build() else {
+            return Err(DscError::Operation("Could not build Regex filter for resource name".to_string()));
+        };
+
+        let mut resources = BTreeMap::<String, Vec<DscResource>>::new();
+        for resource in get_custom_resources() {
+            if regex.is_match(&resource.type_name) {
+                resources.insert(resource.type_name.clone(), vec![resource]);
+            }
+        }
+        debug!("Found {} matching custom resources", resources.len());
+        self.resources = resources;
+        Ok(())
+    }
+
+    fn discover_adapted_resources(&mut self, _name_filter: &str, _adapter_filter: &str) -> Result<(), DscError> {
+        // custom resources are never adapters
+        Ok(())
+    }
+
+    fn list_available_resources(&mut self, type_name_filter: &str, adapter_name_filter: &str) -> Result<BTreeMap<String, Vec<DscResource>>, DscError> {
+        let mut resources = BTreeMap::<String, Vec<DscResource>>::new();
+        if adapter_name_filter.is_empty() {
+            self.discover_resources(type_name_filter)?;
+            resources.append(&mut self.resources);
+        }
+        Ok(resources)
+    }
+
+    fn find_resources(&mut self, required_resource_types: &[String]) -> Result<BTreeMap<String, DscResource>, DscError> {
+        self.discover_resources("*")?;
+        let required_resource_types = required_resource_types.iter().map(|x| x.to_lowercase()).collect::<Vec<String>>();
+
+        let mut found_resources = BTreeMap::<String, DscResource>::new();
+        for (resource_name, resources) in &self.resources {
+            let Some(resource) = resources.first() else {
+                continue;
+            };
+            if required_resource_types.contains(&resource_name.to_lowercase()) {
+                found_resources.insert(resource_name.to_lowercase(), resource.clone());
+            }
+        }
+        Ok(found_resources)
+    }
+
+    fn get_diagnostics(&self) -> DiscoveryDiagnostics {
+        // custom resources are part of the library, so there is nothing to report
+        DiscoveryDiagnostics::default()
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// What happened while discovering resources, to find out why a resource isn't found or isn't the one expected.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveryDiagnostics {
+    /// The directories searched for resource manifests, in search order.
+    pub search_paths: Vec<String>,
+    /// Whether the search paths come from `DSC_RESOURCE_PATH` instead of `PATH`.
+    pub using_resource_path: bool,
+    /// The manifests that couldn't be loaded, their resources aren't available.
+    pub invalid_manifests: Vec<InvalidManifest>,
+    /// The resource types found more than once.
+    pub duplicate_resources: Vec<DuplicateResource>,
+    /// The adapters that failed to list their resources.
+    pub failed_adapters: Vec<FailedAdapter>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidManifest {
+    /// The path to the manifest.
+    pub path: String,
+    /// Why the manifest couldn't be loaded.
+    pub error: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateResource {
+    /// The type of the resource.
+    #[serde(rename = "type")]
+    pub type_name: String,
+    /// The resource used for the type.
+    pub effective: ResourceLocation,
+    /// The other resources found for the type, which are not used unless requested by version.
+    pub shadowed: Vec<ResourceLocation>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceLocation {
+    /// The type of the resource, as declared by its manifest.
+    #[serde(rename = "type")]
+    pub type_name: String,
+    /// The version of the resource.
+    pub version: String,
+    /// The path to the manifest of the resource.
+    pub path: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedAdapter {
+    /// The type of the adapter.
+    #[serde(rename = "type")]
+    pub type_name: String,
+    /// The path to the manifest of the adapter.
+    pub path: String,
+    /// The exit code of the list command, if it ran.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Why listing the resources failed.
+    pub error: String,
+    /// The stderr of the list command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+}
+
+impl DiscoveryDiagnostics {
+    /// Add the diagnostics of another discovery, search paths already present are not repeated.
+    pub fn merge(&mut self, other: DiscoveryDiagnostics) {
+        for path in other.search_paths {
+            if !self.search_paths.contains(&path) {
+                self.search_paths.push(path);
+            }
+        }
+        self.using_resource_path |= other.using_resource_path;
+        self.invalid_manifests.extend(other.invalid_manifests);
+        self.duplicate_resources.extend(other.duplicate_resources);
+        self.failed_adapters.extend(other.failed_adapters);
+    }
+}
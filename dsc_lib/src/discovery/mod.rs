@@ -2,15 +2,19 @@
 // Licensed under the MIT License.
 
 mod command_discovery;
+mod custom_discovery;
+pub mod diagnostics;
 mod discovery_trait;
 
-use crate::discovery::discovery_trait::ResourceDiscovery;
-use crate::{dscresources::dscresource::DscResource, dscerror::DscError};
+use crate::discovery::{diagnostics::DiscoveryDiagnostics, discovery_trait::ResourceDiscovery};
+use crate::{dscresources::dscresource::DscResource, dscerror::DscError, settings::DiscoverySettings};
+use regex::RegexBuilder;
 use std::collections::BTreeMap;
 use tracing::error;
 
 pub struct Discovery {
     pub resources: BTreeMap<String, DscResource>,
+    settings: DiscoverySettings,
 }
 
 impl Discovery {
@@ -21,8 +25,23 @@ impl Discovery {
     /// This function will return an error if the underlying instance creation fails.
     ///
     pub fn new() -> Result<Self, DscError> {
+        Self::with_settings(DiscoverySettings::default())
+    }
+
+    /// Create a new `Discovery` instance with settings taking precedence over the ones of the settings files.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - The discovery settings, like the ones of the command line.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying instance creation fails.
+    ///
+    pub fn with_settings(settings: DiscoverySettings) -> Result<Self, DscError> {
         Ok(Self {
             resources: BTreeMap::new(),
+            settings,
         })
     }
 
@@ -38,7 +57,8 @@ impl Discovery {
     /// A vector of `DscResource` instances.
     pub fn list_available_resources(&mut self, type_name_filter: &str, adapter_name_filter: &str) -> Vec<DscResource> {
         let discovery_types: Vec<Box<dyn ResourceDiscovery>> = vec![
-            Box::new(command_discovery::CommandDiscovery::new()),
+            Box::new(custom_discovery::CustomDiscovery::new()),
+            Box::new(command_discovery::CommandDiscovery::new(self.settings.clone())),
         ];
 
         let mut resources: Vec<DscResource> = Vec::new();
@@ -63,6 +83,45 @@ impl Discovery {
         resources
     }
 
+    /// Discover resources and report what prevents resources from being found or used.
+    ///
+    /// # Arguments
+    ///
+    /// * `type_name_filter` - The filter for the type names of the duplicate resources to report.
+    /// * `adapter_name_filter` - The filter for the adapters to enumerate, all adapters if empty.
+    ///
+    /// # Returns
+    ///
+    /// The search paths, invalid manifests, duplicate resources, and failing adapters.
+    pub fn get_diagnostics(&mut self, type_name_filter: &str, adapter_name_filter: &str) -> DiscoveryDiagnostics {
+        let discovery_types: Vec<Box<dyn ResourceDiscovery>> = vec![
+            Box::new(custom_discovery::CustomDiscovery::new()),
+            Box::new(command_discovery::CommandDiscovery::new(self.settings.clone())),
+        ];
+        // adapters are always enumerated since a failing adapter is a common reason for a missing resource
+        let adapter_name_filter = if adapter_name_filter.is_empty() { "*" } else { adapter_name_filter };
+
+        let mut diagnostics = DiscoveryDiagnostics::default();
+        for mut discovery_type in discovery_types {
+            if let Err(err) = discovery_type.discover_resources("*") {
+                error!("{err}");
+                continue;
+            }
+            if let Err(err) = discovery_type.discover_adapted_resources("*", adapter_name_filter) {
+                error!("{err}");
+            }
+            diagnostics.merge(discovery_type.get_diagnostics());
+        }
+
+        let mut regex_builder = RegexBuilder::new(&convert_wildcard_to_regex(type_name_filter));
+        regex_builder.case_insensitive(true);
+        match regex_builder.build() {
+            Ok(regex) => diagnostics.duplicate_resources.retain(|duplicate| regex.is_match(&duplicate.type_name)),
+            Err(err) => error!("Could not build Regex filter for resource name: {err}"),
+        }
+        diagnostics
+    }
+
     #[must_use]
     pub fn find_resource(&self, type_name: &str) -> Option<&DscResource> {
         self.resources.get(&type_name.to_lowercase())
@@ -75,11 +134,16 @@ impl Discovery {
     /// * `required_resource_types` - The required resource types.
     pub fn find_resources(&mut self, required_resource_types: &[String]) {
         let discovery_types: Vec<Box<dyn ResourceDiscovery>> = vec![
-            Box::new(command_discovery::CommandDiscovery::new()),
+            Box::new(custom_discovery::CustomDiscovery::new()),
+            Box::new(command_discovery::CommandDiscovery::new(self.settings.clone())),
         ];
         let mut remaining_required_resource_types = required_resource_types.to_owned();
         for mut discovery_type in discovery_types {
 
+            if remaining_required_resource_types.is_empty() {
+                break;
+            }
+
             let discovered_resources = match discovery_type.find_resources(&remaining_required_resource_types) {
                 Ok(value) => value,
                 Err(err) => {
@@ -90,13 +154,13 @@ impl Discovery {
 
             for resource in discovered_resources {
                 self.resources.insert(resource.0.clone(), resource.1);
-                remaining_required_resource_types.retain(|x| *x != resource.0);
+                remaining_required_resource_types.retain(|x| x.to_lowercase() != resource.0);
             };
         }
     }
 }
 
-fn convert_wildcard_to_regex(wildcard: &str) -> String {
+pub(crate) fn convert_wildcard_to_regex(wildcard: &str) -> String {
     let mut regex = wildcard.to_string().replace('.', "\\.").replace('?', ".").replace('*', ".*?");
     regex.insert(0, '^');
     regex.push('$');
@@ -106,6 +170,32 @@ fn convert_wildcard_to_regex(wildcard: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dscresources::custom_resource::{register_custom_resource, CustomResource};
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Deserialize, Serialize, JsonSchema)]
+    struct Setting {
+        name: String,
+    }
+
+    struct SettingResource;
+
+    impl CustomResource for SettingResource {
+        type Properties = Setting;
+
+        fn type_name(&self) -> &'static str {
+            "Test/DiscoveredCustomSetting"
+        }
+
+        fn version(&self) -> &'static str {
+            "1.0.0"
+        }
+
+        fn get(&self, filter: Setting) -> Result<Setting, DscError> {
+            Ok(filter)
+        }
+    }
 
     #[test]
     fn test_convert_wildcard_to_regex() {
@@ -121,6 +211,18 @@ mod tests {
         let regex = convert_wildcard_to_regex(wildcard);
         assert_eq!(regex, "^r.*?$");
     }
+
+    #[test]
+    fn custom_resources_are_discovered() {
+        register_custom_resource(SettingResource);
+        let mut discovery = Discovery::new().unwrap();
+        let resources = discovery.list_available_resources("Test/DiscoveredCustom*", "");
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].type_name, "Test/DiscoveredCustomSetting");
+
+        discovery.find_resources(&["test/discoveredcustomsetting".to_string()]);
+        assert!(discovery.find_resource("Test/DiscoveredCustomSetting").is_some());
+    }
 }
 
 impl Default for Discovery {
@@ -13,9 +13,15 @@ pub enum DscError {
     #[error("Adapter '{0}' not found")]
     AdapterNotFound(String),
 
+    #[error("Audit log '{0}': {1}")]
+    Audit(String, String),
+
     #[error("Function boolean argument conversion error: {0}")]
     BooleanConversion(#[from] std::str::ParseBoolError),
 
+    #[error("Checkpoint: {0}")]
+    Checkpoint(String),
+
     #[error("Command: Resource '{0}' [Exit code {1}] {2}")]
     Command(String, i32, String),
 
@@ -23,11 +29,14 @@ pub enum DscError {
     CommandExit(String, i32, String),
 
     #[error("Command: Resource '{0}' [Exit code {1}] manifest description: {2}")]
-    CommandExitFromManifest(String, i32, String),
+    CommandExitFromManifest(String, i32, String, String),
 
     #[error("CommandOperation: {0} for executable '{1}'")]
     CommandOperation(String, String),
 
+    #[error("Execution policy: {0}")]
+    ExecutionPolicy(String),
+
     #[error("Function '{0}' error: {1}")]
     Function(String, String),
 
@@ -37,6 +46,12 @@ pub enum DscError {
     #[error("Function integer argument conversion error: {0}")]
     IntegerConversion(#[from] std::num::ParseIntError),
 
+    #[error("History: {0}")]
+    History(String),
+
+    #[error("Integrity of manifest '{0}': {1}")]
+    Integrity(String, String),
+
     #[error("Invalid configuration:\n{0}")]
     InvalidConfiguration(String),
 
@@ -82,9 +97,15 @@ pub enum DscError {
     #[error("Operation: {0}")]
     Operation(String),
 
+    #[error("Package: {0}")]
+    Package(String),
+
     #[error("Parser: {0}")]
     Parser(String),
 
+    #[error("Plan: {0}")]
+    Plan(String),
+
     #[error("Progress: {0}")]
     Progress(#[from] TemplateError),
 
@@ -103,6 +124,9 @@ pub enum DscError {
     #[error("Security context: {0}")]
     SecurityContext(String),
 
+    #[error("Settings '{0}': {1}")]
+    Settings(String, String),
+
     #[error("Utf-8 conversion error: {0}")]
     Utf8Conversion(#[from] Utf8Error),
 
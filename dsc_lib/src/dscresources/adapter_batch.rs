@@ -0,0 +1,247 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use serde_json::{json, Map, Value};
+use tracing::debug;
+
+use crate::configure::config_doc::ExecutionKind;
+use crate::dscerror::DscError;
+use super::{command_resource, dscresource::DscResource, invoke_result::{GetResult, SetResult, TestResult}, resource_manifest::{import_manifest, ConfigKind, ResourceManifest}};
+
+/// An instance of an adapted resource sent to its adapter as part of a batch.
+#[derive(Debug, Clone)]
+pub struct BatchInstance {
+    /// The name of the instance in the configuration.
+    pub name: String,
+    /// The type of the adapted resource.
+    pub resource_type: String,
+    /// The properties of the instance.
+    pub properties: Map<String, Value>,
+}
+
+/// Get the adapter of a resource if the adapter accepts full configuration.
+///
+/// # Arguments
+///
+/// * `resource` - The adapted resource.
+#[must_use]
+pub fn get_batch_adapter(resource: &DscResource) -> Option<&DscResource> {
+    let adapter = resource.adapter.as_deref()?;
+    let manifest = import_manifest(adapter.manifest.clone()?).ok()?;
+    match manifest.adapter?.config {
+        ConfigKind::Full => Some(adapter),
+        ConfigKind::Sequence => None,
+    }
+}
+
+/// Invoke the get operation for multiple instances using their adapter in a single call.
+///
+/// # Arguments
+///
+/// * `adapter` - The adapter accepting full configuration.
+/// * `instances` - The instances to get.
+///
+/// # Returns
+///
+/// The actual state of each instance in the same order as the instances.
+///
+/// # Errors
+///
+/// Error returned if the adapter fails or doesn't return a result for each instance.
+pub fn invoke_batch_get(adapter: &DscResource, instances: &[BatchInstance]) -> Result<Vec<Value>, DscError> {
+    let manifest = get_adapter_manifest(adapter)?;
+    debug!("Invoking get for {} instances using adapter '{}'", instances.len(), adapter.type_name);
+    let output = match command_resource::invoke_get(&manifest, &adapter.directory, &get_batch_input(instances))? {
+        GetResult::Resource(response) => response.actual_state,
+        GetResult::Group(results) => Value::Array(results.into_iter().map(|result| {
+            let GetResult::Resource(response) = result.result else {
+                return get_entry(&result.name, &result.resource_type, Value::Null);
+            };
+            get_entry(&result.name, &result.resource_type, response.actual_state)
+        }).collect()),
+    };
+    split_batch_output(&adapter.type_name, output, instances)
+}
+
+/// Invoke the test operation for multiple instances using their adapter in a single call.
+///
+/// The test of the adapter is used if it implements one, otherwise its get.
+///
+/// # Arguments
+///
+/// * `adapter` - The adapter accepting full configuration.
+/// * `instances` - The instances to test.
+///
+/// # Returns
+///
+/// The actual state of each instance in the same order as the instances, to compare to their desired state.
+///
+/// # Errors
+///
+/// Error returned if the adapter fails or doesn't return a result for each instance.
+pub fn invoke_batch_test(adapter: &DscResource, instances: &[BatchInstance]) -> Result<Vec<Value>, DscError> {
+    let manifest = get_adapter_manifest(adapter)?;
+    if manifest.test.is_none() {
+        return invoke_batch_get(adapter, instances);
+    }
+    debug!("Invoking test for {} instances using adapter '{}'", instances.len(), adapter.type_name);
+    let output = match command_resource::invoke_test(&manifest, &adapter.directory, &get_batch_input(instances))? {
+        TestResult::Resource(response) => response.actual_state,
+        TestResult::Group(results) => Value::Array(results.into_iter().map(|result| {
+            let TestResult::Resource(response) = result.result else {
+                return get_entry(&result.name, &result.resource_type, Value::Null);
+            };
+            get_entry(&result.name, &result.resource_type, response.actual_state)
+        }).collect()),
+    };
+    split_batch_output(&adapter.type_name, output, instances)
+}
+
+/// Invoke the set operation for multiple instances using their adapter in a single call.
+///
+/// # Arguments
+///
+/// * `adapter` - The adapter accepting full configuration.
+/// * `instances` - The instances to set.
+/// * `skip_test` - Whether the adapter skips its test before the set.
+/// * `execution_type` - Whether the changes are made or only predicted.
+///
+/// # Returns
+///
+/// The state of each instance before and after the set in the same order as the instances.
+///
+/// # Errors
+///
+/// Error returned if the adapter fails or doesn't return a result for each instance.
+pub fn invoke_batch_set(adapter: &DscResource, instances: &[BatchInstance], skip_test: bool, execution_type: &ExecutionKind) -> Result<Vec<(Value, Value)>, DscError> {
+    let manifest = get_adapter_manifest(adapter)?;
+    debug!("Invoking set for {} instances using adapter '{}'", instances.len(), adapter.type_name);
+    let (before, after) = match command_resource::invoke_set(&manifest, &adapter.directory, &get_batch_input(instances), skip_test, execution_type)? {
+        SetResult::Resource(response) => (response.before_state, response.after_state),
+        SetResult::Group(results) => {
+            let mut before = Vec::new();
+            let mut after = Vec::new();
+            for result in results {
+                let (before_state, after_state) = match result.result {
+                    SetResult::Resource(response) => (response.before_state, response.after_state),
+                    SetResult::Group(_) => (Value::Null, Value::Null),
+                };
+                before.push(get_entry(&result.name, &result.resource_type, before_state));
+                after.push(get_entry(&result.name, &result.resource_type, after_state));
+            }
+            (Value::Array(before), Value::Array(after))
+        },
+    };
+    let before = split_batch_output(&adapter.type_name, before, instances)?;
+    let after = split_batch_output(&adapter.type_name, after, instances)?;
+    Ok(before.into_iter().zip(after).collect())
+}
+
+fn get_adapter_manifest(adapter: &DscResource) -> Result<ResourceManifest, DscError> {
+    let Some(manifest) = &adapter.manifest else {
+        return Err(DscError::MissingManifest(adapter.type_name.clone()));
+    };
+    adapter.import_manifest(manifest)
+}
+
+// results returned as a group are entries like the ones of adapters returning an array
+fn get_entry(name: &str, resource_type: &str, state: Value) -> Value {
+    let mut entry = Map::new();
+    entry.insert("name".to_string(), Value::String(name.to_string()));
+    entry.insert("type".to_string(), Value::String(resource_type.to_string()));
+    entry.insert("properties".to_string(), state);
+    Value::Object(entry)
+}
+
+// the adapter receives the instances as a configuration like resource groups do
+fn get_batch_input(instances: &[BatchInstance]) -> String {
+    let resources: Vec<Value> = instances.iter().map(|instance| json!({
+        "name": instance.name,
+        "type": instance.resource_type,
+        "properties": instance.properties,
+    })).collect();
+    json!({
+        "metadata": {
+            "Microsoft.DSC": {
+                "context": "configuration"
+            }
+        },
+        "resources": resources,
+    }).to_string()
+}
+
+// adapters return an array of results, optionally as the `result` property of an object, where results
+// including the name and type of the instance are matched by name and others by position
+fn split_batch_output(adapter_type: &str, output: Value, instances: &[BatchInstance]) -> Result<Vec<Value>, DscError> {
+    let entries = match output {
+        Value::Array(entries) => entries,
+        Value::Object(mut map) => match map.remove("result") {
+            Some(Value::Array(entries)) => entries,
+            _ => return Err(DscError::Operation(format!("Adapter '{adapter_type}' did not return an array of results"))),
+        },
+        _ => return Err(DscError::Operation(format!("Adapter '{adapter_type}' did not return an array of results"))),
+    };
+    if entries.len() != instances.len() {
+        return Err(DscError::Operation(format!("Adapter '{adapter_type}' returned {} results for {} instances", entries.len(), instances.len())));
+    }
+
+    let mut states = Vec::new();
+    for (index, instance) in instances.iter().enumerate() {
+        let entry = entries.iter().find(|entry| {
+            entry.get("name").and_then(Value::as_str) == Some(instance.name.as_str())
+                && entry.get("type").and_then(Value::as_str).is_some_and(|resource_type| resource_type.eq_ignore_ascii_case(&instance.resource_type))
+        });
+        let state = match entry {
+            Some(entry) => entry.get("properties").cloned().unwrap_or(Value::Null),
+            None => entries[index].clone(),
+        };
+        states.push(state);
+    }
+    Ok(states)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instances() -> Vec<BatchInstance> {
+        ["first", "second"].iter().map(|name| BatchInstance {
+            name: (*name).to_string(),
+            resource_type: "Test/Adapted".to_string(),
+            properties: Map::new(),
+        }).collect()
+    }
+
+    #[test]
+    fn results_matched_by_name() {
+        let output = json!({"result": [
+            {"name": "second", "type": "Test/Adapted", "properties": {"value": 2}},
+            {"name": "first", "type": "Test/Adapted", "properties": {"value": 1}}
+        ]});
+        let states = split_batch_output("Test/Adapter", output, &instances()).unwrap();
+        assert_eq!(states, vec![json!({"value": 1}), json!({"value": 2})]);
+    }
+
+    #[test]
+    fn results_matched_by_position() {
+        let output = json!([{"value": 1}, {"value": 2}]);
+        let states = split_batch_output("Test/Adapter", output, &instances()).unwrap();
+        assert_eq!(states, vec![json!({"value": 1}), json!({"value": 2})]);
+    }
+
+    #[test]
+    fn group_results_matched_by_name() {
+        let output = Value::Array(vec![
+            get_entry("second", "Test/Adapted", json!({"value": 2})),
+            get_entry("first", "Test/Adapted", json!({"value": 1})),
+        ]);
+        let states = split_batch_output("Test/Adapter", output, &instances()).unwrap();
+        assert_eq!(states, vec![json!({"value": 1}), json!({"value": 2})]);
+    }
+
+    #[test]
+    fn missing_results() {
+        let output = json!([{"value": 1}]);
+        assert!(split_batch_output("Test/Adapter", output, &instances()).is_err());
+    }
+}
@@ -2,15 +2,39 @@
 // Licensed under the MIT License.
 
 use jsonschema::JSONSchema;
-use serde_json::Value;
-use std::{collections::HashMap, env, process::Stdio};
-use crate::configure::{config_doc::ExecutionKind, config_result::{ResourceGetResult, ResourceTestResult}};
+use serde_json::{Map, Value};
+use std::{cell::RefCell, collections::HashMap, env, ffi::OsStr, path::{Path, PathBuf}, process::Stdio};
+use crate::configure::{config_doc::{ExecutionKind, SecurityContextKind}, config_result::{ResourceGetResult, ResourceTestResult}};
 use crate::dscerror::DscError;
-use super::{dscresource::get_diff, invoke_result::{ExportResult, GetResult, ResolveResult, SetResult, TestResult, ValidateResult, ResourceGetResponse, ResourceSetResponse, ResourceTestResponse, get_in_desired_state}, resource_manifest::{ArgKind, InputKind, Kind, ResourceManifest, ReturnKind, SchemaKind}};
+use crate::policy::check_executable_policy;
+use super::{elevation::{get_security_context_wrapper, wrap_command}, invoke_result::{ExportResult, GetResult, ResolveResult, SetResult, TestResult, ValidateResult, ResourceGetResponse, ResourceSetResponse, ResourceTestResponse, get_in_desired_state}, manifest_lint::find_executable, property_diff::{get_changes_using_schema, get_differences_using_schema}, resource_host::get_resource_host, resource_manifest::{ArgKind, ArrayEncoding, EnvInput, ExitCode, ExitCodeSemantic, InputKind, Kind, ResourceManifest, ReturnKind, SchemaKind}};
 use tracing::{error, warn, info, debug, trace};
 use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, process::Command};
 
 pub const EXIT_PROCESS_TERMINATED: i32 = 0x102;
+const DEFAULT_ENV_SEPARATOR: &str = "__";
+// variables needed by most commands to run, in addition to the `LC_*` locale variables
+const CLEAN_ENVIRONMENT_VARIABLES: [&str; 11] = ["PATH", "PATHEXT", "HOME", "USERPROFILE", "SystemRoot", "TEMP", "TMP", "TMPDIR", "LANG", "LANGUAGE", "TERM"];
+
+thread_local! {
+    // commands run synchronously on the thread invoking the operation, so signals are kept per thread
+    static EXIT_CODE_SIGNALS: RefCell<ExitCodeSignals> = RefCell::new(ExitCodeSignals::default());
+}
+
+/// Signals reported by resource commands through exit codes with a semantic.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExitCodeSignals {
+    /// A command returned an exit code indicating a reboot is required.
+    pub reboot_required: bool,
+    /// Descriptions of the exit codes indicating a warning.
+    pub warnings: Vec<String>,
+}
+
+/// Take the signals reported by resource commands invoked on this thread since the last call.
+#[must_use]
+pub fn take_exit_code_signals() -> ExitCodeSignals {
+    EXIT_CODE_SIGNALS.with(RefCell::take)
+}
 
 /// Invoke the get operation on a resource
 ///
@@ -28,14 +52,20 @@ pub fn invoke_get(resource: &ResourceManifest, cwd: &str, filter: &str) -> Resul
     let Some(get) = &resource.get else {
         return Err(DscError::NotImplemented("get".to_string()));
     };
-    let args = process_args(&get.args, filter);
     if !filter.is_empty() {
         verify_json(resource, cwd, filter)?;
-        command_input = get_command_input(&get.input, filter)?;
+        command_input = get_command_input(&get.input, resource.env_input.as_ref(), filter)?;
     }
+    let args = process_args(&get.args, &get.input, filter)?;
 
     info!("Invoking get '{}' using '{}'", &resource.resource_type, &get.executable);
-    let (_exit_code, stdout, stderr) = invoke_command(&get.executable, args, command_input.stdin.as_deref(), Some(cwd), command_input.env, &resource.exit_codes)?;
+    let (exit_code, stdout, stderr) = invoke_resource_command(resource, cwd, &MethodCommand::new("get", &get.executable, get.env.as_ref(), get.working_directory.as_deref()), args, command_input, filter)?;
+    if get_exit_code_semantic(resource, exit_code) == Some(ExitCodeSemantic::NotFound) {
+        debug!("Get '{}' returned not found exit code {exit_code}", &resource.resource_type);
+        return Ok(GetResult::Resource(ResourceGetResponse {
+            actual_state: get_not_found_state(filter)?,
+        }));
+    }
     if resource.kind == Some(Kind::Resource) {
         debug!("Verifying output of get '{}' using '{}'", &resource.resource_type, &get.executable);
         verify_json(resource, cwd, &stdout)?;
@@ -74,7 +104,6 @@ pub fn invoke_get(resource: &ResourceManifest, cwd: &str, filter: &str) -> Resul
 pub fn invoke_set(resource: &ResourceManifest, cwd: &str, desired: &str, skip_test: bool, execution_type: &ExecutionKind) -> Result<SetResult, DscError> {
     debug!("Invoking set for '{}'", &resource.resource_type);
     let operation_type: String;
-    let mut is_synthetic_what_if = false;
     let set_method = match execution_type {
         ExecutionKind::Actual => {
             operation_type = "set".to_string();
@@ -83,11 +112,10 @@ pub fn invoke_set(resource: &ResourceManifest, cwd: &str, desired: &str, skip_te
         ExecutionKind::WhatIf => {
             operation_type = "whatif".to_string();
             if resource.what_if.is_none() {
-                is_synthetic_what_if = true;
-                &resource.set
-            } else {
-                &resource.what_if
+                info!("Resource '{}' does not implement what-if, performing synthetic what-if", &resource.resource_type);
+                return invoke_synthetic_what_if(resource, cwd, desired);
             }
+            &resource.what_if
         }
     };
     let Some(set) = set_method else {
@@ -99,9 +127,6 @@ pub fn invoke_set(resource: &ResourceManifest, cwd: &str, desired: &str, skip_te
     if !skip_test && set.pre_test != Some(true) {
         info!("No pretest, invoking test {}", &resource.resource_type);
         let test_result = invoke_test(resource, cwd, desired)?;
-        if is_synthetic_what_if {
-            return Ok(test_result.into());
-        }
         let (in_desired_state, actual_state) = match &test_result {
             TestResult::Group(group_response) => {
                 let in_desired_state = get_in_desired_state(&test_result);
@@ -121,29 +146,30 @@ pub fn invoke_set(resource: &ResourceManifest, cwd: &str, desired: &str, skip_te
                 before_state: serde_json::from_str(desired)?,
                 after_state: actual_state,
                 changed_properties: None,
+                changes: None,
             }));
         }
     }
 
-    if is_synthetic_what_if {
-        return Err(DscError::NotImplemented("cannot process what-if execution type, as resource implements pre-test and does not support what-if".to_string()));
-    }
-
     let Some(get) = &resource.get else {
         return Err(DscError::NotImplemented("get".to_string()));
     };
-    let args = process_args(&get.args, desired);
-    let command_input = get_command_input(&get.input, desired)?;
+    let args = process_args(&get.args, &get.input, desired)?;
+    let command_input = get_command_input(&get.input, resource.env_input.as_ref(), desired)?;
 
     info!("Getting current state for set by invoking get '{}' using '{}'", &resource.resource_type, &get.executable);
-    let (exit_code, stdout, stderr) = invoke_command(&get.executable, args, command_input.stdin.as_deref(), Some(cwd), command_input.env, &resource.exit_codes)?;
+    let (exit_code, stdout, stderr) = invoke_resource_command(resource, cwd, &MethodCommand::new("get", &get.executable, get.env.as_ref(), get.working_directory.as_deref()), args, command_input, desired)?;
+    let exit_code_semantic = get_exit_code_semantic(resource, exit_code);
 
-    if resource.kind == Some(Kind::Resource) {
+    if resource.kind == Some(Kind::Resource) && exit_code_semantic != Some(ExitCodeSemantic::NotFound) {
         debug!("Verifying output of get '{}' using '{}'", &resource.resource_type, &get.executable);
         verify_json(resource, cwd, &stdout)?;
     }
 
-    let pre_state: Value = if exit_code == 0 {
+    let pre_state: Value = if exit_code_semantic == Some(ExitCodeSemantic::NotFound) {
+        get_not_found_state(desired)?
+    }
+    else if exit_code == 0 || exit_code_semantic.is_some() {
         serde_json::from_str(&stdout)?
     }
     else {
@@ -152,21 +178,23 @@ pub fn invoke_set(resource: &ResourceManifest, cwd: &str, desired: &str, skip_te
 
     let mut env: Option<HashMap<String, String>> = None;
     let mut input_desired: Option<&str> = None;
-    let args = process_args(&set.args, desired);
+    let args = process_args(&set.args, &set.input, desired)?;
     match &set.input {
         Some(InputKind::Env) => {
-            env = Some(json_to_hashmap(desired)?);
+            env = Some(json_to_hashmap(desired, resource.env_input.as_ref())?);
         },
         Some(InputKind::Stdin) => {
             input_desired = Some(desired);
         },
-        None => {
+        Some(InputKind::Args) | None => {
             // leave input as none
         },
     }
 
     info!("Invoking {} '{}' using '{}'", operation_type, &resource.resource_type, &set.executable);
-    let (exit_code, stdout, stderr) = invoke_command(&set.executable, args, input_desired, Some(cwd), env, &resource.exit_codes)?;
+    let method = if execution_type == &ExecutionKind::WhatIf { "whatIf" } else { "set" };
+    let command_input = CommandInput { env, stdin: input_desired.map(ToString::to_string) };
+    let (exit_code, stdout, stderr) = invoke_resource_command(resource, cwd, &MethodCommand::new(method, &set.executable, set.env.as_ref(), set.working_directory.as_deref()), args, command_input, desired)?;
 
     match set.returns {
         Some(ReturnKind::State) => {
@@ -184,12 +212,8 @@ pub fn invoke_set(resource: &ResourceManifest, cwd: &str, desired: &str, skip_te
             };
 
             // for changed_properties, we compare post state to pre state
-            let diff_properties = get_diff( &actual_value, &pre_state);
-            Ok(SetResult::Resource(ResourceSetResponse{
-                before_state: pre_state,
-                after_state: actual_value,
-                changed_properties: Some(diff_properties),
-            }))
+            let changes = get_changes_using_schema(&pre_state, &actual_value, || get_schema_value(resource, cwd));
+            Ok(SetResult::Resource(ResourceSetResponse::from_changes(pre_state, actual_value, changes)))
         },
         Some(ReturnKind::StateAndDiff) => {
             // command should be returning actual state as a JSON line and a list of properties that differ as separate JSON line
@@ -207,6 +231,7 @@ pub fn invoke_set(resource: &ResourceManifest, cwd: &str, desired: &str, skip_te
                 before_state: pre_state,
                 after_state: actual_value,
                 changed_properties: Some(diff_properties),
+                changes: None,
             }))
         },
         None => {
@@ -225,12 +250,8 @@ pub fn invoke_set(resource: &ResourceManifest, cwd: &str, desired: &str, skip_te
                     response.actual_state
                 }
             };
-            let diff_properties = get_diff( &actual_state, &pre_state);
-            Ok(SetResult::Resource(ResourceSetResponse {
-                before_state: pre_state,
-                after_state: actual_state,
-                changed_properties: Some(diff_properties),
-            }))
+            let changes = get_changes_using_schema(&pre_state, &actual_state, || get_schema_value(resource, cwd));
+            Ok(SetResult::Resource(ResourceSetResponse::from_changes(pre_state, actual_state, changes)))
         },
     }
 }
@@ -254,11 +275,20 @@ pub fn invoke_test(resource: &ResourceManifest, cwd: &str, expected: &str) -> Re
 
     verify_json(resource, cwd, expected)?;
 
-    let args = process_args(&test.args, expected);
-    let command_input = get_command_input(&test.input, expected)?;
+    let args = process_args(&test.args, &test.input, expected)?;
+    let command_input = get_command_input(&test.input, resource.env_input.as_ref(), expected)?;
 
     info!("Invoking test '{}' using '{}'", &resource.resource_type, &test.executable);
-    let (exit_code, stdout, stderr) = invoke_command(&test.executable, args, command_input.stdin.as_deref(), Some(cwd), command_input.env, &resource.exit_codes)?;
+    let (exit_code, stdout, stderr) = invoke_resource_command(resource, cwd, &MethodCommand::new("test", &test.executable, test.env.as_ref(), test.working_directory.as_deref()), args, command_input, expected)?;
+    let not_in_desired_state = get_exit_code_semantic(resource, exit_code) == Some(ExitCodeSemantic::NotInDesiredState);
+    if not_in_desired_state && stdout.trim().is_empty() {
+        debug!("Test '{}' returned not in desired state exit code {exit_code} without output, performing get", &resource.resource_type);
+        let mut test_result = invoke_synthetic_test(resource, cwd, expected)?;
+        if let TestResult::Resource(response) = &mut test_result {
+            response.in_desired_state = false;
+        }
+        return Ok(test_result);
+    }
 
     if resource.kind == Some(Kind::Resource) {
         debug!("Verifying output of test '{}' using '{}'", &resource.resource_type, &test.executable);
@@ -272,7 +302,7 @@ pub fn invoke_test(resource: &ResourceManifest, cwd: &str, expected: &str) -> Re
     }
 
     let expected_value: Value = serde_json::from_str(expected)?;
-    match test.returns {
+    let mut response = match test.returns {
         Some(ReturnKind::State) => {
             let actual_value: Value = match serde_json::from_str(&stdout){
                 Result::Ok(r) => {r},
@@ -280,13 +310,8 @@ pub fn invoke_test(resource: &ResourceManifest, cwd: &str, expected: &str) -> Re
                     return Err(DscError::Operation(format!("Failed to parse json from test {}|{}|{} -> {err}", &test.executable, stdout, stderr)))
                 }
             };
-            let diff_properties = get_diff(&expected_value, &actual_value);
-            Ok(TestResult::Resource(ResourceTestResponse {
-                desired_state: expected_value,
-                actual_state: actual_value,
-                in_desired_state: diff_properties.is_empty(),
-                diff_properties,
-            }))
+            let differences = get_differences_using_schema(&expected_value, &actual_value, || get_schema_value(resource, cwd));
+            ResourceTestResponse::from_differences(expected_value, actual_value, differences)
         },
         Some(ReturnKind::StateAndDiff) => {
             // command should be returning actual state as a JSON line and a list of properties that differ as separate JSON line
@@ -299,12 +324,13 @@ pub fn invoke_test(resource: &ResourceManifest, cwd: &str, expected: &str) -> Re
                 return Err(DscError::Command(resource.resource_type.clone(), exit_code, "No diff properties returned".to_string()));
             };
             let diff_properties: Vec<String> = serde_json::from_str(diff_properties)?;
-            Ok(TestResult::Resource(ResourceTestResponse {
+            ResourceTestResponse {
                 desired_state: expected_value,
                 actual_state: actual_value,
                 in_desired_state: diff_properties.is_empty(),
                 diff_properties,
-            }))
+                differences: None,
+            }
         },
         None => {
             // perform a get and compare the result to the expected state
@@ -321,15 +347,14 @@ pub fn invoke_test(resource: &ResourceManifest, cwd: &str, expected: &str) -> Re
                     response.actual_state
                 }
             };
-            let diff_properties = get_diff( &expected_value, &actual_state);
-            Ok(TestResult::Resource(ResourceTestResponse {
-                desired_state: expected_value,
-                actual_state,
-                in_desired_state: diff_properties.is_empty(),
-                diff_properties,
-            }))
+            let differences = get_differences_using_schema(&expected_value, &actual_state, || get_schema_value(resource, cwd));
+            ResourceTestResponse::from_differences(expected_value, actual_state, differences)
         },
+    };
+    if not_in_desired_state {
+        response.in_desired_state = false;
     }
+    Ok(TestResult::Resource(response))
 }
 
 fn invoke_synthetic_test(resource: &ResourceManifest, cwd: &str, expected: &str) -> Result<TestResult, DscError> {
@@ -347,13 +372,48 @@ fn invoke_synthetic_test(resource: &ResourceManifest, cwd: &str, expected: &str)
         }
     };
     let expected_value: Value = serde_json::from_str(expected)?;
-    let diff_properties = get_diff(&expected_value, &actual_state);
-    Ok(TestResult::Resource(ResourceTestResponse {
-        desired_state: expected_value,
-        actual_state,
-        in_desired_state: diff_properties.is_empty(),
-        diff_properties,
-    }))
+    let differences = get_differences_using_schema(&expected_value, &actual_state, || get_schema_value(resource, cwd));
+    Ok(TestResult::Resource(ResourceTestResponse::from_differences(expected_value, actual_state, differences)))
+}
+
+/// Predict the result of a set operation for a resource that does not implement `whatIf`.
+///
+/// The prediction is computed from the current state returned by `test` (or synthetic test) and the
+/// desired state, so the `set` method of the resource is never invoked.
+///
+/// # Arguments
+///
+/// * `resource` - The resource manifest for the command resource.
+/// * `cwd` - The current working directory.
+/// * `desired` - The desired state of the resource in JSON.
+///
+/// # Errors
+///
+/// Error is returned if the resource does not implement `get` or the current state can't be retrieved.
+fn invoke_synthetic_what_if(resource: &ResourceManifest, cwd: &str, desired: &str) -> Result<SetResult, DscError> {
+    if resource.set.is_none() {
+        return Err(DscError::NotImplemented("set".to_string()));
+    }
+    if resource.get.is_none() {
+        return Err(DscError::NotSupported(format!("What-if can't be synthesized for resource '{}' as it does not implement get", &resource.resource_type)));
+    }
+
+    let test_result = invoke_test(resource, cwd, desired)?;
+    let response = match test_result {
+        TestResult::Group(_) => {
+            return Ok(test_result.into());
+        },
+        TestResult::Resource(response) => response,
+    };
+
+    // group resources return the results of their nested resources as the actual state
+    if resource.kind == Some(Kind::Group) {
+        if let Ok(group_response) = serde_json::from_value::<Vec<ResourceTestResult>>(response.actual_state.clone()) {
+            return Ok(TestResult::Group(group_response).into());
+        }
+    }
+
+    Ok(SetResult::Resource(ResourceSetResponse::from_test_response(response)))
 }
 
 /// Invoke the delete operation against a command resource.
@@ -374,11 +434,14 @@ pub fn invoke_delete(resource: &ResourceManifest, cwd: &str, filter: &str) -> Re
 
     verify_json(resource, cwd, filter)?;
 
-    let args = process_args(&delete.args, filter);
-    let command_input = get_command_input(&delete.input, filter)?;
+    let args = process_args(&delete.args, &delete.input, filter)?;
+    let command_input = get_command_input(&delete.input, resource.env_input.as_ref(), filter)?;
 
     info!("Invoking delete '{}' using '{}'", &resource.resource_type, &delete.executable);
-    let (_exit_code, _stdout, _stderr) = invoke_command(&delete.executable, args, command_input.stdin.as_deref(), Some(cwd), command_input.env, &resource.exit_codes)?;
+    let (exit_code, _stdout, _stderr) = invoke_resource_command(resource, cwd, &MethodCommand::new("delete", &delete.executable, delete.env.as_ref(), delete.working_directory.as_deref()), args, command_input, filter)?;
+    if get_exit_code_semantic(resource, exit_code) == Some(ExitCodeSemantic::NotFound) {
+        debug!("Delete '{}' returned not found exit code {exit_code}, instance is already absent", &resource.resource_type);
+    }
 
     Ok(())
 }
@@ -405,11 +468,11 @@ pub fn invoke_validate(resource: &ResourceManifest, cwd: &str, config: &str) ->
         return Err(DscError::NotImplemented("validate".to_string()));
     };
 
-    let args = process_args(&validate.args, config);
-    let command_input = get_command_input(&validate.input, config)?;
+    let args = process_args(&validate.args, &validate.input, config)?;
+    let command_input = get_command_input(&validate.input, resource.env_input.as_ref(), config)?;
 
     info!("Invoking validate '{}' using '{}'", &resource.resource_type, &validate.executable);
-    let (_exit_code, stdout, _stderr) = invoke_command(&validate.executable, args, command_input.stdin.as_deref(), Some(cwd), command_input.env, &resource.exit_codes)?;
+    let (_exit_code, stdout, _stderr) = invoke_resource_command(resource, cwd, &MethodCommand::new("validate", &validate.executable, validate.env.as_ref(), validate.working_directory.as_deref()), args, command_input, config)?;
     let result: ValidateResult = serde_json::from_str(&stdout)?;
     Ok(result)
 }
@@ -430,7 +493,10 @@ pub fn get_schema(resource: &ResourceManifest, cwd: &str) -> Result<String, DscE
 
     match schema_kind {
         SchemaKind::Command(ref command) => {
-            let (_exit_code, stdout, _stderr) = invoke_command(&command.executable, command.args.clone(), None, Some(cwd), None, &resource.exit_codes)?;
+            let mut env = HashMap::new();
+            add_command_path(&mut env, cwd);
+            let env = if env.is_empty() { None } else { Some(env) };
+            let (_exit_code, stdout, _stderr) = invoke_process(&resolve_executable(&command.executable, cwd), command.args.clone(), None, Some(cwd), env, resource.clean_environment == Some(true), &resource.exit_codes)?;
             Ok(stdout)
         },
         SchemaKind::Embedded(ref schema) => {
@@ -440,6 +506,12 @@ pub fn get_schema(resource: &ResourceManifest, cwd: &str) -> Result<String, DscE
     }
 }
 
+// the schema is only used for comparison annotations, so a missing or invalid schema is not an error here
+fn get_schema_value(resource: &ResourceManifest, cwd: &str) -> Option<Value> {
+    let schema = get_schema(resource, cwd).ok()?;
+    serde_json::from_str(&schema).ok()
+}
+
 /// Invoke the export operation on a resource
 ///
 /// # Arguments
@@ -466,15 +538,15 @@ pub fn invoke_export(resource: &ResourceManifest, cwd: &str, input: Option<&str>
         if !input.is_empty() {
             verify_json(resource, cwd, input)?;
 
-            command_input = get_command_input(&export.input, input)?;
+            command_input = get_command_input(&export.input, resource.env_input.as_ref(), input)?;
         }
 
-        args = process_args(&export.args, input);
+        args = process_args(&export.args, &export.input, input)?;
     } else {
-        args = process_args(&export.args, "");
+        args = process_args(&export.args, &export.input, "")?;
     }
 
-    let (_exit_code, stdout, stderr) = invoke_command(&export.executable, args, command_input.stdin.as_deref(), Some(cwd), command_input.env, &resource.exit_codes)?;
+    let (_exit_code, stdout, stderr) = invoke_resource_command(resource, cwd, &MethodCommand::new("export", &export.executable, export.env.as_ref(), export.working_directory.as_deref()), args, command_input, input.unwrap_or_default())?;
     let mut instances: Vec<Value> = Vec::new();
     for line in stdout.lines()
     {
@@ -516,11 +588,11 @@ pub fn invoke_resolve(resource: &ResourceManifest, cwd: &str, input: &str) -> Re
         return Err(DscError::Operation(format!("Resolve is not supported by resource {}", &resource.resource_type)));
     };
 
-    let args = process_args(&resolve.args, input);
-    let command_input = get_command_input(&resolve.input, input)?;
+    let args = process_args(&resolve.args, &resolve.input, input)?;
+    let command_input = get_command_input(&resolve.input, resource.env_input.as_ref(), input)?;
 
     info!("Invoking resolve '{}' using '{}'", &resource.resource_type, &resolve.executable);
-    let (_exit_code, stdout, _stderr) = invoke_command(&resolve.executable, args, command_input.stdin.as_deref(), Some(cwd), command_input.env, &resource.exit_codes)?;
+    let (_exit_code, stdout, _stderr) = invoke_resource_command(resource, cwd, &MethodCommand::new("resolve", &resolve.executable, resolve.env.as_ref(), resolve.working_directory.as_deref()), args, command_input, input)?;
     let result: ResolveResult = serde_json::from_str(&stdout)?;
     Ok(result)
 }
@@ -534,13 +606,14 @@ pub fn invoke_resolve(resource: &ResourceManifest, cwd: &str, input: &str) -> Re
 /// * `input` - Optional input to pass to the command
 /// * `cwd` - Optional working directory to execute the command in
 /// * `env` - Optional environment variable mappings to add or update
-/// * `exit_codes` - Optional descriptions of exit codes
+/// * `exit_codes` - Optional descriptions and semantics of exit codes
 ///
 /// # Errors
 ///
 /// Error is returned if the command fails to execute or stdin/stdout/stderr cannot be opened.
+/// Non-zero exit codes are only returned as success if the manifest gives them a semantic other than `retryable`.
 ///
-async fn run_process_async(executable: &str, args: Option<Vec<String>>, input: Option<&str>, cwd: Option<&str>, env: Option<HashMap<String, String>>, exit_codes: &Option<HashMap<i32, String>>) -> Result<(i32, String, String), DscError> {
+async fn run_process_async(executable: &str, args: Option<Vec<String>>, input: Option<&str>, cwd: Option<&str>, env: Option<HashMap<String, String>>, clean_env: bool, exit_codes: &Option<HashMap<i32, ExitCode>>) -> Result<(i32, String, String), DscError> {
 
     // use somewhat large initial buffer to avoid early string reallocations;
     // the value is based on list result of largest of built-in adapters - WMI adapter ~500KB
@@ -558,10 +631,14 @@ async fn run_process_async(executable: &str, args: Option<Vec<String>>, input: O
     if let Some(cwd) = cwd {
         command.current_dir(cwd);
     }
+    if clean_env {
+        command.env_clear();
+        command.envs(get_clean_environment());
+    }
     if let Some(env) = env {
         command.envs(env);
     }
-    if executable == "dsc" && env::var("DEBUG_DSC").is_ok() {
+    if Path::new(executable).file_stem() == Some(OsStr::new("dsc")) && env::var("DEBUG_DSC").is_ok() {
         // remove this env var from child process as it will fail reading from keyboard to allow attaching
         command.env_remove("DEBUG_DSC");
     }
@@ -622,10 +699,12 @@ async fn run_process_async(executable: &str, args: Option<Vec<String>>, input: O
         debug!("Process '{executable}' id {child_id} exited with code {code}");
 
         if code != 0 {
-            if let Some(exit_codes) = exit_codes {
-                if let Some(error_message) = exit_codes.get(&code) {
-                    return Err(DscError::CommandExitFromManifest(executable.to_string(), code, error_message.to_string()));
+            if let Some(exit_code) = exit_codes.as_ref().and_then(|exit_codes| exit_codes.get(&code)) {
+                // codes with a semantic other than retryable are acted on by the caller
+                if exit_code.semantic().is_some_and(|semantic| *semantic != ExitCodeSemantic::Retryable) {
+                    return Ok((code, stdout_result, stderr_result));
                 }
+                return Err(DscError::CommandExitFromManifest(executable.to_string(), code, exit_code.description().to_string(), stderr_result));
             }
             return Err(DscError::Command(executable.to_string(), code, stderr_result));
         }
@@ -646,35 +725,226 @@ async fn run_process_async(executable: &str, args: Option<Vec<String>>, input: O
 /// * `input` - Optional input to pass to the command
 /// * `cwd` - Optional working directory to execute the command in
 /// * `env` - Optional environment variable mappings to add or update
-/// * `exit_codes` - Optional descriptions of exit codes
+/// * `exit_codes` - Optional descriptions and semantics of exit codes
 ///
 /// # Errors
 ///
 /// Error is returned if the command fails to execute or stdin/stdout/stderr cannot be opened.
+/// Non-zero exit codes are only returned as success if the manifest gives them a semantic other than `retryable`.
 ///
 /// # Panics
 ///
 /// Will panic if tokio runtime can't be created.
 ///
 #[allow(clippy::implicit_hasher)]
-pub fn invoke_command(executable: &str, args: Option<Vec<String>>, input: Option<&str>, cwd: Option<&str>, env: Option<HashMap<String, String>>, exit_codes: &Option<HashMap<i32, String>>) -> Result<(i32, String, String), DscError> {
+pub fn invoke_command(executable: &str, args: Option<Vec<String>>, input: Option<&str>, cwd: Option<&str>, env: Option<HashMap<String, String>>, exit_codes: &Option<HashMap<i32, ExitCode>>) -> Result<(i32, String, String), DscError> {
+    let mut env = env.unwrap_or_default();
+    let executable = match cwd {
+        Some(cwd) => {
+            add_command_path(&mut env, cwd);
+            resolve_executable(executable, cwd)
+        },
+        None => executable.to_string(),
+    };
+    let env = if env.is_empty() { None } else { Some(env) };
+    invoke_process(&executable, args, input, cwd, env, false, exit_codes)
+}
+
+// every command of a resource or adapter is started here, so the execution policy is checked before it
+fn invoke_process(executable: &str, args: Option<Vec<String>>, input: Option<&str>, cwd: Option<&str>, env: Option<HashMap<String, String>>, clean_env: bool, exit_codes: &Option<HashMap<i32, ExitCode>>) -> Result<(i32, String, String), DscError> {
+    // executables are resolved before, so one that isn't a full path wasn't found
+    check_executable_policy(executable, Some(Path::new(executable)).filter(|path| path.is_absolute()))?;
     debug!("Invoking command '{}' with args {:?}", executable, args);
 
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .unwrap()
-        .block_on(run_process_async(executable, args, input, cwd, env, exit_codes))
+        .block_on(run_process_async(executable, args, input, cwd, env, clean_env, exit_codes))
 }
 
-fn process_args(args: &Option<Vec<ArgKind>>, value: &str) -> Option<Vec<String>> {
-    let Some(arg_values) = args else {
-        debug!("No args to process");
+/// Resolve an executable of a resource to the full path it is started with.
+///
+/// Executables are found like `find_executable()` finds them, so the file started is the one integrity verification
+/// and the execution policy checked, even if the `PATH` of the command finds another one first.
+///
+/// # Arguments
+///
+/// * `executable` - The executable as written in the manifest.
+/// * `directory` - The directory of the resource.
+///
+/// # Returns
+///
+/// The full path of the executable, or the executable as written if it isn't found.
+#[must_use]
+pub fn resolve_executable(executable: &str, directory: &str) -> String {
+    find_executable(executable, Path::new(directory)).map_or_else(|| executable.to_string(), |path| path.to_string_lossy().to_string())
+}
+
+/// Get the `PATH` for commands of a resource, with the directory of `dsc` and the one of the resource added
+/// so their executables are found without changing the `PATH` of the process.
+///
+/// # Arguments
+///
+/// * `directory` - The directory of the resource.
+///
+/// # Returns
+///
+/// The `PATH` for the commands, or `None` if the directories are already in it.
+#[must_use]
+pub fn get_command_path(directory: &str) -> Option<String> {
+    let mut paths = env::var_os("PATH").map(|path| env::split_paths(&path).collect::<Vec<PathBuf>>()).unwrap_or_default();
+    let exe_home = env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf));
+    let mut added = false;
+    for directory in exe_home.into_iter().chain([PathBuf::from(directory)]) {
+        if !paths.contains(&directory) {
+            paths.push(directory);
+            added = true;
+        }
+    }
+    if !added {
         return None;
+    }
+    env::join_paths(paths).ok().map(|path| path.to_string_lossy().to_string())
+}
+
+// a `PATH` set by the manifest or the input is kept as is
+fn add_command_path(env: &mut HashMap<String, String>, directory: &str) {
+    if env.keys().any(|name| name.eq_ignore_ascii_case("PATH")) {
+        return;
+    }
+    if let Some(path) = get_command_path(directory) {
+        env.insert("PATH".to_string(), path);
+    }
+}
+
+/// Get the environment variables passed to commands of resources requesting a clean environment.
+///
+/// `PATH` is the original one of the process, the directories added by `get_command_path()` are appended when the command is run.
+#[must_use]
+pub fn get_clean_environment() -> HashMap<String, String> {
+    env::vars().filter(|(name, _)| {
+        CLEAN_ENVIRONMENT_VARIABLES.iter().any(|allowed| allowed.eq_ignore_ascii_case(name)) || name.starts_with("LC_")
+    }).collect()
+}
+
+// how the command of a manifest method is run
+struct MethodCommand<'a> {
+    name: &'a str,
+    executable: &'a str,
+    env: Option<&'a HashMap<String, String>>,
+    working_directory: Option<&'a str>,
+}
+
+impl<'a> MethodCommand<'a> {
+    fn new(name: &'a str, executable: &'a str, env: Option<&'a HashMap<String, String>>, working_directory: Option<&'a str>) -> Self {
+        Self { name, executable, env, working_directory }
+    }
+}
+
+// exit codes with a semantic are checked against the operation, retryable ones are failures retried by the caller
+fn invoke_resource_command(resource: &ResourceManifest, cwd: &str, method: &MethodCommand, args: Option<Vec<String>>, command_input: CommandInput, input: &str) -> Result<(i32, String, String), DscError> {
+    let (exit_code, stdout, stderr) = run_resource_command(resource, cwd, method, args, command_input, input)?;
+    check_exit_code_semantic(resource, method, exit_code, &stderr)?;
+    Ok((exit_code, stdout, stderr))
+}
+
+// resources declaring a host receive the operation over their persistent connection instead of a new process
+fn run_resource_command(resource: &ResourceManifest, cwd: &str, method: &MethodCommand, args: Option<Vec<String>>, command_input: CommandInput, input: &str) -> Result<(i32, String, String), DscError> {
+    let clean_env = resource.clean_environment == Some(true);
+    let security_context = resource.security_context.clone().unwrap_or(SecurityContextKind::Current);
+    let wrapper = get_security_context_wrapper(&resource.resource_type, &security_context, resource.security_context_settings.as_ref())?;
+    if resource.host.is_some() && wrapper.is_some() {
+        return Err(DscError::SecurityContext(format!("The host of resource '{}' can't be run through a security context wrapper", resource.resource_type)));
+    }
+
+    // variables declared by the manifest take precedence over the ones from the input
+    let mut env = command_input.env.unwrap_or_default();
+    if let Some(method_env) = method.env {
+        env.extend(method_env.clone());
+    }
+    let working_directory = method.working_directory.map(|working_directory| Path::new(cwd).join(working_directory).to_string_lossy().to_string());
+    if let Some(host) = &resource.host {
+        // the host was started with the `PATH` of the resource, the variables and directory apply to the operation only
+        let resource_host = get_resource_host(host, cwd, clean_env)?;
+        let env = if env.is_empty() { None } else { Some(env) };
+        return resource_host.invoke(method.name, input, env, working_directory, &resource.exit_codes);
+    }
+    add_command_path(&mut env, cwd);
+    let env = if env.is_empty() { None } else { Some(env) };
+    let working_directory = working_directory.unwrap_or_else(|| cwd.to_string());
+    if let Some(wrapper) = wrapper {
+        // the policy applies to the executable of the resource as well as to the wrapper
+        check_executable_policy(method.executable, find_executable(method.executable, Path::new(cwd)).as_deref())?;
+        let (executable, args) = wrap_command(&wrapper, method.executable, args, cwd);
+        return invoke_process(&executable, args, command_input.stdin.as_deref(), Some(&working_directory), env, clean_env, &resource.exit_codes);
+    }
+    invoke_process(&resolve_executable(method.executable, cwd), args, command_input.stdin.as_deref(), Some(&working_directory), env, clean_env, &resource.exit_codes)
+}
+
+fn get_exit_code_semantic(resource: &ResourceManifest, exit_code: i32) -> Option<ExitCodeSemantic> {
+    resource.exit_codes.as_ref()?.get(&exit_code)?.semantic().copied()
+}
+
+// records the signals of a non-zero exit code and fails if its semantic doesn't apply to the operation
+fn check_exit_code_semantic(resource: &ResourceManifest, method: &MethodCommand, exit_code: i32, stderr: &str) -> Result<(), DscError> {
+    let Some(code) = resource.exit_codes.as_ref().and_then(|exit_codes| exit_codes.get(&exit_code)) else {
+        return Ok(());
+    };
+    match code.semantic() {
+        Some(ExitCodeSemantic::RebootRequired) => {
+            info!("Resource '{}' {} requires a reboot: {}", &resource.resource_type, method.name, code.description());
+            EXIT_CODE_SIGNALS.with(|signals| signals.borrow_mut().reboot_required = true);
+        },
+        Some(ExitCodeSemantic::Warning) => {
+            warn!("Resource '{}' {}: {}", &resource.resource_type, method.name, code.description());
+            EXIT_CODE_SIGNALS.with(|signals| signals.borrow_mut().warnings.push(code.description().to_string()));
+        },
+        Some(ExitCodeSemantic::NotInDesiredState) if method.name == "test" => {},
+        Some(ExitCodeSemantic::NotFound) if method.name == "get" || method.name == "delete" => {},
+        _ if exit_code == 0 => {},
+        _ => {
+            return Err(DscError::CommandExitFromManifest(method.executable.to_string(), exit_code, code.description().to_string(), stderr.to_string()));
+        },
+    }
+    Ok(())
+}
+
+// the instance requested by the filter marked as not existing
+fn get_not_found_state(filter: &str) -> Result<Value, DscError> {
+    let mut state = if filter.is_empty() {
+        Map::new()
+    } else {
+        match serde_json::from_str::<Value>(filter)? {
+            Value::Object(properties) => properties,
+            _ => Map::new(),
+        }
+    };
+    state.insert("_exist".to_string(), Value::Bool(false));
+    Ok(Value::Object(state))
+}
+
+fn process_args(args: &Option<Vec<ArgKind>>, input_kind: &Option<InputKind>, value: &str) -> Result<Option<Vec<String>>, DscError> {
+    let input_as_args = input_kind == &Some(InputKind::Args);
+    if args.is_none() && !input_as_args {
+        debug!("No args to process");
+        return Ok(None);
+    }
+
+    // properties are only parsed when arguments are rendered from them
+    let uses_properties = input_as_args || args.iter().flatten().any(|arg| matches!(arg, ArgKind::Property { .. }));
+    let properties = if uses_properties && !value.is_empty() {
+        match serde_json::from_str::<Value>(value)? {
+            Value::Object(properties) => properties,
+            _ => return Err(DscError::Operation("Input must be a JSON object to render arguments from its properties".to_string())),
+        }
+    } else {
+        Map::new()
     };
 
     let mut processed_args = Vec::<String>::new();
-    for arg in arg_values {
+    let mut rendered_properties = Vec::<&str>::new();
+    for arg in args.iter().flatten() {
         match arg {
             ArgKind::String(s) => {
                 processed_args.push(s.clone());
@@ -687,10 +957,56 @@ fn process_args(args: &Option<Vec<ArgKind>>, value: &str) -> Option<Vec<String>>
                 processed_args.push(json_input_arg.clone());
                 processed_args.push(value.to_string());
             },
+            ArgKind::Property { property_arg, template } => {
+                rendered_properties.push(property_arg);
+                let Some(property_value) = properties.get(property_arg) else {
+                    continue;
+                };
+                match template {
+                    Some(template) => render_property_args(template, property_value, &mut processed_args),
+                    None => render_property_args(&["{value}".to_string()], property_value, &mut processed_args),
+                }
+            },
         }
     }
 
-    Some(processed_args)
+    if input_as_args {
+        for (name, property_value) in &properties {
+            // well-known properties like `_exist` are not passed to the command
+            if name.starts_with('_') || rendered_properties.contains(&name.as_str()) {
+                continue;
+            }
+            let flag = format!("--{name}");
+            if property_value.is_boolean() {
+                render_property_args(&[flag], property_value, &mut processed_args);
+            } else {
+                render_property_args(&[flag, "{value}".to_string()], property_value, &mut processed_args);
+            }
+        }
+    }
+
+    Ok(Some(processed_args))
+}
+
+// unset properties and false booleans are omitted, arrays repeat the arguments for each item
+fn render_property_args(template: &[String], value: &Value, processed_args: &mut Vec<String>) {
+    match value {
+        Value::Null | Value::Bool(false) => {},
+        Value::Array(items) => {
+            for item in items {
+                render_property_args(template, item, processed_args);
+            }
+        },
+        _ => {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                _ => value.to_string(),
+            };
+            for arg in template {
+                processed_args.push(arg.replace("{value}", &value));
+            }
+        },
+    }
 }
 
 struct CommandInput {
@@ -698,18 +1014,22 @@ struct CommandInput {
     stdin: Option<String>,
 }
 
-fn get_command_input(input_kind: &Option<InputKind>, input: &str) -> Result<CommandInput, DscError> {
+fn get_command_input(input_kind: &Option<InputKind>, env_input: Option<&EnvInput>, input: &str) -> Result<CommandInput, DscError> {
     let mut env: Option<HashMap<String, String>> = None;
     let mut stdin: Option<String> = None;
     match input_kind {
         Some(InputKind::Env) => {
             debug!("Parsing input as environment variables");
-            env = Some(json_to_hashmap(input)?);
+            env = Some(json_to_hashmap(input, env_input)?);
         },
         Some(InputKind::Stdin) => {
             debug!("Parsing input as stdin");
             stdin = Some(input.to_string());
         },
+        Some(InputKind::Args) => {
+            debug!("Input is passed as arguments");
+            // the arguments are rendered by `process_args`
+        },
         None => {
             debug!("No input kind specified");
             // leave input as none
@@ -759,49 +1079,73 @@ fn verify_json(resource: &ResourceManifest, cwd: &str, json: &str) -> Result<(),
     Ok(())
 }
 
-fn json_to_hashmap(json: &str) -> Result<HashMap<String, String>, DscError> {
+fn json_to_hashmap(json: &str, env_input: Option<&EnvInput>) -> Result<HashMap<String, String>, DscError> {
+    let default_env_input = EnvInput::default();
+    let env_input = env_input.unwrap_or(&default_env_input);
+    let prefix = env_input.prefix.as_deref().unwrap_or_default();
     let mut map = HashMap::new();
     let json: Value = serde_json::from_str(json)?;
     if let Value::Object(obj) = json {
         for (key, value) in obj {
-            match value {
-                Value::String(s) => {
-                    map.insert(key, s);
-                },
-                Value::Bool(b) => {
-                    map.insert(key, b.to_string());
-                },
-                Value::Number(n) => {
-                    map.insert(key, n.to_string());
+            add_env_var(&mut map, format!("{prefix}{key}"), value, env_input)?;
+        }
+    }
+    Ok(map)
+}
+
+// nested objects are flattened by joining the property names with the separator
+fn add_env_var(map: &mut HashMap<String, String>, name: String, value: Value, env_input: &EnvInput) -> Result<(), DscError> {
+    let separator = env_input.separator.as_deref().unwrap_or(DEFAULT_ENV_SEPARATOR);
+    match value {
+        Value::String(s) => {
+            map.insert(name, s);
+        },
+        Value::Bool(b) => {
+            map.insert(name, b.to_string());
+        },
+        Value::Number(n) => {
+            map.insert(name, n.to_string());
+        },
+        Value::Null => {},
+        Value::Object(obj) => {
+            for (key, value) in obj {
+                add_env_var(map, format!("{name}{separator}{key}"), value, env_input)?;
+            }
+        },
+        Value::Array(a) => {
+            let delimiter = match env_input.array_encoding {
+                Some(ArrayEncoding::Json) => {
+                    map.insert(name, Value::Array(a).to_string());
+                    return Ok(());
                 },
-                Value::Array(a) => {
-                    // only array of number or strings is supported
-                    let mut array = Vec::new();
-                    for v in a {
-                        match v {
-                            Value::String(s) => {
-                                array.push(s);
-                            },
-                            Value::Number(n) => {
-                                array.push(n.to_string());
-                            },
-                            _ => {
-                                return Err(DscError::Operation(format!("Unsupported array value for key {key}.  Only string and number is supported.")));
-                            },
-                        }
+                Some(ArrayEncoding::Indexed) => {
+                    for (index, v) in a.into_iter().enumerate() {
+                        add_env_var(map, format!("{name}{separator}{index}"), v, env_input)?;
                     }
-                    map.insert(key, array.join(","));
+                    return Ok(());
                 },
-                Value::Null => {
-                    continue;
+                Some(ArrayEncoding::Newline) => "\n",
+                Some(ArrayEncoding::Comma) | None => ",",
+            };
+            // only array of number or strings is supported when joining the items
+            let mut array = Vec::new();
+            for v in a {
+                match v {
+                    Value::String(s) => {
+                        array.push(s);
+                    },
+                    Value::Number(n) => {
+                        array.push(n.to_string());
+                    },
+                    _ => {
+                        return Err(DscError::Operation(format!("Unsupported array value for key {name}.  Only string and number is supported unless the array encoding is json or indexed.")));
+                    },
                 }
-                Value::Object(_) => {
-                    return Err(DscError::Operation(format!("Unsupported value for key {key}.  Only string, bool, number, and array is supported.")));
-                },
             }
-        }
+            map.insert(name, array.join(delimiter));
+        },
     }
-    Ok(map)
+    Ok(())
 }
 
 /// Log output from a process as traces.
@@ -0,0 +1,491 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use schemars::{gen::SchemaGenerator, JsonSchema};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, sync::{Arc, OnceLock, RwLock}};
+use tracing::{debug, info};
+
+use crate::{configure::config_doc::ExecutionKind, dscerror::DscError};
+use super::{
+    dscresource::{Capability, DscResource, ImplementedAs},
+    invoke_result::{ExportResult, GetResult, ResourceGetResponse, ResourceSetResponse, ResourceTestResponse, SetResult, TestResult},
+    property_diff::{get_changes, get_differences},
+    resource_manifest::Kind,
+};
+
+/// A resource implemented in Rust and invoked within the `dsc` process instead of running a command.
+///
+/// Only `get` is required, the other operations are only invoked if listed in the capabilities.
+pub trait CustomResource: Send + Sync {
+    /// The properties of an instance of the resource, the schema of the resource is generated from it.
+    type Properties: Serialize + DeserializeOwned + JsonSchema;
+
+    /// The namespaced name of the resource.
+    fn type_name(&self) -> &str;
+
+    /// The version of the resource.
+    fn version(&self) -> &str;
+
+    /// The description of the resource.
+    fn description(&self) -> Option<&str> {
+        None
+    }
+
+    /// The operations implemented by the resource.
+    fn capabilities(&self) -> Vec<Capability> {
+        vec![Capability::Get]
+    }
+
+    /// Get the actual state of an instance.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the state can't be retrieved.
+    fn get(&self, filter: Self::Properties) -> Result<Self::Properties, DscError>;
+
+    /// Apply the desired state of an instance, or only predict the result if `what_if` is set.
+    ///
+    /// # Returns
+    ///
+    /// The state of the instance after applying the desired state.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the desired state can't be applied.
+    fn set(&self, _desired: Self::Properties, _what_if: bool) -> Result<Self::Properties, DscError> {
+        Err(DscError::NotImplemented("set".to_string()))
+    }
+
+    /// Test whether an instance is in the desired state, the differences are computed from the `get` result.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the state can't be tested.
+    fn test(&self, _desired: Self::Properties) -> Result<bool, DscError> {
+        Err(DscError::NotImplemented("test".to_string()))
+    }
+
+    /// Delete an instance.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the instance can't be deleted.
+    fn delete(&self, _filter: Self::Properties) -> Result<(), DscError> {
+        Err(DscError::NotImplemented("delete".to_string()))
+    }
+
+    /// Get the actual state of all instances, optionally matching a filter.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the instances can't be retrieved.
+    fn export(&self, _filter: Option<Self::Properties>) -> Result<Vec<Self::Properties>, DscError> {
+        Err(DscError::NotImplemented("export".to_string()))
+    }
+}
+
+// object safe form of `CustomResource` exchanging JSON so resources with different properties can be registered together
+trait RegisteredResource: Send + Sync {
+    fn type_name(&self) -> &str;
+    fn version(&self) -> &str;
+    fn description(&self) -> Option<&str>;
+    fn capabilities(&self) -> Vec<Capability>;
+    fn schema(&self) -> Result<Value, DscError>;
+    fn get(&self, filter: &str) -> Result<Value, DscError>;
+    fn set(&self, desired: &str, what_if: bool) -> Result<Value, DscError>;
+    fn test(&self, desired: &str) -> Result<bool, DscError>;
+    fn delete(&self, filter: &str) -> Result<(), DscError>;
+    fn export(&self, filter: Option<&str>) -> Result<Vec<Value>, DscError>;
+}
+
+impl<R: CustomResource> RegisteredResource for R {
+    fn type_name(&self) -> &str {
+        CustomResource::type_name(self)
+    }
+
+    fn version(&self) -> &str {
+        CustomResource::version(self)
+    }
+
+    fn description(&self) -> Option<&str> {
+        CustomResource::description(self)
+    }
+
+    fn capabilities(&self) -> Vec<Capability> {
+        CustomResource::capabilities(self)
+    }
+
+    fn schema(&self) -> Result<Value, DscError> {
+        let schema = SchemaGenerator::default().into_root_schema_for::<R::Properties>();
+        Ok(serde_json::to_value(schema)?)
+    }
+
+    fn get(&self, filter: &str) -> Result<Value, DscError> {
+        let actual = CustomResource::get(self, parse_properties(filter)?)?;
+        Ok(serde_json::to_value(actual)?)
+    }
+
+    fn set(&self, desired: &str, what_if: bool) -> Result<Value, DscError> {
+        let after = CustomResource::set(self, parse_properties(desired)?, what_if)?;
+        Ok(serde_json::to_value(after)?)
+    }
+
+    fn test(&self, desired: &str) -> Result<bool, DscError> {
+        CustomResource::test(self, parse_properties(desired)?)
+    }
+
+    fn delete(&self, filter: &str) -> Result<(), DscError> {
+        CustomResource::delete(self, parse_properties(filter)?)
+    }
+
+    fn export(&self, filter: Option<&str>) -> Result<Vec<Value>, DscError> {
+        let filter = match filter {
+            Some(filter) if !filter.is_empty() => Some(parse_properties(filter)?),
+            _ => None,
+        };
+        let mut instances = Vec::new();
+        for instance in CustomResource::export(self, filter)? {
+            instances.push(serde_json::to_value(instance)?);
+        }
+        Ok(instances)
+    }
+}
+
+// an empty input is treated as an instance without properties
+fn parse_properties<T: DeserializeOwned>(input: &str) -> Result<T, DscError> {
+    let input = if input.is_empty() { "{}" } else { input };
+    Ok(serde_json::from_str(input)?)
+}
+
+type Registry = RwLock<BTreeMap<String, Arc<dyn RegisteredResource>>>;
+
+fn get_registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(BTreeMap::new()))
+}
+
+/// Register a resource implemented in Rust so it's discovered alongside command resources.
+///
+/// A resource registered with the same type name as an existing one replaces it.
+///
+/// # Arguments
+///
+/// * `resource` - The resource to register.
+///
+/// # Panics
+///
+/// Panics if the lock on the registry is poisoned.
+pub fn register_custom_resource<R: CustomResource + 'static>(resource: R) {
+    let type_name = CustomResource::type_name(&resource).to_lowercase();
+    info!("Registering custom resource '{}'", CustomResource::type_name(&resource));
+    get_registry().write().unwrap().insert(type_name, Arc::new(resource));
+}
+
+fn get_registered_resource(type_name: &str) -> Result<Arc<dyn RegisteredResource>, DscError> {
+    match get_registry().read().unwrap().get(&type_name.to_lowercase()) {
+        Some(resource) => Ok(resource.clone()),
+        None => Err(DscError::ResourceNotFound(type_name.to_string())),
+    }
+}
+
+/// Get the registered custom resources.
+///
+/// # Panics
+///
+/// Panics if the lock on the registry is poisoned.
+#[must_use]
+pub fn get_custom_resources() -> Vec<DscResource> {
+    let resources: Vec<Arc<dyn RegisteredResource>> = get_registry().read().unwrap().values().cloned().collect();
+    resources.iter().map(|resource| {
+        // the property names are taken from the generated schema
+        let properties = resource.schema().ok()
+            .and_then(|schema| schema.get("properties").and_then(Value::as_object).map(|properties| properties.keys().cloned().collect()))
+            .unwrap_or_default();
+        DscResource {
+            type_name: resource.type_name().to_string(),
+            kind: Kind::Resource,
+            version: resource.version().to_string(),
+            capabilities: resource.capabilities(),
+            description: resource.description().map(ToString::to_string),
+            implemented_as: ImplementedAs::Custom(resource.type_name().to_string()),
+            properties,
+            ..Default::default()
+        }
+    }).collect()
+}
+
+/// Invoke the get operation against a custom resource.
+///
+/// # Arguments
+///
+/// * `type_name` - The type of the registered resource.
+/// * `filter` - The filter to apply to the resource in JSON.
+///
+/// # Errors
+///
+/// Error returned if the resource isn't registered or the operation fails.
+pub fn invoke_get(type_name: &str, filter: &str) -> Result<GetResult, DscError> {
+    let resource = get_registered_resource(type_name)?;
+    debug!("Invoking get for custom resource '{type_name}'");
+    Ok(GetResult::Resource(ResourceGetResponse {
+        actual_state: resource.get(filter)?,
+    }))
+}
+
+/// Invoke the set operation against a custom resource.
+///
+/// # Arguments
+///
+/// * `type_name` - The type of the registered resource.
+/// * `desired` - The desired state of the resource in JSON.
+/// * `skip_test` - If true, the test operation is not used to skip resources already in the desired state.
+/// * `execution_type` - Whether to apply the desired state or only predict the result.
+///
+/// # Errors
+///
+/// Error returned if the resource isn't registered, doesn't implement set, or the operation fails.
+pub fn invoke_set(type_name: &str, desired: &str, skip_test: bool, execution_type: &ExecutionKind) -> Result<SetResult, DscError> {
+    let resource = get_registered_resource(type_name)?;
+    let capabilities = resource.capabilities();
+    if !capabilities.contains(&Capability::Set) {
+        return Err(DscError::NotImplemented("set".to_string()));
+    }
+    let what_if = execution_type == &ExecutionKind::WhatIf;
+    if what_if && !capabilities.contains(&Capability::WhatIf) {
+        info!("Custom resource '{type_name}' does not implement what-if, performing synthetic what-if");
+        let response = get_test_response(resource.as_ref(), desired)?;
+        return Ok(SetResult::Resource(ResourceSetResponse::from_test_response(response)));
+    }
+
+    if !skip_test && !what_if {
+        let response = get_test_response(resource.as_ref(), desired)?;
+        if response.in_desired_state {
+            return Ok(SetResult::Resource(ResourceSetResponse::from_test_response(response)));
+        }
+    }
+
+    debug!("Invoking set for custom resource '{type_name}'");
+    let before_state = resource.get(desired)?;
+    let after_state = resource.set(desired, what_if)?;
+    let changes = get_changes(&before_state, &after_state, Some(&resource.schema()?));
+    Ok(SetResult::Resource(ResourceSetResponse::from_changes(before_state, after_state, changes)))
+}
+
+/// Invoke the test operation against a custom resource.
+///
+/// Resources that don't implement test are tested by comparing the desired state to the result of get.
+///
+/// # Arguments
+///
+/// * `type_name` - The type of the registered resource.
+/// * `expected` - The expected state of the resource in JSON.
+///
+/// # Errors
+///
+/// Error returned if the resource isn't registered or the operation fails.
+pub fn invoke_test(type_name: &str, expected: &str) -> Result<TestResult, DscError> {
+    let resource = get_registered_resource(type_name)?;
+    debug!("Invoking test for custom resource '{type_name}'");
+    Ok(TestResult::Resource(get_test_response(resource.as_ref(), expected)?))
+}
+
+// the result of the test of the resource takes precedence, differences aren't reported for an instance it considers in the desired state
+fn get_test_response(resource: &dyn RegisteredResource, expected: &str) -> Result<ResourceTestResponse, DscError> {
+    let desired_state: Value = parse_properties(expected)?;
+    let actual_state = resource.get(expected)?;
+    let mut differences = get_differences(&desired_state, &actual_state, Some(&resource.schema()?));
+    let in_desired_state = if resource.capabilities().contains(&Capability::Test) {
+        Some(resource.test(expected)?)
+    } else {
+        None
+    };
+    if in_desired_state == Some(true) {
+        differences.clear();
+    }
+    let mut response = ResourceTestResponse::from_differences(desired_state, actual_state, differences);
+    if let Some(in_desired_state) = in_desired_state {
+        response.in_desired_state = in_desired_state;
+    }
+    Ok(response)
+}
+
+/// Invoke the delete operation against a custom resource.
+///
+/// # Arguments
+///
+/// * `type_name` - The type of the registered resource.
+/// * `filter` - The filter to apply to the resource in JSON.
+///
+/// # Errors
+///
+/// Error returned if the resource isn't registered, doesn't implement delete, or the operation fails.
+pub fn invoke_delete(type_name: &str, filter: &str) -> Result<(), DscError> {
+    let resource = get_registered_resource(type_name)?;
+    if !resource.capabilities().contains(&Capability::Delete) {
+        return Err(DscError::NotImplemented("delete".to_string()));
+    }
+    debug!("Invoking delete for custom resource '{type_name}'");
+    resource.delete(filter)
+}
+
+/// Get the schema of a custom resource generated from its properties.
+///
+/// # Arguments
+///
+/// * `type_name` - The type of the registered resource.
+///
+/// # Errors
+///
+/// Error returned if the resource isn't registered.
+pub fn get_schema(type_name: &str) -> Result<String, DscError> {
+    let resource = get_registered_resource(type_name)?;
+    Ok(resource.schema()?.to_string())
+}
+
+/// Invoke the export operation against a custom resource.
+///
+/// # Arguments
+///
+/// * `type_name` - The type of the registered resource.
+/// * `input` - The optional filter for the instances in JSON.
+///
+/// # Errors
+///
+/// Error returned if the resource isn't registered, doesn't implement export, or the operation fails.
+pub fn invoke_export(type_name: &str, input: Option<&str>) -> Result<ExportResult, DscError> {
+    let resource = get_registered_resource(type_name)?;
+    if !resource.capabilities().contains(&Capability::Export) {
+        return Err(DscError::Operation(format!("Export is not supported by resource {type_name}")));
+    }
+    debug!("Invoking export for custom resource '{type_name}'");
+    Ok(ExportResult {
+        actual_state: resource.export(input)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dscresources::dscresource::Invoke;
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+    #[serde(deny_unknown_fields)]
+    struct Setting {
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+    }
+
+    struct SettingResource;
+
+    impl CustomResource for SettingResource {
+        type Properties = Setting;
+
+        fn type_name(&self) -> &'static str {
+            "Test/CustomSetting"
+        }
+
+        fn version(&self) -> &'static str {
+            "1.0.0"
+        }
+
+        fn capabilities(&self) -> Vec<Capability> {
+            vec![Capability::Get, Capability::Set, Capability::Export]
+        }
+
+        fn get(&self, filter: Setting) -> Result<Setting, DscError> {
+            Ok(Setting { name: filter.name, value: Some("current".to_string()) })
+        }
+
+        fn set(&self, desired: Setting, _what_if: bool) -> Result<Setting, DscError> {
+            Ok(desired)
+        }
+
+        fn export(&self, _filter: Option<Setting>) -> Result<Vec<Setting>, DscError> {
+            Ok(vec![Setting { name: "first".to_string(), value: None }, Setting { name: "second".to_string(), value: None }])
+        }
+    }
+
+    // the value is compared without case by its own test
+    struct CaseInsensitiveResource;
+
+    impl CustomResource for CaseInsensitiveResource {
+        type Properties = Setting;
+
+        fn type_name(&self) -> &'static str {
+            "Test/CustomCaseInsensitive"
+        }
+
+        fn version(&self) -> &'static str {
+            "1.0.0"
+        }
+
+        fn capabilities(&self) -> Vec<Capability> {
+            vec![Capability::Get, Capability::Test]
+        }
+
+        fn get(&self, filter: Setting) -> Result<Setting, DscError> {
+            Ok(Setting { name: filter.name, value: Some("current".to_string()) })
+        }
+
+        fn test(&self, desired: Setting) -> Result<bool, DscError> {
+            Ok(desired.value.is_none_or(|value| value.eq_ignore_ascii_case("current")))
+        }
+    }
+
+    fn get_resource() -> DscResource {
+        register_custom_resource(SettingResource);
+        get_custom_resources().into_iter().find(|resource| resource.type_name == "Test/CustomSetting").unwrap()
+    }
+
+    #[test]
+    fn registered_resource_discovered() {
+        let resource = get_resource();
+        assert_eq!(resource.implemented_as, ImplementedAs::Custom("Test/CustomSetting".to_string()));
+        assert_eq!(resource.properties, vec!["name".to_string(), "value".to_string()]);
+        assert!(resource.capabilities.contains(&Capability::Export));
+    }
+
+    #[test]
+    fn synthetic_test_and_set() {
+        let resource = get_resource();
+        let TestResult::Resource(response) = resource.test(r#"{"name":"a","value":"desired"}"#).unwrap() else {
+            panic!("expected resource result");
+        };
+        assert!(!response.in_desired_state);
+        assert_eq!(response.diff_properties, vec!["value".to_string()]);
+
+        let SetResult::Resource(response) = resource.set(r#"{"name":"a","value":"desired"}"#, false, &ExecutionKind::Actual).unwrap() else {
+            panic!("expected resource result");
+        };
+        assert_eq!(response.after_state["value"], "desired");
+        assert_eq!(response.changed_properties, Some(vec!["value".to_string()]));
+    }
+
+    #[test]
+    fn test_of_resource_takes_precedence() {
+        register_custom_resource(CaseInsensitiveResource);
+        let resource = get_custom_resources().into_iter().find(|resource| resource.type_name == "Test/CustomCaseInsensitive").unwrap();
+        let TestResult::Resource(response) = resource.test(r#"{"name":"a","value":"CURRENT"}"#).unwrap() else {
+            panic!("expected resource result");
+        };
+        assert!(response.in_desired_state);
+        assert!(response.diff_properties.is_empty());
+
+        let TestResult::Resource(response) = resource.test(r#"{"name":"a","value":"other"}"#).unwrap() else {
+            panic!("expected resource result");
+        };
+        assert!(!response.in_desired_state);
+        assert_eq!(response.diff_properties, vec!["value".to_string()]);
+    }
+
+    #[test]
+    fn invalid_properties() {
+        let resource = get_resource();
+        assert!(resource.get(r#"{"name":"a","unknown":true}"#).is_err());
+        assert!(resource.delete(r#"{"name":"a"}"#).is_err());
+    }
+}
@@ -1,15 +1,18 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use crate::{configure::config_doc::ExecutionKind, dscresources::resource_manifest::Kind};
+use crate::{configure::config_doc::ExecutionKind, dscresources::resource_manifest::Kind, policy::{check_resource_policy, check_set_policy}};
 use dscerror::DscError;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use tracing::debug;
 
-use super::{command_resource, dscerror, invoke_result::{ExportResult, GetResult, ResolveResult, ResourceTestResponse, SetResult, TestResult, ValidateResult}, resource_manifest::import_manifest};
+use super::{command_resource, custom_resource, dscerror, elevation::InstanceSecurityContext, invoke_result::{ExportResult, GetResult, ResolveResult, ResourceTestResponse, SetResult, TestResult, ValidateResult}, property_diff::{get_differences, get_differences_using_schema, get_property_names}, resource_manifest::{import_manifest, ResourceManifest}};
+
+/// The property added to the input of an adapter to identify the adapted resource.
+pub const ADAPTED_TYPE_PROPERTY: &str = "adapted_dsc_type";
 
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
@@ -41,6 +44,12 @@ pub struct DscResource {
     pub require_adapter: Option<String>,
     /// The manifest of the resource.
     pub manifest: Option<Value>,
+    /// The adapter used to invoke the resource, resolved during discovery.
+    #[serde(skip)]
+    pub adapter: Option<Box<DscResource>>,
+    /// The security context of the configuration instance the resource is invoked for.
+    #[serde(skip)]
+    pub security_context: Option<InstanceSecurityContext>,
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
@@ -88,7 +97,72 @@ impl DscResource {
             properties: Vec::new(),
             require_adapter: None,
             manifest: None,
+            adapter: None,
+            security_context: None,
+        }
+    }
+
+    // the commands of the manifest run in the security context of the configuration instance
+    pub(crate) fn import_manifest(&self, manifest: &Value) -> Result<ResourceManifest, DscError> {
+        let mut resource_manifest = import_manifest(manifest.clone())?;
+        if let Some(security_context) = &self.security_context {
+            security_context.apply(&mut resource_manifest);
         }
+        Ok(resource_manifest)
+    }
+
+    // adapted resources are invoked using the manifest of their adapter
+    fn get_adapter_manifest(&self) -> Result<Option<(ResourceManifest, &str)>, DscError> {
+        let Some(adapter_type) = &self.require_adapter else {
+            return Ok(None);
+        };
+        let Some(adapter) = &self.adapter else {
+            return Err(DscError::AdapterNotFound(adapter_type.clone()));
+        };
+        let Some(manifest) = &adapter.manifest else {
+            return Err(DscError::MissingManifest(adapter.type_name.clone()));
+        };
+        let adapter_manifest = self.import_manifest(manifest)?;
+        if adapter_manifest.adapter.is_none() {
+            return Err(DscError::Operation(format!("Resource '{}' required by '{}' is not an adapter", adapter.type_name, self.type_name)));
+        }
+        debug!("Invoking resource '{}' using adapter '{}'", self.type_name, adapter.type_name);
+        Ok(Some((adapter_manifest, adapter.directory.as_str())))
+    }
+
+    // the adapter identifies the adapted resource using the `adapted_dsc_type` property of the input
+    fn get_adapter_input(&self, input: &str) -> Result<String, DscError> {
+        let mut value: Value = if input.is_empty() {
+            Value::Object(Map::new())
+        } else {
+            serde_json::from_str(input)?
+        };
+        let Some(map) = value.as_object_mut() else {
+            return Err(DscError::Operation(format!("Input for adapted resource '{}' must be a JSON object", self.type_name)));
+        };
+        map.insert(ADAPTED_TYPE_PROPERTY.to_string(), Value::String(self.type_name.clone()));
+        Ok(value.to_string())
+    }
+
+    fn invoke_synthetic_test(&self, expected: &str) -> Result<TestResult, DscError> {
+        let get_result = self.get(expected)?;
+        let desired_state = serde_json::from_str(expected)?;
+        let actual_state = match get_result {
+            GetResult::Group(results) => {
+                let mut result_array: Vec<Value> = Vec::new();
+                for result in results {
+                    result_array.push(serde_json::to_value(result)?);
+                }
+                Value::from(result_array)
+            },
+            GetResult::Resource(response) => {
+                response.actual_state
+            }
+        };
+        let differences = get_differences_using_schema(&desired_state, &actual_state, || {
+            self.schema().ok().and_then(|schema| serde_json::from_str(&schema).ok())
+        });
+        Ok(TestResult::Resource(ResourceTestResponse::from_differences(desired_state, actual_state, differences)))
     }
 }
 
@@ -189,15 +263,19 @@ pub trait Invoke {
 impl Invoke for DscResource {
     fn get(&self, filter: &str) -> Result<GetResult, DscError> {
         debug!("Invoking get for resource: {}", self.type_name);
+        check_resource_policy(self)?;
+        if let Some((adapter_manifest, directory)) = self.get_adapter_manifest()? {
+            return command_resource::invoke_get(&adapter_manifest, directory, &self.get_adapter_input(filter)?);
+        }
         match &self.implemented_as {
-            ImplementedAs::Custom(_custom) => {
-                Err(DscError::NotImplemented("get custom resources".to_string()))
+            ImplementedAs::Custom(custom) => {
+                custom_resource::invoke_get(custom, filter)
             },
             ImplementedAs::Command => {
                 let Some(manifest) = &self.manifest else {
                     return Err(DscError::MissingManifest(self.type_name.clone()));
                 };
-                let resource_manifest = import_manifest(manifest.clone())?;
+                let resource_manifest = self.import_manifest(manifest)?;
                 command_resource::invoke_get(&resource_manifest, &self.directory, filter)
             },
         }
@@ -205,15 +283,22 @@ impl Invoke for DscResource {
 
     fn set(&self, desired: &str, skip_test: bool, execution_type: &ExecutionKind) -> Result<SetResult, DscError> {
         debug!("Invoking set for resource: {}", self.type_name);
+        check_resource_policy(self)?;
+        if *execution_type == ExecutionKind::Actual {
+            check_set_policy(&self.type_name)?;
+        }
+        if let Some((adapter_manifest, directory)) = self.get_adapter_manifest()? {
+            return command_resource::invoke_set(&adapter_manifest, directory, &self.get_adapter_input(desired)?, skip_test, execution_type);
+        }
         match &self.implemented_as {
-            ImplementedAs::Custom(_custom) => {
-                Err(DscError::NotImplemented("set custom resources".to_string()))
+            ImplementedAs::Custom(custom) => {
+                custom_resource::invoke_set(custom, desired, skip_test, execution_type)
             },
             ImplementedAs::Command => {
                 let Some(manifest) = &self.manifest else {
                     return Err(DscError::MissingManifest(self.type_name.clone()));
                 };
-                let resource_manifest = import_manifest(manifest.clone())?;
+                let resource_manifest = self.import_manifest(manifest)?;
                 command_resource::invoke_set(&resource_manifest, &self.directory, desired, skip_test, execution_type)
             },
         }
@@ -221,9 +306,16 @@ impl Invoke for DscResource {
 
     fn test(&self, expected: &str) -> Result<TestResult, DscError> {
         debug!("Invoking test for resource: {}", self.type_name);
+        check_resource_policy(self)?;
+        if let Some((adapter_manifest, directory)) = self.get_adapter_manifest()? {
+            if adapter_manifest.test.is_none() {
+                return self.invoke_synthetic_test(expected);
+            }
+            return command_resource::invoke_test(&adapter_manifest, directory, &self.get_adapter_input(expected)?);
+        }
         match &self.implemented_as {
-            ImplementedAs::Custom(_custom) => {
-                Err(DscError::NotImplemented("test custom resources".to_string()))
+            ImplementedAs::Custom(custom) => {
+                custom_resource::invoke_test(custom, expected)
             },
             ImplementedAs::Command => {
                 let Some(manifest) = &self.manifest else {
@@ -231,30 +323,9 @@ impl Invoke for DscResource {
                 };
 
                 // if test is not directly implemented, then we need to handle it here
-                let resource_manifest = import_manifest(manifest.clone())?;
+                let resource_manifest = self.import_manifest(manifest)?;
                 if resource_manifest.test.is_none() {
-                    let get_result = self.get(expected)?;
-                    let desired_state = serde_json::from_str(expected)?;
-                    let actual_state = match get_result {
-                        GetResult::Group(results) => {
-                            let mut result_array: Vec<Value> = Vec::new();
-                            for result in results {
-                                result_array.push(serde_json::to_value(result)?);
-                            }
-                            Value::from(result_array)
-                        },
-                        GetResult::Resource(response) => {
-                            response.actual_state
-                        }
-                    };
-                    let diff_properties = get_diff( &desired_state, &actual_state);
-                    let test_result = TestResult::Resource(ResourceTestResponse {
-                        desired_state,
-                        actual_state,
-                        in_desired_state: diff_properties.is_empty(),
-                        diff_properties,
-                    });
-                    Ok(test_result)
+                    self.invoke_synthetic_test(expected)
                 }
                 else {
                     command_resource::invoke_test(&resource_manifest, &self.directory, expected)
@@ -265,15 +336,20 @@ impl Invoke for DscResource {
 
     fn delete(&self, filter: &str) -> Result<(), DscError> {
         debug!("Invoking delete for resource: {}", self.type_name);
+        check_resource_policy(self)?;
+        check_set_policy(&self.type_name)?;
+        if let Some((adapter_manifest, directory)) = self.get_adapter_manifest()? {
+            return command_resource::invoke_delete(&adapter_manifest, directory, &self.get_adapter_input(filter)?);
+        }
         match &self.implemented_as {
-            ImplementedAs::Custom(_custom) => {
-                Err(DscError::NotImplemented("set custom resources".to_string()))
+            ImplementedAs::Custom(custom) => {
+                custom_resource::invoke_delete(custom, filter)
             },
             ImplementedAs::Command => {
                 let Some(manifest) = &self.manifest else {
                     return Err(DscError::MissingManifest(self.type_name.clone()));
                 };
-                let resource_manifest = import_manifest(manifest.clone())?;
+                let resource_manifest = self.import_manifest(manifest)?;
                 command_resource::invoke_delete(&resource_manifest, &self.directory, filter)
             },
         }
@@ -281,6 +357,10 @@ impl Invoke for DscResource {
 
     fn validate(&self, config: &str) -> Result<ValidateResult, DscError> {
         debug!("Invoking validate for resource: {}", self.type_name);
+        check_resource_policy(self)?;
+        if let Some((adapter_manifest, directory)) = self.get_adapter_manifest()? {
+            return command_resource::invoke_validate(&adapter_manifest, directory, &self.get_adapter_input(config)?);
+        }
         match &self.implemented_as {
             ImplementedAs::Custom(_custom) => {
                 Err(DscError::NotImplemented("validate custom resources".to_string()))
@@ -289,7 +369,7 @@ impl Invoke for DscResource {
                 let Some(manifest) = &self.manifest else {
                     return Err(DscError::MissingManifest(self.type_name.clone()));
                 };
-                let resource_manifest = import_manifest(manifest.clone())?;
+                let resource_manifest = self.import_manifest(manifest)?;
                 command_resource::invoke_validate(&resource_manifest, &self.directory, config)
             },
         }
@@ -298,14 +378,14 @@ impl Invoke for DscResource {
     fn schema(&self) -> Result<String, DscError> {
         debug!("Invoking schema for resource: {}", self.type_name);
         match &self.implemented_as {
-            ImplementedAs::Custom(_custom) => {
-                Err(DscError::NotImplemented("schema custom resources".to_string()))
+            ImplementedAs::Custom(custom) => {
+                custom_resource::get_schema(custom)
             },
             ImplementedAs::Command => {
                 let Some(manifest) = &self.manifest else {
                     return Err(DscError::MissingManifest(self.type_name.clone()));
                 };
-                let resource_manifest = import_manifest(manifest.clone())?;
+                let resource_manifest = self.import_manifest(manifest)?;
                 command_resource::get_schema(&resource_manifest, &self.directory)
             },
         }
@@ -313,19 +393,30 @@ impl Invoke for DscResource {
 
     fn export(&self, input: &str) -> Result<ExportResult, DscError> {
         debug!("Invoking export for resource: {}", self.type_name);
+        check_resource_policy(self)?;
+        if let Some((adapter_manifest, directory)) = self.get_adapter_manifest()? {
+            return command_resource::invoke_export(&adapter_manifest, directory, Some(&self.get_adapter_input(input)?));
+        }
+        if let ImplementedAs::Custom(custom) = &self.implemented_as {
+            return custom_resource::invoke_export(custom, Some(input));
+        }
         let Some(manifest) = &self.manifest else {
             return Err(DscError::MissingManifest(self.type_name.clone()));
         };
-        let resource_manifest = import_manifest(manifest.clone())?;
+        let resource_manifest = self.import_manifest(manifest)?;
         command_resource::invoke_export(&resource_manifest, &self.directory, Some(input))
     }
 
     fn resolve(&self, input: &str) -> Result<ResolveResult, DscError> {
         debug!("Invoking resolve for resource: {}", self.type_name);
+        check_resource_policy(self)?;
+        if let ImplementedAs::Custom(_custom) = &self.implemented_as {
+            return Err(DscError::NotImplemented("resolve custom resources".to_string()));
+        }
         let Some(manifest) = &self.manifest else {
             return Err(DscError::MissingManifest(self.type_name.clone()));
         };
-        let resource_manifest = import_manifest(manifest.clone())?;
+        let resource_manifest = self.import_manifest(manifest)?;
         command_resource::invoke_resolve(&resource_manifest, &self.directory, input)
     }
 }
@@ -337,54 +428,14 @@ pub fn get_well_known_properties() -> HashMap<String, Value> {
     ])
 }
 
+/// Get the names of the top-level properties that differ between the expected and actual state.
+///
+/// # Arguments
+///
+/// * `expected` - The expected state.
+/// * `actual` - The actual state.
 #[must_use]
 pub fn get_diff(expected: &Value, actual: &Value) -> Vec<String> {
-    let mut diff_properties: Vec<String> = Vec::new();
-    if expected.is_null() {
-        return diff_properties;
-    }
-
-    let mut expected = expected.clone();
-    let mut actual = actual.clone();
-
-    if let Some(map) = expected.as_object_mut() {
-        // handle well-known optional properties with default values by adding them
-        for (key, value) in get_well_known_properties() {
-            if !map.contains_key(&key) {
-                map.insert(key.clone(), value.clone());
-            }
-
-            if actual.is_object() && actual[&key].is_null() {
-                actual[key.clone()] = value.clone();
-            }
-        }
-
-        for (key, value) in &*map {
-            if value.is_object() {
-                let sub_diff = get_diff(value, &actual[key]);
-                if !sub_diff.is_empty() {
-                    diff_properties.push(key.to_string());
-                }
-            }
-            else {
-                match actual.as_object() {
-                    Some(actual_object) => {
-                        if actual_object.contains_key(key) {
-                            if value != &actual[key] {
-                                diff_properties.push(key.to_string());
-                            }
-                        }
-                        else {
-                            diff_properties.push(key.to_string());
-                        }
-                    },
-                    None => {
-                        diff_properties.push(key.to_string());
-                    },
-                }
-            }
-        }
-    }
-
-    diff_properties
+    let differences = get_differences(expected, actual, None);
+    get_property_names(differences.iter().map(|difference| difference.path.as_str()))
 }
@@ -0,0 +1,105 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use security_context_lib::{get_security_context, SecurityContext};
+use tracing::debug;
+
+use crate::configure::config_doc::SecurityContextKind;
+use crate::dscerror::DscError;
+use crate::settings::{get_settings, SecurityContextSettings};
+use super::{command_resource::resolve_executable, manifest_lint::find_in_path, resource_manifest::ResourceManifest};
+
+/// The security context a configuration instance is invoked in.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InstanceSecurityContext {
+    /// The security context required by the instance, `None` to use the one of the manifest.
+    pub required: Option<SecurityContextKind>,
+    /// The wrappers changing the security context, loaded once by the configurator.
+    pub settings: SecurityContextSettings,
+}
+
+impl InstanceSecurityContext {
+    /// Apply the security context to the manifest whose commands are run for the instance.
+    pub fn apply(&self, manifest: &mut ResourceManifest) {
+        if let Some(required) = &self.required {
+            manifest.security_context = Some(required.clone());
+        }
+        manifest.security_context_settings = Some(self.settings.clone());
+    }
+}
+
+/// Get the command used to run commands of a resource in the required security context.
+///
+/// # Arguments
+///
+/// * `type_name` - The type of the resource, used for errors.
+/// * `required` - The security context the resource requires.
+/// * `settings` - The wrappers changing the security context, `None` to read them from the settings.
+///
+/// # Returns
+///
+/// `None` if the process already runs in the required security context, otherwise the wrapper command
+/// configured in the `securityContext` settings.
+///
+/// # Errors
+///
+/// Error returned if the process doesn't run in the required security context and no wrapper is configured.
+pub fn get_security_context_wrapper(type_name: &str, required: &SecurityContextKind, settings: Option<&SecurityContextSettings>) -> Result<Option<Vec<String>>, DscError> {
+    let current = get_security_context();
+    let (wrapper_name, description) = match (required, current) {
+        (SecurityContextKind::Current, _) | (SecurityContextKind::Elevated, SecurityContext::Admin) | (SecurityContextKind::Restricted, SecurityContext::User) => return Ok(None),
+        (SecurityContextKind::Elevated, SecurityContext::User) => ("elevateWith", "elevated"),
+        (SecurityContextKind::Restricted, SecurityContext::Admin) => ("restrictWith", "restricted"),
+    };
+
+    let settings = match settings {
+        Some(settings) => settings.clone(),
+        None => get_settings()?.security_context.unwrap_or_default(),
+    };
+    let wrapper = if *required == SecurityContextKind::Elevated { settings.elevate_with } else { settings.restrict_with };
+    match wrapper.filter(|wrapper| !wrapper.is_empty()) {
+        Some(_) if cfg!(windows) => Err(DscError::SecurityContext(format!("Resource '{type_name}' requires the {description} security context, which can't be changed on Windows"))),
+        Some(wrapper) => {
+            debug!("Resource '{type_name}' requires the {description} security context, using {wrapper:?}");
+            Ok(Some(wrapper))
+        },
+        None => Err(DscError::SecurityContext(format!("Resource '{type_name}' requires the {description} security context and `securityContext.{wrapper_name}` isn't configured"))),
+    }
+}
+
+/// Get the executable and arguments running a command through a wrapper like `sudo -n`.
+///
+/// The executable is resolved from the directory of the resource as the wrapper may search a different `PATH`,
+/// the wrapper is only found in `PATH` so a resource can't provide it.
+#[must_use]
+pub fn wrap_command(wrapper: &[String], executable: &str, args: Option<Vec<String>>, directory: &str) -> (String, Option<Vec<String>>) {
+    let wrapper_executable = find_in_path(&wrapper[0]).map_or_else(|| wrapper[0].clone(), |path| path.to_string_lossy().to_string());
+    let mut wrapped_args = wrapper[1..].to_vec();
+    wrapped_args.push(resolve_executable(executable, directory));
+    wrapped_args.extend(args.unwrap_or_default());
+    (wrapper_executable, Some(wrapped_args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instance_context_overrides_manifest() {
+        let mut manifest = ResourceManifest { security_context: Some(SecurityContextKind::Elevated), ..Default::default() };
+        InstanceSecurityContext::default().apply(&mut manifest);
+        assert_eq!(manifest.security_context, Some(SecurityContextKind::Elevated));
+        let settings = SecurityContextSettings { elevate_with: None, restrict_with: Some(vec!["doas".to_string()]) };
+        InstanceSecurityContext { required: Some(SecurityContextKind::Restricted), settings: settings.clone() }.apply(&mut manifest);
+        assert_eq!(manifest.security_context, Some(SecurityContextKind::Restricted));
+        assert_eq!(manifest.security_context_settings, Some(settings));
+    }
+
+    #[test]
+    fn wrapped_command_runs_executable_after_wrapper() {
+        let wrapper = vec!["doesNotExistWrapper".to_string(), "-n".to_string()];
+        let (executable, args) = wrap_command(&wrapper, "/bin/doesNotExist", Some(vec!["get".to_string()]), "/");
+        assert_eq!(executable, "doesNotExistWrapper");
+        assert_eq!(args, Some(vec!["-n".to_string(), "/bin/doesNotExist".to_string(), "get".to_string()]));
+    }
+}
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use crate::configure::config_result::{ResourceGetResult, ResourceSetResult, ResourceTestResult};
+use super::property_diff::{get_property_names, PropertyChange, PropertyDifference};
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 #[serde(untagged)]
@@ -63,6 +64,7 @@ impl From<TestResult> for SetResult {
                     before_state: resource.actual_state,
                     after_state: resource.desired_state,
                     changed_properties: if resource.diff_properties.is_empty() { None } else { Some(resource.diff_properties) },
+                    changes: resource.differences.map(|differences| differences.into_iter().map(PropertyChange::from).collect()),
                 })
             }
         }
@@ -81,6 +83,55 @@ pub struct ResourceSetResponse {
     /// The properties that were changed by the Set method from the before state.
     #[serde(rename = "changedProperties")]
     pub changed_properties: Option<Vec<String>>,
+    /// The individual values that were changed by the Set method from the before state.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes: Option<Vec<PropertyChange>>,
+}
+
+impl ResourceSetResponse {
+    /// Create a set response where the changed properties are derived from the individual changes.
+    #[must_use]
+    pub fn from_changes(before_state: Value, after_state: Value, changes: Vec<PropertyChange>) -> Self {
+        Self {
+            before_state,
+            after_state,
+            changed_properties: Some(get_property_names(changes.iter().map(|change| change.path.as_str()))),
+            changes: Some(changes),
+        }
+    }
+
+    /// Create a set response predicting the result of applying the desired state of a test response.
+    #[must_use]
+    pub fn from_test_response(response: ResourceTestResponse) -> Self {
+        if response.in_desired_state {
+            return Self {
+                before_state: response.actual_state.clone(),
+                after_state: response.actual_state,
+                changed_properties: None,
+                changes: None,
+            };
+        }
+
+        // the predicted state is the current state with the desired properties applied
+        let mut after_state = response.actual_state.clone();
+        match (after_state.as_object_mut(), response.desired_state.as_object()) {
+            (Some(after_map), Some(desired_map)) => {
+                for (key, value) in desired_map {
+                    after_map.insert(key.clone(), value.clone());
+                }
+            },
+            _ => {
+                after_state = response.desired_state.clone();
+            },
+        }
+
+        Self {
+            before_state: response.actual_state,
+            after_state,
+            changed_properties: Some(response.diff_properties),
+            changes: response.differences.map(|differences| differences.into_iter().map(PropertyChange::from).collect()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
@@ -122,6 +173,24 @@ pub struct ResourceTestResponse {
     /// The properties that were different from the expected state.
     #[serde(rename = "differingProperties")]
     pub diff_properties: Vec<String>,
+    /// The individual values that were different from the expected state.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub differences: Option<Vec<PropertyDifference>>,
+}
+
+impl ResourceTestResponse {
+    /// Create a test response where the differing properties are derived from the individual differences.
+    #[must_use]
+    pub fn from_differences(desired_state: Value, actual_state: Value, differences: Vec<PropertyDifference>) -> Self {
+        let diff_properties = get_property_names(differences.iter().map(|difference| difference.path.as_str()));
+        Self {
+            desired_state,
+            actual_state,
+            in_desired_state: differences.is_empty(),
+            diff_properties,
+            differences: Some(differences),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
@@ -0,0 +1,503 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use jsonschema::JSONSchema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{env, fs, path::{Path, PathBuf}};
+use tracing::debug;
+
+use super::{command_resource::get_schema, resource_manifest::{import_manifest, validate_semver, Kind, ManifestSchemaUri, ResourceManifest, SchemaKind, MANIFEST_SCHEMA_VERSION}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum LintLevel {
+    /// The manifest can't be used by DSC.
+    Error,
+    /// The manifest can be used, but likely doesn't behave as intended.
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum LintRule {
+    /// The file can't be read or parsed as a manifest.
+    Manifest,
+    /// The `$schema` of the manifest is missing or not a supported version.
+    SchemaVersion,
+    /// The manifest doesn't conform to the schema of its version.
+    ManifestSchema,
+    /// The version of the resource isn't a semantic version.
+    Version,
+    /// An executable of the manifest can't be found.
+    Executable,
+    /// The schema of the resource isn't available or isn't a valid JSON schema.
+    ResourceSchema,
+    /// The declared methods and kind of the resource aren't consistent.
+    Capabilities,
+    /// A key of `exitCodes` isn't a 32-bit integer.
+    ExitCodes,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LintDiagnostic {
+    pub level: LintLevel,
+    pub rule: LintRule,
+    pub message: String,
+    /// JSON pointer to the location of the issue in the manifest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestLintResult {
+    pub path: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub resource_type: Option<String>,
+    /// Indicates the manifest has no errors.
+    pub valid: bool,
+    pub diagnostics: Vec<LintDiagnostic>,
+}
+
+impl ManifestLintResult {
+    fn add(&mut self, level: LintLevel, rule: LintRule, message: String, location: Option<&str>) {
+        if level == LintLevel::Error {
+            self.valid = false;
+        }
+        self.diagnostics.push(LintDiagnostic {
+            level,
+            rule,
+            message,
+            location: location.map(ToString::to_string),
+        });
+    }
+}
+
+/// Check if a file name is the name of a resource manifest.
+#[must_use]
+pub fn is_manifest_file_name(file_name: &str) -> bool {
+    let file_name = file_name.to_lowercase();
+    file_name.ends_with(".dsc.resource.json") || file_name.ends_with(".dsc.resource.yaml") || file_name.ends_with(".dsc.resource.yml")
+}
+
+/// Lint a resource manifest file.
+///
+/// # Arguments
+///
+/// * `path` - The path to the manifest file.
+///
+/// # Returns
+///
+/// The issues found in the manifest.  Failures to read or parse the file are reported as issues.
+#[must_use]
+pub fn lint_manifest(path: &Path) -> ManifestLintResult {
+    let (mut result, manifest) = lint_manifest_content(path);
+    if let Some(manifest) = manifest {
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        lint_schema_command(&manifest, &directory, &mut result);
+    }
+    result
+}
+
+/// Lint a resource manifest file without running any of its commands, like for a package that isn't installed yet.
+/// Schemas returned by a command aren't checked.
+///
+/// # Arguments
+///
+/// * `path` - The path to the manifest file.
+///
+/// # Returns
+///
+/// The issues found in the manifest.  Failures to read or parse the file are reported as issues.
+#[must_use]
+pub fn lint_manifest_static(path: &Path) -> ManifestLintResult {
+    lint_manifest_content(path).0
+}
+
+// the checks not running commands, the manifest is returned if its schema command can be run
+fn lint_manifest_content(path: &Path) -> (ManifestLintResult, Option<ResourceManifest>) {
+    let mut result = ManifestLintResult {
+        path: path.to_string_lossy().to_string(),
+        resource_type: None,
+        valid: true,
+        diagnostics: Vec::new(),
+    };
+
+    let mut manifest = match read_manifest(path) {
+        Ok(manifest) => manifest,
+        Err(message) => {
+            result.add(LintLevel::Error, LintRule::Manifest, message, None);
+            return (result, None);
+        }
+    };
+    let Value::Object(properties) = &mut manifest else {
+        result.add(LintLevel::Error, LintRule::Manifest, "Manifest must be an object".to_string(), None);
+        return (result, None);
+    };
+    result.resource_type = properties.get("type").and_then(Value::as_str).map(ToString::to_string);
+
+    if !properties.contains_key("$schema") {
+        result.add(LintLevel::Warning, LintRule::SchemaVersion, format!("Manifest doesn't declare `$schema`, '{MANIFEST_SCHEMA_VERSION}' is used"), Some("/$schema"));
+        properties.insert("$schema".to_string(), Value::String(MANIFEST_SCHEMA_VERSION.to_string()));
+    }
+    let schema_version = serde_json::from_value::<ManifestSchemaUri>(properties["$schema"].clone()).ok();
+    if schema_version.is_none() {
+        result.add(LintLevel::Error, LintRule::SchemaVersion, format!("Unsupported manifest schema {}, use '{MANIFEST_SCHEMA_VERSION}'", properties["$schema"]), Some("/$schema"));
+    }
+
+    if let Some(Value::Object(exit_codes)) = properties.get("exitCodes") {
+        for code in exit_codes.keys() {
+            if code.parse::<i32>().is_err() {
+                result.add(LintLevel::Error, LintRule::ExitCodes, format!("Exit code '{code}' is not a 32-bit integer"), Some(&format!("/exitCodes/{}", escape_pointer(code))));
+            }
+        }
+    }
+
+    let Some(schema_version) = schema_version else {
+        return (result, None);
+    };
+    if !result.valid {
+        return (result, None);
+    }
+    lint_manifest_schema(&manifest, schema_version, &mut result);
+
+    // the published schema is stricter than DSC, so the other rules still apply to manifests DSC can use
+    let manifest = match import_manifest(manifest) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            if result.valid {
+                result.add(LintLevel::Error, LintRule::Manifest, err.to_string(), None);
+            }
+            return (result, None);
+        }
+    };
+
+    if let Err(err) = validate_semver(&manifest.version) {
+        result.add(LintLevel::Error, LintRule::Version, format!("Version '{}' is not a semantic version: {err}", manifest.version), Some("/version"));
+    }
+
+    let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    lint_capabilities(&manifest, &mut result);
+    let schema_executable_found = lint_executables(&manifest, &directory, &mut result);
+    lint_resource_schema(&manifest, &mut result);
+    let run_schema_command = schema_executable_found && matches!(manifest.schema, Some(SchemaKind::Command(_)));
+    (result, run_schema_command.then_some(manifest))
+}
+
+fn read_manifest(path: &Path) -> Result<Value, String> {
+    let content = fs::read_to_string(path).map_err(|err| format!("Failed to read manifest: {err}"))?;
+    let is_json = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+    if is_json {
+        serde_json::from_str(&content).map_err(|err| format!("Failed to parse manifest as JSON: {err}"))
+    } else {
+        serde_yaml::from_str(&content).map_err(|err| format!("Failed to parse manifest as YAML: {err}"))
+    }
+}
+
+// the published schemas are bundled with DSC so manifests are validated without fetching them
+fn get_published_manifest_schema(schema_version: ManifestSchemaUri) -> &'static str {
+    match schema_version {
+        ManifestSchemaUri::Version2024_04 | ManifestSchemaUri::Bundled2024_04 | ManifestSchemaUri::VSCode2024_04 => {
+            include_str!("../../../schemas/2024/04/bundled/resource/manifest.json")
+        },
+        ManifestSchemaUri::Version2023_10 | ManifestSchemaUri::Bundled2023_10 | ManifestSchemaUri::VSCode2023_10 => {
+            include_str!("../../../schemas/2023/10/bundled/resource/manifest.json")
+        },
+        ManifestSchemaUri::Version2023_08 | ManifestSchemaUri::Bundled2023_08 | ManifestSchemaUri::VSCode2023_08 => {
+            include_str!("../../../schemas/2023/08/bundled/resource/manifest.json")
+        },
+    }
+}
+
+fn lint_manifest_schema(manifest: &Value, schema_version: ManifestSchemaUri, result: &mut ManifestLintResult) {
+    let schema: Value = match serde_json::from_str(get_published_manifest_schema(schema_version)) {
+        Ok(schema) => schema,
+        Err(err) => {
+            result.add(LintLevel::Error, LintRule::ManifestSchema, format!("Failed to get manifest schema: {err}"), None);
+            return;
+        }
+    };
+    // the bundled schemas reference their definitions by `$id`
+    let mut options = JSONSchema::options();
+    if let Some(Value::Object(definitions)) = schema.get("$defs") {
+        for definition in definitions.values() {
+            if let Some(id) = definition.get("$id").and_then(Value::as_str) {
+                options.with_document(id.to_string(), definition.clone());
+            }
+        }
+    }
+    let compiled_schema = match options.compile(&schema) {
+        Ok(compiled_schema) => compiled_schema,
+        Err(err) => {
+            result.add(LintLevel::Error, LintRule::ManifestSchema, format!("Failed to compile manifest schema: {err}"), None);
+            return;
+        }
+    };
+    let errors = match compiled_schema.validate(manifest) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.map(|error| (error.instance_path.to_string(), error.to_string())).collect::<Vec<(String, String)>>(),
+    };
+    for (location, message) in errors {
+        // the schema version and the version of the resource are reported by their own rules
+        if location == "/$schema" || location == "/version" {
+            continue;
+        }
+        let location = if location.is_empty() { "/".to_string() } else { location };
+        result.add(LintLevel::Error, LintRule::ManifestSchema, message, Some(&location));
+    }
+}
+
+fn lint_capabilities(manifest: &ResourceManifest, result: &mut ManifestLintResult) {
+    let kind = manifest.kind.clone().unwrap_or(if manifest.adapter.is_some() { Kind::Adapter } else { Kind::Resource });
+    if manifest.get.is_none() && kind != Kind::Import {
+        result.add(LintLevel::Error, LintRule::Capabilities, "Resource must implement `get`".to_string(), Some("/get"));
+    }
+    if manifest.set.is_none() {
+        if manifest.what_if.is_some() {
+            result.add(LintLevel::Error, LintRule::Capabilities, "`whatIf` requires `set` to be implemented".to_string(), Some("/whatIf"));
+        }
+        if manifest.what_if.as_ref().is_some_and(|what_if| what_if.handles_exist == Some(true)) {
+            result.add(LintLevel::Error, LintRule::Capabilities, "`handlesExist` requires `set` to be implemented".to_string(), Some("/whatIf/handlesExist"));
+        }
+    }
+    if let (Some(set), Some(what_if)) = (&manifest.set, &manifest.what_if) {
+        if set.handles_exist != what_if.handles_exist {
+            result.add(LintLevel::Warning, LintRule::Capabilities, "`handlesExist` of `whatIf` differs from `set`".to_string(), Some("/whatIf/handlesExist"));
+        }
+    }
+    if manifest.set.as_ref().is_some_and(|set| set.handles_exist == Some(true)) && manifest.delete.is_some() {
+        result.add(LintLevel::Warning, LintRule::Capabilities, "`delete` is not used when `set` handles `_exist`".to_string(), Some("/delete"));
+    }
+    if kind == Kind::Adapter && manifest.adapter.is_none() {
+        result.add(LintLevel::Error, LintRule::Capabilities, "Adapter kind requires `adapter` to be defined".to_string(), Some("/adapter"));
+    }
+    if kind != Kind::Adapter && manifest.adapter.is_some() {
+        result.add(LintLevel::Error, LintRule::Capabilities, "`adapter` requires the kind to be `Adapter`".to_string(), Some("/kind"));
+    }
+    if kind == Kind::Import && manifest.resolve.is_none() {
+        result.add(LintLevel::Error, LintRule::Capabilities, "Import kind requires `resolve` to be implemented".to_string(), Some("/resolve"));
+    }
+    if kind != Kind::Import && manifest.resolve.is_some() {
+        result.add(LintLevel::Warning, LintRule::Capabilities, "`resolve` is only used by the `Import` kind".to_string(), Some("/resolve"));
+    }
+}
+
+/// Get the executables of the commands of a manifest with the JSON pointers to them.
+pub(crate) fn get_manifest_executables(manifest: &ResourceManifest) -> Vec<(&'static str, &String)> {
+    let mut executables = Vec::new();
+    if let Some(get) = &manifest.get { executables.push(("/get/executable", &get.executable)); }
+    if let Some(set) = &manifest.set { executables.push(("/set/executable", &set.executable)); }
+    if let Some(what_if) = &manifest.what_if { executables.push(("/whatIf/executable", &what_if.executable)); }
+    if let Some(test) = &manifest.test { executables.push(("/test/executable", &test.executable)); }
+    if let Some(delete) = &manifest.delete { executables.push(("/delete/executable", &delete.executable)); }
+    if let Some(export) = &manifest.export { executables.push(("/export/executable", &export.executable)); }
+    if let Some(validate) = &manifest.validate { executables.push(("/validate/executable", &validate.executable)); }
+    if let Some(resolve) = &manifest.resolve { executables.push(("/resolve/executable", &resolve.executable)); }
+    if let Some(adapter) = &manifest.adapter { executables.push(("/adapter/list/executable", &adapter.list.executable)); }
+    if let Some(host) = &manifest.host { executables.push(("/host/executable", &host.executable)); }
+    if let Some(SchemaKind::Command(command)) = &manifest.schema { executables.push(("/schema/command/executable", &command.executable)); }
+    executables
+}
+
+// returns whether the executable of the schema command was found
+fn lint_executables(manifest: &ResourceManifest, directory: &Path, result: &mut ManifestLintResult) -> bool {
+    let mut schema_executable_found = true;
+    for (location, executable) in get_manifest_executables(manifest) {
+        if let Some(found) = find_executable(executable, directory) {
+            debug!("Executable '{executable}' at '{location}' resolved to {found:?}");
+        } else {
+            result.add(LintLevel::Error, LintRule::Executable, format!("Executable '{executable}' not found"), Some(location));
+            if location == "/schema/command/executable" {
+                schema_executable_found = false;
+            }
+        }
+    }
+    schema_executable_found
+}
+
+fn lint_resource_schema(manifest: &ResourceManifest, result: &mut ManifestLintResult) {
+    match &manifest.schema {
+        None => {
+            if matches!(manifest.kind, None | Some(Kind::Resource)) && manifest.adapter.is_none() {
+                result.add(LintLevel::Warning, LintRule::ResourceSchema, "Resource doesn't define a schema, so input can't be validated".to_string(), Some("/schema"));
+            }
+        },
+        Some(SchemaKind::Embedded(schema)) => lint_schema(schema, result),
+        Some(SchemaKind::Command(_)) => {},
+    }
+}
+
+fn lint_schema_command(manifest: &ResourceManifest, directory: &Path, result: &mut ManifestLintResult) {
+    let schema = match get_schema(manifest, &directory.to_string_lossy()) {
+        Ok(schema) => schema,
+        Err(err) => {
+            result.add(LintLevel::Error, LintRule::ResourceSchema, format!("Failed to get schema: {err}"), Some("/schema"));
+            return;
+        }
+    };
+    match serde_json::from_str(&schema) {
+        Ok(schema) => lint_schema(&schema, result),
+        Err(err) => result.add(LintLevel::Error, LintRule::ResourceSchema, format!("Schema is not valid JSON: {err}"), Some("/schema")),
+    }
+}
+
+fn lint_schema(schema: &Value, result: &mut ManifestLintResult) {
+    if let Err(err) = JSONSchema::compile(schema) {
+        result.add(LintLevel::Error, LintRule::ResourceSchema, format!("Schema is not a valid JSON schema: {err}"), Some("/schema"));
+    }
+}
+
+// executables are found in the directory of the resource, the one of `dsc`, then `PATH`, commands of resources are started
+// with the path found here so it is the one integrity verification and the execution policy checked
+pub(crate) fn find_executable(executable: &str, directory: &Path) -> Option<PathBuf> {
+    let path = Path::new(executable);
+    if path.is_absolute() || path.components().count() > 1 {
+        let path = directory.join(path);
+        return path.is_file().then_some(path);
+    }
+
+    let mut search_paths = vec![directory.to_path_buf()];
+    if let Some(exe_home) = env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        search_paths.push(exe_home);
+    }
+    search_paths.extend(get_path_directories());
+    find_in_directories(executable, &search_paths)
+}
+
+// commands of the settings, like the wrappers running resources in another security context, are only found in `PATH`
+pub(crate) fn find_in_path(executable: &str) -> Option<PathBuf> {
+    let path = Path::new(executable);
+    if path.is_absolute() {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    find_in_directories(executable, &get_path_directories())
+}
+
+fn get_path_directories() -> Vec<PathBuf> {
+    env::var_os("PATH").map(|path_env| env::split_paths(&path_env).collect()).unwrap_or_default()
+}
+
+fn find_in_directories(executable: &str, search_paths: &[PathBuf]) -> Option<PathBuf> {
+    let mut extensions = vec![String::new()];
+    if cfg!(windows) {
+        let path_ext = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+        extensions.extend(path_ext.split(';').filter(|extension| !extension.is_empty()).map(ToString::to_string));
+    }
+    search_paths.iter()
+        .flat_map(|search_path| extensions.iter().map(move |extension| search_path.join(format!("{executable}{extension}"))))
+        .find(|candidate| candidate.is_file())
+}
+
+fn escape_pointer(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint_content(name: &str, content: &str) -> ManifestLintResult {
+        let directory = env::temp_dir().join(format!("dsc_lint_{}_{name}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(name);
+        fs::write(&path, content).unwrap();
+        let result = lint_manifest(&path);
+        fs::remove_dir_all(&directory).unwrap();
+        result
+    }
+
+    // the test binary is an executable that always exists
+    fn test_executable() -> String {
+        serde_json::to_string(&env::current_exe().unwrap()).unwrap()
+    }
+
+    fn rules(result: &ManifestLintResult) -> Vec<LintRule> {
+        result.diagnostics.iter().map(|diagnostic| diagnostic.rule).collect()
+    }
+
+    #[test]
+    fn unsupported_schema_version_is_error() {
+        let result = lint_content("bad.dsc.resource.json", r#"{
+            "$schema": "https://example.com/manifest.json",
+            "type": "Test/Lint",
+            "version": "0.1.0",
+            "get": { "executable": "dsc_lint_missing_executable" }
+        }"#);
+        assert!(!result.valid);
+        assert_eq!(rules(&result), vec![LintRule::SchemaVersion]);
+    }
+
+    #[test]
+    fn invalid_exit_code_and_capabilities_are_reported() {
+        let result = lint_content("caps.dsc.resource.yaml", &format!(r#"
+$schema: {MANIFEST_SCHEMA_VERSION}
+type: Test/Lint
+version: 0.1.0
+exitCodes:
+  abc: not a number
+"#));
+        assert!(!result.valid);
+        assert_eq!(rules(&result), vec![LintRule::ExitCodes]);
+
+        let result = lint_content("caps.dsc.resource.json", &format!(r#"{{
+            "$schema": "{MANIFEST_SCHEMA_VERSION}",
+            "type": "Test/Lint",
+            "version": "1.0",
+            "whatIf": {{ "executable": "dsc_lint_missing_executable", "handlesExist": true }},
+            "schema": {{ "embedded": {{ "$schema": "http://json-schema.org/draft-07/schema#", "type": "object", "properties": {{ "name": {{ "type": "string" }} }} }} }}
+        }}"#));
+        assert!(!result.valid);
+        // the published schema requires `get` as well
+        assert_eq!(rules(&result), vec![LintRule::ManifestSchema, LintRule::Version, LintRule::Capabilities, LintRule::Capabilities, LintRule::Capabilities, LintRule::Executable]);
+    }
+
+    #[test]
+    fn manifest_is_validated_against_published_schema() {
+        let result = lint_content("published.dsc.resource.json", &format!(r#"{{
+            "$schema": "{MANIFEST_SCHEMA_VERSION}",
+            "type": "Test/Lint",
+            "version": "0.1.0",
+            "get": {{ "executable": {}, "input": "unknown" }},
+            "schema": {{ "embedded": {{ "$schema": "http://json-schema.org/draft-07/schema#", "type": "object", "properties": {{ "name": {{ "type": "string" }} }} }} }}
+        }}"#, test_executable()));
+        assert!(!result.valid);
+        assert_eq!(rules(&result), vec![LintRule::ManifestSchema]);
+        assert_eq!(result.diagnostics[0].location.as_deref(), Some("/get/input"));
+    }
+
+    #[test]
+    fn invalid_embedded_schema_is_error() {
+        let result = lint_content("schema.dsc.resource.json", &format!(r#"{{
+            "$schema": "{MANIFEST_SCHEMA_VERSION}",
+            "type": "Test/Lint",
+            "version": "0.1.0",
+            "get": {{ "executable": {} }},
+            "schema": {{ "embedded": {{ "$schema": "http://json-schema.org/draft-07/schema#", "type": "object", "properties": {{ "name": {{ "type": "unknown" }} }} }} }}
+        }}"#, test_executable()));
+        assert!(!result.valid);
+        assert_eq!(rules(&result), vec![LintRule::ResourceSchema]);
+    }
+
+    #[test]
+    fn static_lint_doesnt_run_schema_command() {
+        let directory = env::temp_dir().join(format!("dsc_lint_{}_static", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("static.dsc.resource.json");
+        // the test binary doesn't return a schema, so the schema is only invalid if the command is run
+        fs::write(&path, format!(r#"{{
+            "$schema": "{MANIFEST_SCHEMA_VERSION}",
+            "type": "Test/Lint",
+            "version": "0.1.0",
+            "get": {{ "executable": {0} }},
+            "schema": {{ "command": {{ "executable": {0}, "args": ["--exact", "dsc_lint_no_such_test"] }} }}
+        }}"#, test_executable())).unwrap();
+        let static_result = lint_manifest_static(&path);
+        let result = lint_manifest(&path);
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(static_result.valid);
+        assert_eq!(rules(&result), vec![LintRule::ResourceSchema]);
+    }
+}
@@ -1,9 +1,16 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
+pub mod adapter_batch;
 pub mod command_resource;
+pub mod custom_resource;
 pub mod dscresource;
+pub mod elevation;
 pub mod invoke_result;
+pub mod manifest_lint;
+pub mod property_diff;
+pub mod resource_host;
 pub mod resource_manifest;
+pub mod retry;
 
 use super::dscerror;
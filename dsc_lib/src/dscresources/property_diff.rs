@@ -0,0 +1,553 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::dscresource::get_well_known_properties;
+
+/// Schema keyword used by resources to declare how an array property is compared.
+pub const ARRAY_COMPARISON_KEYWORD: &str = "arrayComparison";
+/// Schema keyword used by resources to declare the property identifying items of a keyed array.
+pub const ARRAY_KEY_KEYWORD: &str = "arrayKey";
+/// Schema keyword used by resources to declare that a string property is compared ignoring case.
+pub const CASE_INSENSITIVE_KEYWORD: &str = "caseInsensitive";
+/// Schema keyword used by resources to declare the allowed difference for a numeric property.
+pub const TOLERANCE_KEYWORD: &str = "tolerance";
+
+/// A single difference between the expected and actual state of a resource.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PropertyDifference {
+    /// The JSON pointer to the differing value.
+    pub path: String,
+    /// The expected value, absent if the value only exists in the actual state.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<Value>,
+    /// The actual value, absent if the value is missing from the actual state.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<Value>,
+}
+
+/// A single change made to the state of a resource.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PropertyChange {
+    /// The JSON pointer to the changed value.
+    pub path: String,
+    /// The value before the change, absent if the value was added.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+    /// The value after the change, absent if the value was removed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+}
+
+impl From<PropertyDifference> for PropertyChange {
+    fn from(difference: PropertyDifference) -> Self {
+        Self {
+            path: difference.path,
+            before: difference.actual,
+            after: difference.expected,
+        }
+    }
+}
+
+/// How the items of an array property are compared.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArrayComparison {
+    /// Items are compared by position, this is the default.
+    Ordered,
+    /// Items are compared as a set regardless of position.
+    Unordered,
+    /// Items are objects matched by the value of the named property.
+    Keyed(String),
+}
+
+impl ArrayComparison {
+    fn from_schema(schema: Option<&Value>) -> Self {
+        let Some(schema) = schema else {
+            return ArrayComparison::Ordered;
+        };
+        match schema.get(ARRAY_COMPARISON_KEYWORD).and_then(Value::as_str) {
+            Some("unordered") => ArrayComparison::Unordered,
+            Some("keyed") => {
+                match schema.get(ARRAY_KEY_KEYWORD).and_then(Value::as_str) {
+                    Some(key) => ArrayComparison::Keyed(key.to_string()),
+                    None => ArrayComparison::Ordered,
+                }
+            },
+            _ => ArrayComparison::Ordered,
+        }
+    }
+}
+
+/// Compare the expected state of a resource to its actual state.
+///
+/// Only properties present in the expected state are compared, so additional properties returned
+/// in the actual state are ignored.  Paths refer to the expected state except for array items only
+/// found in the actual state.  Properties the schema marks as `readOnly` or `writeOnly` are not
+/// compared since they can't be set or aren't returned by the resource.
+///
+/// # Arguments
+///
+/// * `expected` - The expected state.
+/// * `actual` - The actual state.
+/// * `schema` - Optional JSON schema of the resource used to find comparison annotations.
+#[must_use]
+pub fn get_differences(expected: &Value, actual: &Value, schema: Option<&Value>) -> Vec<PropertyDifference> {
+    let mut differences = Vec::new();
+    if !expected.is_object() {
+        return differences;
+    }
+    let comparer = Comparer { root_schema: schema, symmetric: false };
+    let (expected, actual) = with_well_known_properties(expected, actual);
+    comparer.compare(&expected, &actual, schema, "", &mut differences);
+    differences
+}
+
+/// Compare the state of a resource before and after a change.
+///
+/// Unlike `get_differences`, properties that only exist in one of the states are reported.
+///
+/// # Arguments
+///
+/// * `before` - The state before the change.
+/// * `after` - The state after the change.
+/// * `schema` - Optional JSON schema of the resource used to find comparison annotations.
+#[must_use]
+pub fn get_changes(before: &Value, after: &Value, schema: Option<&Value>) -> Vec<PropertyChange> {
+    let mut differences = Vec::new();
+    if !after.is_object() {
+        return Vec::new();
+    }
+    let comparer = Comparer { root_schema: schema, symmetric: true };
+    let (after, before) = with_well_known_properties(after, before);
+    comparer.compare(&after, &before, schema, "", &mut differences);
+    differences.into_iter().map(PropertyChange::from).collect()
+}
+
+/// Compare the expected state of a resource to its actual state, retrieving the resource schema
+/// only when the states differ since schema annotations can only relax the comparison.
+///
+/// # Arguments
+///
+/// * `expected` - The expected state.
+/// * `actual` - The actual state.
+/// * `get_schema` - Function returning the JSON schema of the resource if available.
+#[must_use]
+pub fn get_differences_using_schema(expected: &Value, actual: &Value, get_schema: impl FnOnce() -> Option<Value>) -> Vec<PropertyDifference> {
+    let differences = get_differences(expected, actual, None);
+    if differences.is_empty() {
+        return differences;
+    }
+    match get_schema() {
+        Some(schema) => get_differences(expected, actual, Some(&schema)),
+        None => differences,
+    }
+}
+
+/// Compare the state of a resource before and after a change, retrieving the resource schema
+/// only when the states differ.
+///
+/// # Arguments
+///
+/// * `before` - The state before the change.
+/// * `after` - The state after the change.
+/// * `get_schema` - Function returning the JSON schema of the resource if available.
+#[must_use]
+pub fn get_changes_using_schema(before: &Value, after: &Value, get_schema: impl FnOnce() -> Option<Value>) -> Vec<PropertyChange> {
+    let changes = get_changes(before, after, None);
+    if changes.is_empty() {
+        return changes;
+    }
+    match get_schema() {
+        Some(schema) => get_changes(before, after, Some(&schema)),
+        None => changes,
+    }
+}
+
+/// Get the names of the top-level properties for a list of JSON pointers.
+#[must_use]
+pub fn get_property_names<'a>(paths: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for path in paths {
+        let name = path.trim_start_matches('/').split('/').next().unwrap_or_default();
+        let name = name.replace("~1", "/").replace("~0", "~");
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+// handle well-known optional properties with default values by adding them to both states
+fn with_well_known_properties(expected: &Value, actual: &Value) -> (Value, Value) {
+    let mut expected = expected.clone();
+    let mut actual = actual.clone();
+    if let Some(map) = expected.as_object_mut() {
+        for (key, value) in get_well_known_properties() {
+            if !map.contains_key(&key) {
+                map.insert(key.clone(), value.clone());
+            }
+
+            if actual.is_object() && actual[&key].is_null() {
+                actual[key.clone()] = value.clone();
+            }
+        }
+    }
+    (expected, actual)
+}
+
+fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+struct Comparer<'a> {
+    root_schema: Option<&'a Value>,
+    symmetric: bool,
+}
+
+impl<'a> Comparer<'a> {
+    // follow local `$ref` keywords so annotations on referenced definitions are found
+    fn resolve(&self, schema: Option<&'a Value>) -> Option<&'a Value> {
+        let mut current = schema?;
+        // limit the depth to guard against circular references
+        for _ in 0..32 {
+            let Some(reference) = current.get("$ref").and_then(Value::as_str) else {
+                return Some(current);
+            };
+            let pointer = reference.strip_prefix('#')?;
+            current = self.root_schema?.pointer(pointer)?;
+        }
+        None
+    }
+
+    fn property_schema(&self, schema: Option<&'a Value>, name: &str) -> Option<&'a Value> {
+        let schema = self.resolve(schema)?;
+        let property = schema.get("properties").and_then(|properties| properties.get(name));
+        match property {
+            Some(property) => self.resolve(Some(property)),
+            None => self.resolve(schema.get("additionalProperties").filter(|value| value.is_object())),
+        }
+    }
+
+    fn items_schema(&self, schema: Option<&'a Value>) -> Option<&'a Value> {
+        let schema = self.resolve(schema)?;
+        self.resolve(schema.get("items").filter(|value| value.is_object()))
+    }
+
+    fn compare(&self, expected: &Value, actual: &Value, schema: Option<&'a Value>, path: &str, differences: &mut Vec<PropertyDifference>) {
+        let schema = self.resolve(schema);
+        match (expected, actual) {
+            (Value::Object(expected_map), Value::Object(actual_map)) => {
+                self.compare_objects(expected_map, actual_map, schema, path, differences);
+            },
+            (Value::Array(expected_array), Value::Array(actual_array)) => {
+                self.compare_arrays(expected_array, actual_array, schema, path, differences);
+            },
+            _ => {
+                if !self.values_equal(expected, actual, schema) {
+                    differences.push(PropertyDifference {
+                        path: path.to_string(),
+                        expected: Some(expected.clone()),
+                        actual: if actual.is_null() { None } else { Some(actual.clone()) },
+                    });
+                }
+            },
+        }
+    }
+
+    fn compare_objects(&self, expected: &Map<String, Value>, actual: &Map<String, Value>, schema: Option<&'a Value>, path: &str, differences: &mut Vec<PropertyDifference>) {
+        for (key, expected_value) in expected {
+            let property_path = format!("{path}/{}", escape_pointer_token(key));
+            let property_schema = self.property_schema(schema, key);
+            if !self.symmetric && is_ignored(property_schema) {
+                continue;
+            }
+            if let Some(actual_value) = actual.get(key) {
+                self.compare(expected_value, actual_value, property_schema, &property_path, differences);
+            } else if !(self.symmetric && expected_value.is_null()) {
+                differences.push(PropertyDifference {
+                    path: property_path,
+                    expected: Some(expected_value.clone()),
+                    actual: None,
+                });
+            }
+        }
+
+        if self.symmetric {
+            for (key, actual_value) in actual {
+                if expected.contains_key(key) || actual_value.is_null() {
+                    continue;
+                }
+                differences.push(PropertyDifference {
+                    path: format!("{path}/{}", escape_pointer_token(key)),
+                    expected: None,
+                    actual: Some(actual_value.clone()),
+                });
+            }
+        }
+    }
+
+    fn compare_arrays(&self, expected: &[Value], actual: &[Value], schema: Option<&'a Value>, path: &str, differences: &mut Vec<PropertyDifference>) {
+        let items_schema = self.items_schema(schema);
+        match ArrayComparison::from_schema(schema) {
+            ArrayComparison::Ordered => {
+                for (index, expected_item) in expected.iter().enumerate() {
+                    let item_path = format!("{path}/{index}");
+                    match actual.get(index) {
+                        Some(actual_item) => self.compare(expected_item, actual_item, items_schema, &item_path, differences),
+                        None => differences.push(PropertyDifference {
+                            path: item_path,
+                            expected: Some(expected_item.clone()),
+                            actual: None,
+                        }),
+                    }
+                }
+                for (index, actual_item) in actual.iter().enumerate().skip(expected.len()) {
+                    differences.push(PropertyDifference {
+                        path: format!("{path}/{index}"),
+                        expected: None,
+                        actual: Some(actual_item.clone()),
+                    });
+                }
+            },
+            ArrayComparison::Unordered => {
+                let mut unmatched: Vec<&Value> = actual.iter().collect();
+                let mut equal = expected.len() == actual.len();
+                for expected_item in expected {
+                    match unmatched.iter().position(|actual_item| self.is_match(expected_item, actual_item, items_schema)) {
+                        Some(index) => {
+                            unmatched.remove(index);
+                        },
+                        None => {
+                            equal = false;
+                        },
+                    }
+                }
+                if !equal || !unmatched.is_empty() {
+                    differences.push(PropertyDifference {
+                        path: path.to_string(),
+                        expected: Some(Value::Array(expected.to_vec())),
+                        actual: Some(Value::Array(actual.to_vec())),
+                    });
+                }
+            },
+            ArrayComparison::Keyed(key) => {
+                let mut matched = vec![false; actual.len()];
+                for (index, expected_item) in expected.iter().enumerate() {
+                    let item_path = format!("{path}/{index}");
+                    let key_schema = self.property_schema(items_schema, &key);
+                    let actual_index = expected_item.get(&key).and_then(|key_value| {
+                        actual.iter().enumerate().position(|(actual_index, actual_item)| {
+                            !matched[actual_index] && actual_item.get(&key).is_some_and(|actual_key| self.values_equal(key_value, actual_key, key_schema))
+                        })
+                    });
+                    match actual_index {
+                        Some(actual_index) => {
+                            matched[actual_index] = true;
+                            self.compare(expected_item, &actual[actual_index], items_schema, &item_path, differences);
+                        },
+                        None => differences.push(PropertyDifference {
+                            path: item_path,
+                            expected: Some(expected_item.clone()),
+                            actual: None,
+                        }),
+                    }
+                }
+                for (index, actual_item) in actual.iter().enumerate() {
+                    if !matched[index] {
+                        differences.push(PropertyDifference {
+                            path: format!("{path}/{index}"),
+                            expected: None,
+                            actual: Some(actual_item.clone()),
+                        });
+                    }
+                }
+            },
+        }
+    }
+
+    fn is_match(&self, expected: &Value, actual: &Value, schema: Option<&'a Value>) -> bool {
+        let mut differences = Vec::new();
+        self.compare(expected, actual, schema, "", &mut differences);
+        differences.is_empty()
+    }
+
+    fn values_equal(&self, expected: &Value, actual: &Value, schema: Option<&'a Value>) -> bool {
+        if expected == actual {
+            return true;
+        }
+        let Some(schema) = self.resolve(schema) else {
+            return false;
+        };
+        match (expected, actual) {
+            (Value::String(expected), Value::String(actual)) => {
+                schema.get(CASE_INSENSITIVE_KEYWORD).and_then(Value::as_bool) == Some(true)
+                    && expected.to_lowercase() == actual.to_lowercase()
+            },
+            (Value::Number(expected), Value::Number(actual)) => {
+                match (schema.get(TOLERANCE_KEYWORD).and_then(Value::as_f64), expected.as_f64(), actual.as_f64()) {
+                    (Some(tolerance), Some(expected), Some(actual)) => (expected - actual).abs() <= tolerance,
+                    _ => false,
+                }
+            },
+            _ => false,
+        }
+    }
+}
+
+// read-only properties can't be set and write-only properties are never returned, so neither
+// can be compared to determine if a resource is in the desired state
+fn is_ignored(schema: Option<&Value>) -> bool {
+    schema.is_some_and(|schema| {
+        schema.get("readOnly").and_then(Value::as_bool) == Some(true)
+            || schema.get("writeOnly").and_then(Value::as_bool) == Some(true)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn nested_difference() {
+        let expected = json!({"settings": {"editor": {"fontSize": 12, "theme": "dark"}}});
+        let actual = json!({"settings": {"editor": {"fontSize": 14, "theme": "dark", "tabs": 4}}});
+        let differences = get_differences(&expected, &actual, None);
+        assert_eq!(differences, vec![PropertyDifference {
+            path: "/settings/editor/fontSize".to_string(),
+            expected: Some(json!(12)),
+            actual: Some(json!(14)),
+        }]);
+        assert_eq!(get_property_names(differences.iter().map(|d| d.path.as_str())), vec!["settings".to_string()]);
+    }
+
+    #[test]
+    fn missing_property() {
+        let expected = json!({"name": "a/b"});
+        let actual = json!({});
+        let differences = get_differences(&expected, &actual, None);
+        assert_eq!(differences[0].path, "/name");
+        assert_eq!(differences[0].actual, None);
+        assert_eq!(get_property_names(differences.iter().map(|d| d.path.as_str())), vec!["name".to_string()]);
+    }
+
+    #[test]
+    fn escaped_property_name() {
+        let expected = json!({"a/b": 1});
+        let actual = json!({"a/b": 2});
+        let differences = get_differences(&expected, &actual, None);
+        assert_eq!(differences[0].path, "/a~1b");
+        assert_eq!(get_property_names(differences.iter().map(|d| d.path.as_str())), vec!["a/b".to_string()]);
+    }
+
+    #[test]
+    fn well_known_exist_default() {
+        let expected = json!({"name": "a"});
+        let actual = json!({"name": "a", "_exist": false});
+        let differences = get_differences(&expected, &actual, None);
+        assert_eq!(differences[0].path, "/_exist");
+    }
+
+    #[test]
+    fn ordered_array() {
+        let expected = json!({"items": [1, 2]});
+        let actual = json!({"items": [2, 1, 3]});
+        let differences = get_differences(&expected, &actual, None);
+        let paths: Vec<&str> = differences.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec!["/items/0", "/items/1", "/items/2"]);
+    }
+
+    #[test]
+    fn unordered_array() {
+        let schema = json!({"properties": {"items": {"type": "array", "arrayComparison": "unordered"}}});
+        let expected = json!({"items": [1, 2]});
+        assert!(get_differences(&expected, &json!({"items": [2, 1]}), Some(&schema)).is_empty());
+        let differences = get_differences(&expected, &json!({"items": [2, 2]}), Some(&schema));
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].path, "/items");
+    }
+
+    #[test]
+    fn keyed_array() {
+        let schema = json!({
+            "properties": {"users": {"$ref": "#/definitions/users"}},
+            "definitions": {"users": {"type": "array", "arrayComparison": "keyed", "arrayKey": "name"}}
+        });
+        let expected = json!({"users": [{"name": "a", "shell": "bash"}, {"name": "b", "shell": "zsh"}]});
+        let actual = json!({"users": [{"name": "b", "shell": "sh"}, {"name": "a", "shell": "bash"}, {"name": "c"}]});
+        let differences = get_differences(&expected, &actual, Some(&schema));
+        assert_eq!(differences, vec![
+            PropertyDifference {
+                path: "/users/1/shell".to_string(),
+                expected: Some(json!("zsh")),
+                actual: Some(json!("sh")),
+            },
+            PropertyDifference {
+                path: "/users/2".to_string(),
+                expected: None,
+                actual: Some(json!({"name": "c"})),
+            },
+        ]);
+    }
+
+    #[test]
+    fn read_only_and_write_only_ignored() {
+        let schema = json!({"properties": {
+            "name": {"type": "string"},
+            "id": {"type": "integer", "readOnly": true},
+            "password": {"type": "string", "writeOnly": true}
+        }});
+        let expected = json!({"name": "a", "id": 1, "password": "secret"});
+        let actual = json!({"name": "a", "id": 2});
+        assert!(get_differences(&expected, &actual, Some(&schema)).is_empty());
+        assert_eq!(get_differences(&expected, &actual, None).len(), 2);
+    }
+
+    #[test]
+    fn case_insensitive_string() {
+        let schema = json!({"properties": {"name": {"type": "string", "caseInsensitive": true}, "value": {"type": "string"}}});
+        let expected = json!({"name": "Admin", "value": "A"});
+        let actual = json!({"name": "admin", "value": "a"});
+        let differences = get_differences(&expected, &actual, Some(&schema));
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].path, "/value");
+    }
+
+    #[test]
+    fn case_insensitive_array_key() {
+        let schema = json!({"properties": {"users": {
+            "type": "array",
+            "arrayComparison": "keyed",
+            "arrayKey": "name",
+            "items": {"properties": {"name": {"type": "string", "caseInsensitive": true}}}
+        }}});
+        let expected = json!({"users": [{"name": "Admin"}]});
+        let actual = json!({"users": [{"name": "ADMIN"}]});
+        assert!(get_differences(&expected, &actual, Some(&schema)).is_empty());
+    }
+
+    #[test]
+    fn numeric_tolerance() {
+        let schema = json!({"properties": {"size": {"type": "number", "tolerance": 0.5}}});
+        let expected = json!({"size": 10});
+        assert!(get_differences(&expected, &json!({"size": 10.25}), Some(&schema)).is_empty());
+        assert_eq!(get_differences(&expected, &json!({"size": 11}), Some(&schema)).len(), 1);
+    }
+
+    #[test]
+    fn changes_include_removed_properties() {
+        let before = json!({"name": "a", "value": 1});
+        let after = json!({"name": "a"});
+        let changes = get_changes(&before, &after, None);
+        assert_eq!(changes, vec![PropertyChange {
+            path: "/value".to_string(),
+            before: Some(json!(1)),
+            after: None,
+        }]);
+    }
+}
@@ -0,0 +1,356 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{atomic::{AtomicU64, Ordering}, mpsc, Arc, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, trace, warn};
+
+use crate::dscerror::DscError;
+use crate::policy::check_executable_policy;
+use super::{command_resource::{get_clean_environment, get_command_path, log_stderr_line}, manifest_lint::find_executable, resource_manifest::{ExitCode, ExitCodeSemantic, HostMethod}};
+
+const JSON_RPC_VERSION: &str = "2.0";
+const SHUTDOWN_METHOD: &str = "shutdown";
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_REQUEST_TIMEOUT: u64 = 300;
+// stderr and stdout are separate pipes, stderr written before a failed response may still be in flight
+const STDERR_SETTLE_TIME: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    jsonrpc: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    method: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<RequestParams>,
+}
+
+#[derive(Debug, Serialize)]
+struct RequestParams {
+    input: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    env: Option<HashMap<String, String>>,
+    #[serde(rename = "workingDirectory", skip_serializing_if = "Option::is_none")]
+    working_directory: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    id: Option<u64>,
+    result: Option<Value>,
+    error: Option<ResponseError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseError {
+    code: i32,
+    message: String,
+    // output of operations ending with an exit code that has a semantic
+    data: Option<Value>,
+}
+
+type PendingRequests = Arc<Mutex<HashMap<u64, mpsc::Sender<Response>>>>;
+// the stderr written by the host while each request is pending
+type StderrCaptures = Arc<Mutex<HashMap<u64, String>>>;
+
+/// A resource process started once and handling operations as line-delimited JSON-RPC over stdin and stdout.
+pub struct ResourceHost {
+    key: String,
+    executable: String,
+    timeout: Duration,
+    child: Mutex<Child>,
+    stdin: Mutex<Option<ChildStdin>>,
+    pending: PendingRequests,
+    stderr: StderrCaptures,
+    next_id: AtomicU64,
+}
+
+impl ResourceHost {
+    fn start(key: String, host: &HostMethod, cwd: &str, clean_env: bool) -> Result<Self, DscError> {
+        let executable = find_executable(&host.executable, Path::new(cwd));
+        check_executable_policy(&host.executable, executable.as_deref())?;
+        let mut command = Command::new(executable.unwrap_or_else(|| PathBuf::from(&host.executable)));
+        command.stdin(Stdio::piped());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+        if let Some(args) = &host.args {
+            command.args(args);
+        }
+        command.current_dir(cwd);
+        if clean_env {
+            command.env_clear();
+            command.envs(get_clean_environment());
+        }
+        if let Some(path) = get_command_path(cwd) {
+            command.env("PATH", path);
+        }
+
+        let mut child = match command.spawn() {
+            Ok(c) => c,
+            Err(e) => {
+                return Err(DscError::CommandOperation(e.to_string(), host.executable.clone()))
+            }
+        };
+        let process_id = child.id();
+        info!("Started resource host '{}' with process id {process_id}", host.executable);
+
+        let stdin = child.stdin.take().expect("child did not have a handle to stdin");
+        let stdout = child.stdout.take().expect("child did not have a handle to stdout");
+        let stderr = child.stderr.take().expect("child did not have a handle to stderr");
+
+        let stderr_captures: StderrCaptures = Arc::new(Mutex::new(HashMap::new()));
+        let reader_captures = stderr_captures.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                let filtered_line = log_stderr_line(&process_id, &line);
+                if !filtered_line.is_empty() {
+                    debug!("Process id {process_id} : {filtered_line}");
+                    for capture in reader_captures.lock().unwrap().values_mut() {
+                        capture.push_str(filtered_line);
+                        capture.push('\n');
+                    }
+                }
+            }
+        });
+
+        let pending: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let reader_pending = pending.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                trace!("Resource host {process_id} response: {line}");
+                let response = match serde_json::from_str::<Response>(&line) {
+                    Ok(response) => response,
+                    Err(err) => {
+                        warn!("Resource host {process_id} returned invalid response '{line}': {err}");
+                        continue;
+                    }
+                };
+                let Some(id) = response.id else {
+                    continue;
+                };
+                if let Some(sender) = reader_pending.lock().unwrap().remove(&id) {
+                    let _ = sender.send(response);
+                }
+            }
+            // dropping the remaining senders fails requests still waiting for a response
+            debug!("Resource host {process_id} closed its output");
+            reader_pending.lock().unwrap().clear();
+        });
+
+        Ok(Self {
+            key,
+            executable: host.executable.clone(),
+            timeout: Duration::from_secs(host.timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT)),
+            child: Mutex::new(child),
+            stdin: Mutex::new(Some(stdin)),
+            pending,
+            stderr: stderr_captures,
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    fn send(&self, request: &Request) -> Result<(), DscError> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        trace!("Resource host request: {line}");
+        let mut stdin = self.stdin.lock().unwrap();
+        let Some(stdin) = stdin.as_mut() else {
+            return Err(DscError::CommandOperation("Resource host was shut down".to_string(), self.executable.clone()));
+        };
+        stdin.write_all(line.as_bytes())?;
+        stdin.flush()?;
+        Ok(())
+    }
+
+    /// Send an operation to the host and wait for its output.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The name of the operation.
+    /// * `input` - The JSON input for the operation, can be empty.
+    /// * `env` - The environment variables the operation runs with.
+    /// * `working_directory` - The directory the operation runs in.
+    /// * `exit_codes` - Descriptions and semantics of the error codes returned by the host.
+    ///
+    /// # Returns
+    ///
+    /// The exit code, the output as the lines the equivalent command would write to stdout, and the stderr
+    /// the host wrote while the operation was pending.
+    /// Error codes with a semantic other than `retryable` are returned with the `data` of the error as output.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the host can't be reached, doesn't respond within the timeout, or the operation fails.
+    /// A host that doesn't respond is terminated and started again by the next operation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock on the pending requests or their stderr is poisoned.
+    pub fn invoke(&self, method: &str, input: &str, env: Option<HashMap<String, String>>, working_directory: Option<String>, exit_codes: &Option<HashMap<i32, ExitCode>>) -> Result<(i32, String, String), DscError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let input = if input.is_empty() { Value::Null } else { serde_json::from_str(input)? };
+        let (sender, receiver) = mpsc::channel();
+        self.pending.lock().unwrap().insert(id, sender);
+        self.stderr.lock().unwrap().insert(id, String::new());
+
+        let request = Request {
+            jsonrpc: JSON_RPC_VERSION,
+            id: Some(id),
+            method,
+            params: Some(RequestParams { input, env, working_directory }),
+        };
+        if let Err(err) = self.send(&request) {
+            self.pending.lock().unwrap().remove(&id);
+            self.take_stderr(id);
+            return Err(err);
+        }
+
+        let response = match receiver.recv_timeout(self.timeout) {
+            Ok(response) => response,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                self.pending.lock().unwrap().remove(&id);
+                self.take_stderr(id);
+                warn!("Resource host '{}' did not respond to request {id}, terminating it", self.executable);
+                self.terminate();
+                return Err(DscError::CommandOperation(format!("Resource host did not respond within {} seconds", self.timeout.as_secs()), self.executable.clone()));
+            },
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                self.take_stderr(id);
+                return Err(DscError::CommandOperation("Resource host exited before responding".to_string(), self.executable.clone()));
+            },
+        };
+        if let Some(error) = response.error {
+            debug!("Resource host '{}' request {id} failed with code {}", self.executable, error.code);
+            thread::sleep(STDERR_SETTLE_TIME);
+            let stderr = self.take_stderr(id);
+            if let Some(exit_code) = exit_codes.as_ref().and_then(|exit_codes| exit_codes.get(&error.code)) {
+                if exit_code.semantic().is_some_and(|semantic| *semantic != ExitCodeSemantic::Retryable) {
+                    return Ok((error.code, to_output(error.data), stderr));
+                }
+                return Err(DscError::CommandExitFromManifest(self.executable.clone(), error.code, exit_code.description().to_string(), format!("{stderr}{}", error.message)));
+            }
+            return Err(DscError::Command(self.executable.clone(), error.code, format!("{stderr}{}", error.message)));
+        }
+
+        Ok((0, to_output(response.result), self.take_stderr(id)))
+    }
+
+    fn take_stderr(&self, id: u64) -> String {
+        self.stderr.lock().unwrap().remove(&id).unwrap_or_default()
+    }
+
+    // stop a host that is no longer responding so the next operation starts a new one
+    fn terminate(&self) {
+        {
+            let mut hosts = get_hosts().lock().unwrap();
+            if hosts.get(&self.key).is_some_and(|host| std::ptr::eq(host.as_ref(), self)) {
+                hosts.remove(&self.key);
+            }
+        }
+        self.stdin.lock().unwrap().take();
+        let mut child = self.child.lock().unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    fn shutdown(&self) {
+        let request = Request {
+            jsonrpc: JSON_RPC_VERSION,
+            id: None,
+            method: SHUTDOWN_METHOD,
+            params: None,
+        };
+        if let Err(err) = self.send(&request) {
+            debug!("Failed to send shutdown to resource host '{}': {err}", self.executable);
+        }
+        // closing stdin lets hosts that ignore the notification exit at end of input
+        self.stdin.lock().unwrap().take();
+
+        let mut child = self.child.lock().unwrap();
+        let start = Instant::now();
+        while start.elapsed() < SHUTDOWN_TIMEOUT {
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    debug!("Resource host '{}' exited with {status}", self.executable);
+                    return;
+                },
+                Ok(None) => thread::sleep(Duration::from_millis(20)),
+                Err(err) => {
+                    warn!("Failed to wait for resource host '{}': {err}", self.executable);
+                    break;
+                }
+            }
+        }
+        warn!("Resource host '{}' did not exit, terminating it", self.executable);
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+impl Drop for ResourceHost {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn get_hosts() -> &'static Mutex<HashMap<String, Arc<ResourceHost>>> {
+    static HOSTS: OnceLock<Mutex<HashMap<String, Arc<ResourceHost>>>> = OnceLock::new();
+    HOSTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// each value of the result corresponds to a line of output of the equivalent command
+fn to_output(value: Option<Value>) -> String {
+    match value {
+        Some(Value::Array(values)) => values.iter().map(Value::to_string).collect::<Vec<String>>().join("\n"),
+        Some(value) => value.to_string(),
+        None => String::new(),
+    }
+}
+
+/// Get the running host for a resource, starting it if needed.
+///
+/// # Arguments
+///
+/// * `host` - How to start the host.
+/// * `cwd` - The working directory for the host.
+/// * `clean_env` - Whether the host only receives the allowlisted environment variables.
+///
+/// # Errors
+///
+/// Error returned if the host can't be started.
+///
+/// # Panics
+///
+/// Panics if the lock on the running hosts is poisoned.
+pub fn get_resource_host(host: &HostMethod, cwd: &str, clean_env: bool) -> Result<Arc<ResourceHost>, DscError> {
+    let key = format!("{cwd}|{}|{:?}|{clean_env}", host.executable, host.args);
+    let mut hosts = get_hosts().lock().unwrap();
+    if let Some(resource_host) = hosts.get(&key) {
+        return Ok(resource_host.clone());
+    }
+    let resource_host = Arc::new(ResourceHost::start(key.clone(), host, cwd, clean_env)?);
+    hosts.insert(key, resource_host.clone());
+    Ok(resource_host)
+}
+
+/// Gracefully stop all running resource hosts, this is expected before the process exits.
+/// Hosts still in use by an operation stop when the operation releases them.
+///
+/// # Panics
+///
+/// Panics if the lock on the running hosts is poisoned.
+pub fn shutdown_resource_hosts() {
+    // hosts are dropped outside of the lock as stopping them waits for them to exit
+    let hosts: Vec<Arc<ResourceHost>> = get_hosts().lock().unwrap().drain().map(|(_, host)| host).collect();
+    drop(hosts);
+}
@@ -7,7 +7,9 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 
+use crate::configure::config_doc::SecurityContextKind;
 use crate::dscerror::DscError;
+use crate::settings::SecurityContextSettings;
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 pub enum Kind {
@@ -61,14 +63,36 @@ pub struct ResourceManifest {
     /// Indicates the resource is a adapter of other resources.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub adapter: Option<Adapter>,
-    /// Mapping of exit codes to descriptions.  Zero is always success and non-zero is always failure.
+    /// Details how to start the resource as a persistent process handling operations using JSON-RPC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<HostMethod>,
+    /// Indicates the commands of the resource only receive an allowlist of environment variables, like `PATH` and `HOME`, in addition to the declared ones.
+    /// The directories of the manifest and of dsc are still appended to `PATH` unless a method declares its own.
+    #[serde(rename = "cleanEnvironment", skip_serializing_if = "Option::is_none")]
+    pub clean_environment: Option<bool>,
+    /// Details how input passed as environment variables is encoded.
+    #[serde(rename = "envInput", skip_serializing_if = "Option::is_none")]
+    pub env_input: Option<EnvInput>,
+    /// Mapping of exit codes to descriptions and optional semantics.  Zero is always success and non-zero is failure unless a semantic says otherwise.
     #[serde(rename = "exitCodes", skip_serializing_if = "Option::is_none")]
-    pub exit_codes: Option<HashMap<i32, String>>,
+    pub exit_codes: Option<HashMap<i32, ExitCode>>,
+    /// Details how failed operations of the resource are retried.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryPolicy>,
+    /// The security context the commands of the resource require, the `securityContext` metadata of an instance takes precedence.
+    #[serde(rename = "securityContext", skip_serializing_if = "Option::is_none")]
+    pub security_context: Option<SecurityContextKind>,
+    /// The wrappers changing the security context, set for configuration instances instead of reading the settings.
+    #[serde(skip)]
+    pub security_context_settings: Option<SecurityContextSettings>,
     /// Details how to get the schema of the resource.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub schema: Option<SchemaKind>,
 }
 
+/// The latest version of the resource manifest schema, used when a manifest doesn't declare one.
+pub const MANIFEST_SCHEMA_VERSION: &str = "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json";
+
 // Defines the valid and recognized canonical URIs for the manifest schema
 #[derive(Debug, Default, Clone, Copy, Hash, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
 pub enum ManifestSchemaUri {
@@ -106,6 +130,17 @@ pub enum ArgKind {
         /// Indicates if argument is mandatory which will pass an empty string if no JSON input is provided.  Default is false.
         mandatory: Option<bool>,
     },
+    /// The arguments are rendered from a property of the JSON input object.
+    Property {
+        /// The name of the property of the JSON input object.
+        #[serde(rename = "propertyArg")]
+        property_arg: String,
+        /// The arguments passed when the property is set, where `{value}` is replaced with the value of the property.
+        /// Boolean properties pass the arguments only when true and array properties pass them for each item.
+        /// Default is the value of the property as a single argument.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        template: Option<Vec<String>>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
@@ -116,6 +151,141 @@ pub enum InputKind {
     /// The input is accepted as a JSON object via STDIN.
     #[serde(rename = "stdin")]
     Stdin,
+    /// The input is accepted as arguments, properties not rendered by a `propertyArg` are passed as `--<property> <value>`.
+    #[serde(rename = "args")]
+    Args,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EnvInput {
+    /// The prefix added to the names of the environment variables.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// The separator between the names of nested properties, like `PARENT__CHILD`.  Default is `__`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator: Option<String>,
+    /// How array values are encoded.  Default is `comma`.
+    #[serde(rename = "arrayEncoding", skip_serializing_if = "Option::is_none")]
+    pub array_encoding: Option<ArrayEncoding>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub enum ArrayEncoding {
+    /// The items are joined with commas, only string and number items are supported.
+    #[serde(rename = "comma")]
+    Comma,
+    /// The items are joined with newlines, only string and number items are supported.
+    #[serde(rename = "newline")]
+    Newline,
+    /// The array is passed as JSON.
+    #[serde(rename = "json")]
+    Json,
+    /// Each item is passed as a separate variable using its index as the name, like `PARENT__0`.
+    #[serde(rename = "indexed")]
+    Indexed,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ExitCode {
+    /// The exit code is a failure with this description.
+    Description(String),
+    /// The exit code has a description and a semantic the engine acts on.
+    Semantic {
+        /// The description of the exit code.
+        description: String,
+        /// How the engine treats the exit code.
+        semantic: ExitCodeSemantic,
+    },
+}
+
+impl ExitCode {
+    /// The description of the exit code.
+    #[must_use]
+    pub fn description(&self) -> &str {
+        match self {
+            ExitCode::Description(description) | ExitCode::Semantic { description, .. } => description,
+        }
+    }
+
+    /// The semantic of the exit code, if any.
+    #[must_use]
+    pub fn semantic(&self) -> Option<&ExitCodeSemantic> {
+        match self {
+            ExitCode::Description(_) => None,
+            ExitCode::Semantic { semantic, .. } => Some(semantic),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub enum ExitCodeSemantic {
+    /// The operation succeeded, but a reboot is required to complete it.
+    #[serde(rename = "rebootRequired")]
+    RebootRequired,
+    /// The operation failed because of a transient condition and can be retried.
+    #[serde(rename = "retryable")]
+    Retryable,
+    /// Returned by `test` when the instance isn't in the desired state.
+    #[serde(rename = "notInDesiredState")]
+    NotInDesiredState,
+    /// Returned by `get` or `delete` when the instance doesn't exist.
+    #[serde(rename = "notFound")]
+    NotFound,
+    /// The operation succeeded with a warning given by the description.
+    #[serde(rename = "warning")]
+    Warning,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// The maximum number of times an operation is attempted.  Default is 1, or 3 if any exit code is `retryable`.
+    #[serde(rename = "maxAttempts", skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    /// How the delay between attempts grows.  Default is `fixed`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backoff: Option<RetryBackoff>,
+    /// The delay before the first retry in milliseconds.  Default is 1000.
+    #[serde(rename = "delayMilliseconds", skip_serializing_if = "Option::is_none")]
+    pub delay_milliseconds: Option<u64>,
+    /// The exit codes of failures that are retried, in addition to the ones with the `retryable` semantic.
+    #[serde(rename = "exitCodes", skip_serializing_if = "Option::is_none")]
+    pub exit_codes: Option<Vec<i32>>,
+    /// Regular expressions matched against the stderr captured from a failed command to retry it.
+    #[serde(rename = "stderrPatterns", skip_serializing_if = "Option::is_none")]
+    pub stderr_patterns: Option<Vec<String>>,
+}
+
+impl RetryPolicy {
+    /// Indicates failed operations can be attempted again.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.max_attempts.unwrap_or(1) > 1
+    }
+
+    /// Merge the values set by another policy over the values of this one.
+    #[must_use]
+    pub fn merge(self, overrides: RetryPolicy) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: overrides.max_attempts.or(self.max_attempts),
+            backoff: overrides.backoff.or(self.backoff),
+            delay_milliseconds: overrides.delay_milliseconds.or(self.delay_milliseconds),
+            exit_codes: overrides.exit_codes.or(self.exit_codes),
+            stderr_patterns: overrides.stderr_patterns.or(self.stderr_patterns),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub enum RetryBackoff {
+    /// The delay is the same between all attempts.
+    #[serde(rename = "fixed")]
+    Fixed,
+    /// The delay doubles after each attempt.
+    #[serde(rename = "exponential")]
+    Exponential,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
@@ -146,6 +316,17 @@ pub enum ReturnKind {
     StateAndDiff,
 }
 
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct HostMethod {
+    /// The command to run to start the resource host.
+    pub executable: String,
+    /// The arguments to pass to the command to start the resource host.
+    pub args: Option<Vec<String>>,
+    /// The number of seconds to wait for the resource host to respond to an operation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 pub struct GetMethod {
     /// The command to run to get the state of the resource.
@@ -155,6 +336,12 @@ pub struct GetMethod {
     /// How to pass optional input for a Get.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub input: Option<InputKind>,
+    /// Environment variables to set for the command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+    /// The working directory of the command, relative to the directory of the manifest.  Default is the directory of the manifest.
+    #[serde(rename = "workingDirectory", skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
@@ -165,6 +352,12 @@ pub struct SetMethod {
     pub args: Option<Vec<ArgKind>>,
     /// How to pass required input for a Set.
     pub input: Option<InputKind>,
+    /// Environment variables to set for the command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+    /// The working directory of the command, relative to the directory of the manifest.  Default is the directory of the manifest.
+    #[serde(rename = "workingDirectory", skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
     /// Whether to run the Test method before the Set method.  True means the resource will perform its own test before running the Set method.
     #[serde(rename = "implementsPretest", skip_serializing_if = "Option::is_none")]
     pub pre_test: Option<bool>,
@@ -184,6 +377,12 @@ pub struct TestMethod {
     pub args: Option<Vec<ArgKind>>,
     /// How to pass required input for a Test.
     pub input: Option<InputKind>,
+    /// Environment variables to set for the command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+    /// The working directory of the command, relative to the directory of the manifest.  Default is the directory of the manifest.
+    #[serde(rename = "workingDirectory", skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
     /// The type of return value expected from the Test method.
     #[serde(rename = "return", skip_serializing_if = "Option::is_none")]
     pub returns: Option<ReturnKind>,
@@ -197,16 +396,28 @@ pub struct DeleteMethod {
     pub args: Option<Vec<ArgKind>>,
     /// How to pass required input for a Delete.
     pub input: Option<InputKind>,
+    /// Environment variables to set for the command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+    /// The working directory of the command, relative to the directory of the manifest.  Default is the directory of the manifest.
+    #[serde(rename = "workingDirectory", skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
-pub struct ValidateMethod { // TODO: enable validation via schema or command
+pub struct ValidateMethod {
     /// The command to run to validate the state of the resource.
     pub executable: String,
     /// The arguments to pass to the command to perform a Validate.
     pub args: Option<Vec<ArgKind>>,
     /// How to pass required input for a Validate.
     pub input: Option<InputKind>,
+    /// Environment variables to set for the command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+    /// The working directory of the command, relative to the directory of the manifest.  Default is the directory of the manifest.
+    #[serde(rename = "workingDirectory", skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
@@ -217,6 +428,12 @@ pub struct ExportMethod {
     pub args: Option<Vec<ArgKind>>,
     /// How to pass input for a Export.
     pub input: Option<InputKind>,
+    /// Environment variables to set for the command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+    /// The working directory of the command, relative to the directory of the manifest.  Default is the directory of the manifest.
+    #[serde(rename = "workingDirectory", skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
@@ -227,6 +444,12 @@ pub struct ResolveMethod {
     pub args: Option<Vec<ArgKind>>,
     /// How to pass input for a Export.
     pub input: Option<InputKind>,
+    /// Environment variables to set for the command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+    /// The working directory of the command, relative to the directory of the manifest.  Default is the directory of the manifest.
+    #[serde(rename = "workingDirectory", skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
@@ -268,16 +491,35 @@ pub struct ListMethod {
 /// # Errors
 ///
 /// * `DscError` - The JSON value is invalid or the schema version is not supported.
-pub fn import_manifest(manifest: Value) -> Result<ResourceManifest, DscError> {
-    // TODO: enable schema version validation, if not provided, use the latest
-    // const MANIFEST_SCHEMA_VERSION: &str = "https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/bundled/resource/manifest.json";
+pub fn import_manifest(mut manifest: Value) -> Result<ResourceManifest, DscError> {
+    if let Value::Object(properties) = &mut manifest {
+        match properties.get("$schema") {
+            // if not provided, use the latest
+            None => {
+                properties.insert("$schema".to_string(), Value::String(MANIFEST_SCHEMA_VERSION.to_string()));
+            },
+            Some(schema) => {
+                if !is_supported_schema_version(schema) {
+                    let schema = schema.as_str().map_or_else(|| schema.to_string(), ToString::to_string);
+                    return Err(DscError::InvalidManifestSchemaVersion(schema, MANIFEST_SCHEMA_VERSION.to_string()));
+                }
+            },
+        }
+    }
     let manifest = serde_json::from_value::<ResourceManifest>(manifest)?;
-    // if !manifest.schema_version.eq(MANIFEST_SCHEMA_VERSION) {
-    //     return Err(DscError::InvalidManifestSchemaVersion(manifest.schema_version, MANIFEST_SCHEMA_VERSION.to_string()));
-    // }
     Ok(manifest)
 }
 
+/// Check if a value is the URI of a supported version of the resource manifest schema.
+///
+/// # Arguments
+///
+/// * `schema` - The value of the `$schema` property of a manifest.
+#[must_use]
+pub fn is_supported_schema_version(schema: &Value) -> bool {
+    schema.is_string() && serde_json::from_value::<ManifestSchemaUri>(schema.clone()).is_ok()
+}
+
 /// Validate a semantic version string.
 ///
 /// # Arguments
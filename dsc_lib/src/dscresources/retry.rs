@@ -0,0 +1,211 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use regex::Regex;
+use std::{thread, time::Duration};
+use tracing::{debug, warn};
+
+use crate::dscerror::DscError;
+use super::{dscresource::DscResource, resource_manifest::{import_manifest, ExitCodeSemantic, RetryBackoff, RetryPolicy}};
+
+const DEFAULT_RETRYABLE_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_DELAY_MILLISECONDS: u64 = 1000;
+
+/// Get the retry policy of a resource.
+///
+/// # Arguments
+///
+/// * `resource` - The resource to get the retry policy of.
+/// * `overrides` - Values overriding the ones declared by the manifest, like the ones of a configuration instance.
+///
+/// # Returns
+///
+/// The policy declared by the manifest merged with the overrides.  Exit codes with the `retryable`
+/// semantic are always retried and enable three attempts unless the maximum is set.
+///
+/// # Errors
+///
+/// Error returned if the manifest of the resource is invalid.
+pub fn get_retry_policy(resource: &DscResource, overrides: Option<RetryPolicy>) -> Result<RetryPolicy, DscError> {
+    let manifest = match &resource.manifest {
+        Some(manifest) => Some(import_manifest(manifest.clone())?),
+        None => None,
+    };
+    let mut policy = manifest.as_ref().and_then(|manifest| manifest.retry.clone()).unwrap_or_default();
+    if let Some(overrides) = overrides {
+        policy = policy.merge(overrides);
+    }
+
+    let retryable_exit_codes = manifest.iter()
+        .filter_map(|manifest| manifest.exit_codes.as_ref())
+        .flatten()
+        .filter(|(_, exit_code)| exit_code.semantic() == Some(&ExitCodeSemantic::Retryable))
+        .map(|(code, _)| *code)
+        .collect::<Vec<i32>>();
+    if !retryable_exit_codes.is_empty() {
+        policy.max_attempts.get_or_insert(DEFAULT_RETRYABLE_MAX_ATTEMPTS);
+        policy.exit_codes.get_or_insert_with(Vec::new).extend(retryable_exit_codes);
+    }
+    Ok(policy)
+}
+
+/// Invoke an operation of a resource, attempting it again while it fails in a way the policy retries.
+///
+/// # Arguments
+///
+/// * `policy` - The retry policy of the resource.
+/// * `resource_type` - The type of the resource, used for tracing.
+/// * `operation` - The name of the operation, used for tracing.
+/// * `invoke` - The function invoking the operation.
+///
+/// # Returns
+///
+/// The result of the operation and the number of attempts it took.
+///
+/// # Errors
+///
+/// Error returned if a stderr pattern is not a valid regular expression, or the error of the last
+/// attempt if the operation doesn't succeed.
+pub fn invoke_with_retry<T>(policy: &RetryPolicy, resource_type: &str, operation: &str, mut invoke: impl FnMut() -> Result<T, DscError>) -> Result<(T, u32), DscError> {
+    let max_attempts = policy.max_attempts.unwrap_or(1).max(1);
+    let mut stderr_patterns = Vec::new();
+    for pattern in policy.stderr_patterns.iter().flatten() {
+        match Regex::new(pattern) {
+            Ok(regex) => stderr_patterns.push(regex),
+            Err(err) => return Err(DscError::Operation(format!("Invalid retry stderr pattern '{pattern}': {err}"))),
+        }
+    }
+
+    let mut attempt = 1;
+    loop {
+        match invoke() {
+            Ok(result) => {
+                debug!("Resource '{resource_type}' {operation} succeeded after {attempt} attempt(s)");
+                return Ok((result, attempt));
+            },
+            Err(err) => {
+                if attempt >= max_attempts || !is_retryable(policy, &stderr_patterns, &err) {
+                    return Err(err);
+                }
+                let delay = get_retry_delay(policy, attempt);
+                warn!("Resource '{resource_type}' {operation} attempt {attempt} of {max_attempts} failed, retrying in {} ms: {err}", delay.as_millis());
+                thread::sleep(delay);
+                attempt += 1;
+            },
+        }
+    }
+}
+
+// only failed commands are retried, the patterns are matched against the stderr they captured
+fn is_retryable(policy: &RetryPolicy, stderr_patterns: &[Regex], err: &DscError) -> bool {
+    let (DscError::Command(_, code, stderr) | DscError::CommandExitFromManifest(_, code, _, stderr)) = err else {
+        return false;
+    };
+    if policy.exit_codes.as_ref().is_some_and(|exit_codes| exit_codes.contains(code)) {
+        return true;
+    }
+    stderr_patterns.iter().any(|pattern| pattern.is_match(stderr))
+}
+
+fn get_retry_delay(policy: &RetryPolicy, attempt: u32) -> Duration {
+    let delay = policy.delay_milliseconds.unwrap_or(DEFAULT_RETRY_DELAY_MILLISECONDS);
+    match policy.backoff {
+        Some(RetryBackoff::Exponential) => Duration::from_millis(delay.saturating_mul(2u64.saturating_pow(attempt - 1))),
+        Some(RetryBackoff::Fixed) | None => Duration::from_millis(delay),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_matching_exit_code_until_max_attempts() {
+        let policy = RetryPolicy {
+            max_attempts: Some(3),
+            delay_milliseconds: Some(0),
+            exit_codes: Some(vec![100]),
+            ..Default::default()
+        };
+        let mut calls = 0;
+        let result: Result<((), u32), DscError> = invoke_with_retry(&policy, "Test/Retry", "get", || {
+            calls += 1;
+            Err(DscError::Command("test".to_string(), 100, "locked".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn retries_matching_stderr_pattern_until_success() {
+        let policy = RetryPolicy {
+            max_attempts: Some(5),
+            delay_milliseconds: Some(0),
+            stderr_patterns: Some(vec!["dpkg.*lock".to_string()]),
+            ..Default::default()
+        };
+        let mut calls = 0;
+        let (value, attempts) = invoke_with_retry(&policy, "Test/Retry", "set", || {
+            calls += 1;
+            if calls < 3 {
+                return Err(DscError::Command("test".to_string(), 1, "Could not get dpkg frontend lock".to_string()));
+            }
+            Ok(calls)
+        }).unwrap();
+        assert_eq!(value, 3);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn matches_stderr_pattern_against_captured_stderr() {
+        let policy = RetryPolicy {
+            max_attempts: Some(2),
+            delay_milliseconds: Some(0),
+            stderr_patterns: Some(vec!["^Resource".to_string()]),
+            ..Default::default()
+        };
+        let mut calls = 0;
+        let result: Result<((), u32), DscError> = invoke_with_retry(&policy, "Test/Retry", "get", || {
+            calls += 1;
+            Err(DscError::CommandExitFromManifest("test".to_string(), 1, "Failure".to_string(), "locked".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+
+        let mut calls = 0;
+        let result: Result<((), u32), DscError> = invoke_with_retry(&policy, "Test/Retry", "get", || {
+            calls += 1;
+            Err(DscError::CommandExitFromManifest("test".to_string(), 1, "Failure".to_string(), "Resource locked".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn does_not_retry_other_failures() {
+        let policy = RetryPolicy {
+            max_attempts: Some(3),
+            delay_milliseconds: Some(0),
+            exit_codes: Some(vec![100]),
+            ..Default::default()
+        };
+        let mut calls = 0;
+        let result: Result<((), u32), DscError> = invoke_with_retry(&policy, "Test/Retry", "get", || {
+            calls += 1;
+            Err(DscError::Command("test".to_string(), 1, "failed".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn exponential_backoff_doubles_delay() {
+        let policy = RetryPolicy {
+            backoff: Some(RetryBackoff::Exponential),
+            delay_milliseconds: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(get_retry_delay(&policy, 1), Duration::from_millis(100));
+        assert_eq!(get_retry_delay(&policy, 3), Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,425 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fs, path::{Path, PathBuf}, time::{Duration, SystemTime}};
+use tracing::{debug, trace};
+
+use crate::audit::{get_document_hash, redact, Secrets};
+use crate::configure::config_doc::{ExecutionKind, Operation};
+use crate::dscerror::DscError;
+use crate::dscresources::property_diff::{get_changes, PropertyChange};
+use crate::settings::{get_settings, get_user_state_directory};
+
+/// The number of runs kept in the history when not configured.
+pub const DEFAULT_MAX_RUNS: usize = 100;
+
+/// A configuration run stored in the history.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRecord {
+    /// The identifier of the run, ordered by the time of the run.
+    pub id: String,
+    /// When the run completed, in RFC 3339 format.
+    pub timestamp: String,
+    pub operation: Operation,
+    pub execution_type: ExecutionKind,
+    /// The SHA-256 of the configuration document.
+    pub document_hash: String,
+    pub had_errors: bool,
+    /// The result of the run with secrets redacted.
+    pub result: Value,
+}
+
+/// The summary of a run in the history.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HistorySummary {
+    pub id: String,
+    pub timestamp: String,
+    pub operation: Operation,
+    pub execution_type: ExecutionKind,
+    pub document_hash: String,
+    pub had_errors: bool,
+    /// The number of instances in the result.
+    pub resources: usize,
+    /// Whether all instances were in the desired state, for `test` runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_desired_state: Option<bool>,
+    /// The number of instances with changed properties, for `set` runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum InstanceDiffStatus {
+    /// The instance is only in the later run.
+    Added,
+    /// The instance is only in the earlier run.
+    Removed,
+    /// The state of the instance or whether it is in the desired state differs.
+    Changed,
+    Unchanged,
+}
+
+/// The difference of an instance between two runs.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceDiff {
+    /// The name of the instance, prefixed by the names of the groups containing it.
+    pub name: String,
+    #[serde(rename = "type")]
+    pub resource_type: String,
+    pub status: InstanceDiffStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_in_desired_state: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_in_desired_state: Option<bool>,
+    /// The changes of the state from the earlier run to the later run.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<PropertyChange>,
+}
+
+/// The difference between two runs in the history.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryDiff {
+    pub from: String,
+    pub to: String,
+    pub resources: Vec<InstanceDiff>,
+}
+
+/// The local store of the results of configuration runs, one JSON file per run.
+pub struct HistoryStore {
+    directory: PathBuf,
+    enabled: bool,
+    max_runs: usize,
+    max_age: Option<Duration>,
+}
+
+impl HistoryStore {
+    /// Get the history store configured by the `history` settings.
+    ///
+    /// The store defaults to `%LOCALAPPDATA%\dsc\history` on Windows and `$XDG_STATE_HOME/dsc/history`
+    /// or `~/.local/state/dsc/history` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the settings are invalid or the store can't be located.
+    pub fn from_settings() -> Result<HistoryStore, DscError> {
+        let settings = get_settings()?.history.unwrap_or_default();
+        let directory = match settings.path.filter(|path| !path.is_empty()) {
+            Some(path) => PathBuf::from(path),
+            None => get_default_history_path()?,
+        };
+        Ok(HistoryStore {
+            directory,
+            enabled: settings.enabled != Some(false),
+            max_runs: settings.max_runs.unwrap_or(DEFAULT_MAX_RUNS),
+            max_age: settings.max_age_days.map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+        })
+    }
+
+    /// Whether runs are recorded.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Record a run and remove the runs exceeding the retention limits.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The operation of the run.
+    /// * `document` - The configuration document.
+    /// * `result` - The result of the run.
+    /// * `secrets` - Values redacted from the result in addition to secure strings and objects.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the run can't be written.
+    pub fn add(&self, operation: Operation, document: &str, result: &Value, secrets: &Secrets) -> Result<HistoryRecord, DscError> {
+        let now = chrono::Utc::now();
+        let microsoft = &result["metadata"]["Microsoft.DSC"];
+        let record = HistoryRecord {
+            id: format!("{}-{}", now.format("%Y%m%dT%H%M%S%.3fZ"), std::process::id()),
+            timestamp: now.to_rfc3339(),
+            operation,
+            execution_type: serde_json::from_value(microsoft["executionType"].clone()).unwrap_or(ExecutionKind::Actual),
+            document_hash: get_document_hash(document),
+            had_errors: result["hadErrors"].as_bool().unwrap_or_default(),
+            result: redact(result, secrets),
+        };
+        fs::create_dir_all(&self.directory).map_err(|err| self.error(&err))?;
+        let path = self.directory.join(format!("{}.json", record.id));
+        fs::write(&path, serde_json::to_string(&record)?).map_err(|err| self.error(&err))?;
+        debug!("Run recorded in history as '{}'", record.id);
+        self.prune()?;
+        Ok(record)
+    }
+
+    /// Get the summaries of the runs in the history, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if the store can't be read.
+    pub fn list(&self) -> Result<Vec<HistorySummary>, DscError> {
+        let mut summaries = Vec::new();
+        for path in self.get_record_paths()? {
+            match read_record(&path) {
+                Ok(record) => summaries.push(get_summary(record)),
+                Err(err) => trace!("Skipping history record {path:?}: {err}"),
+            }
+        }
+        Ok(summaries)
+    }
+
+    /// Get a run by its identifier or a unique prefix of it, `latest` gets the most recent run.
+    ///
+    /// # Errors
+    ///
+    /// Error returned if no run or more than one run matches or the run can't be read.
+    pub fn get(&self, id: &str) -> Result<HistoryRecord, DscError> {
+        let paths = self.get_record_paths()?;
+        let matches = if id == "latest" {
+            paths.last().into_iter().collect::<Vec<_>>()
+        } else {
+            paths.iter().filter(|path| path.file_stem().is_some_and(|stem| stem.to_string_lossy().starts_with(id))).collect()
+        };
+        match matches.as_slice() {
+            [path] => read_record(path),
+            [] => Err(DscError::History(format!("Run '{id}' not found"))),
+            _ => Err(DscError::History(format!("Run '{id}' is ambiguous, it matches {} runs", matches.len()))),
+        }
+    }
+
+    // record files are named by their identifier, so sorting by name sorts by time
+    fn get_record_paths(&self) -> Result<Vec<PathBuf>, DscError> {
+        if !self.directory.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut paths = fs::read_dir(&self.directory).map_err(|err| self.error(&err))?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == "json"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        Ok(paths)
+    }
+
+    fn prune(&self) -> Result<(), DscError> {
+        let paths = self.get_record_paths()?;
+        let excess = paths.len().saturating_sub(self.max_runs);
+        for (index, path) in paths.iter().enumerate() {
+            let expired = self.max_age.is_some_and(|max_age| {
+                fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+                    .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                    .is_some_and(|age| age > max_age)
+            });
+            if index < excess || expired {
+                trace!("Removing history record {path:?}");
+                fs::remove_file(path).map_err(|err| self.error(&err))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn error(&self, err: &std::io::Error) -> DscError {
+        DscError::History(format!("{}: {err}", self.directory.to_string_lossy()))
+    }
+}
+
+fn get_default_history_path() -> Result<PathBuf, DscError> {
+    let Some(directory) = get_user_state_directory() else {
+        return Err(DscError::History("Can't locate the history store".to_string()));
+    };
+    Ok(directory.join("history"))
+}
+
+fn read_record(path: &Path) -> Result<HistoryRecord, DscError> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn get_summary(record: HistoryRecord) -> HistorySummary {
+    let instances = get_instance_states(&record.result["results"]);
+    let in_desired_state = (record.operation == Operation::Test)
+        .then(|| instances.iter().all(|instance| instance.in_desired_state != Some(false)));
+    let changed = (record.operation == Operation::Set)
+        .then(|| record.result["results"].as_array().map_or(0, |results| results.iter().filter(|result| has_changes(&result["result"])).count()));
+    HistorySummary {
+        id: record.id,
+        timestamp: record.timestamp,
+        operation: record.operation,
+        execution_type: record.execution_type,
+        document_hash: record.document_hash,
+        had_errors: record.had_errors,
+        resources: instances.len(),
+        in_desired_state,
+        changed,
+    }
+}
+
+fn has_changes(result: &Value) -> bool {
+    match result {
+        Value::Array(results) => results.iter().any(|result| has_changes(&result["result"])),
+        _ => result["changedProperties"].as_array().is_some_and(|changed| !changed.is_empty()),
+    }
+}
+
+// the state of an instance in the result of a run
+struct InstanceState<'a> {
+    name: String,
+    resource_type: String,
+    state: Option<&'a Value>,
+    in_desired_state: Option<bool>,
+}
+
+// the instances of groups are listed with the name of the group as prefix
+fn get_instance_states(results: &Value) -> Vec<InstanceState<'_>> {
+    let mut instances = Vec::new();
+    add_instance_states("", results, &mut instances);
+    instances
+}
+
+fn add_instance_states<'a>(prefix: &str, results: &'a Value, instances: &mut Vec<InstanceState<'a>>) {
+    for item in results.as_array().into_iter().flatten() {
+        let name = format!("{prefix}{}", item["name"].as_str().unwrap_or_default());
+        let result = &item["result"];
+        if result.is_array() {
+            add_instance_states(&format!("{name}/"), result, instances);
+            continue;
+        }
+        instances.push(InstanceState {
+            name,
+            resource_type: item["type"].as_str().unwrap_or_default().to_string(),
+            state: result.get("actualState").or_else(|| result.get("afterState")),
+            in_desired_state: result.get("inDesiredState").and_then(Value::as_bool),
+        });
+    }
+}
+
+/// Compare the instances of two runs, matched by type and name.
+#[must_use]
+pub fn diff_runs(from: &HistoryRecord, to: &HistoryRecord) -> HistoryDiff {
+    let from_instances = get_instance_states(&from.result["results"]);
+    let to_instances = get_instance_states(&to.result["results"]);
+    let mut resources = Vec::new();
+    for from_instance in &from_instances {
+        let to_instance = to_instances.iter().find(|instance| instance.name == from_instance.name && instance.resource_type.eq_ignore_ascii_case(&from_instance.resource_type));
+        let Some(to_instance) = to_instance else {
+            resources.push(InstanceDiff {
+                name: from_instance.name.clone(),
+                resource_type: from_instance.resource_type.clone(),
+                status: InstanceDiffStatus::Removed,
+                from_in_desired_state: from_instance.in_desired_state,
+                to_in_desired_state: None,
+                changes: Vec::new(),
+            });
+            continue;
+        };
+        let changes = match (from_instance.state, to_instance.state) {
+            (Some(before), Some(after)) => get_changes(before, after, None),
+            _ => Vec::new(),
+        };
+        let status = if changes.is_empty() && from_instance.in_desired_state == to_instance.in_desired_state {
+            InstanceDiffStatus::Unchanged
+        } else {
+            InstanceDiffStatus::Changed
+        };
+        resources.push(InstanceDiff {
+            name: from_instance.name.clone(),
+            resource_type: from_instance.resource_type.clone(),
+            status,
+            from_in_desired_state: from_instance.in_desired_state,
+            to_in_desired_state: to_instance.in_desired_state,
+            changes,
+        });
+    }
+    for to_instance in &to_instances {
+        if !from_instances.iter().any(|instance| instance.name == to_instance.name && instance.resource_type.eq_ignore_ascii_case(&to_instance.resource_type)) {
+            resources.push(InstanceDiff {
+                name: to_instance.name.clone(),
+                resource_type: to_instance.resource_type.clone(),
+                status: InstanceDiffStatus::Added,
+                from_in_desired_state: None,
+                to_in_desired_state: to_instance.in_desired_state,
+                changes: Vec::new(),
+            });
+        }
+    }
+    HistoryDiff {
+        from: from.id.clone(),
+        to: to.id.clone(),
+        resources,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(id: &str, results: &Value) -> HistoryRecord {
+        HistoryRecord {
+            id: id.to_string(),
+            timestamp: String::new(),
+            operation: Operation::Test,
+            execution_type: ExecutionKind::Actual,
+            document_hash: String::new(),
+            had_errors: false,
+            result: json!({ "results": results }),
+        }
+    }
+
+    #[test]
+    fn diff_reports_drifted_instances() {
+        let from = record("1", &json!([
+            { "name": "Service", "type": "Test/Service", "result": { "actualState": { "state": "Running" }, "inDesiredState": true } },
+            { "name": "Removed", "type": "Test/Echo", "result": { "actualState": { "output": "a" }, "inDesiredState": true } },
+            { "name": "Group", "type": "Microsoft.DSC/Group", "result": [
+                { "name": "Nested", "type": "Test/Echo", "result": { "actualState": { "output": "b" }, "inDesiredState": true } },
+            ] },
+        ]));
+        let to = record("2", &json!([
+            { "name": "Service", "type": "Test/Service", "result": { "actualState": { "state": "Stopped" }, "inDesiredState": false } },
+            { "name": "Group", "type": "Microsoft.DSC/Group", "result": [
+                { "name": "Nested", "type": "Test/Echo", "result": { "actualState": { "output": "b" }, "inDesiredState": true } },
+            ] },
+            { "name": "Added", "type": "Test/Echo", "result": { "actualState": { "output": "c" }, "inDesiredState": true } },
+        ]));
+        let diff = diff_runs(&from, &to);
+        let statuses = diff.resources.iter().map(|resource| (resource.name.as_str(), resource.status.clone())).collect::<Vec<_>>();
+        assert_eq!(statuses, vec![
+            ("Service", InstanceDiffStatus::Changed),
+            ("Removed", InstanceDiffStatus::Removed),
+            ("Group/Nested", InstanceDiffStatus::Unchanged),
+            ("Added", InstanceDiffStatus::Added),
+        ]);
+        assert_eq!(diff.resources[0].changes.len(), 1);
+        assert_eq!(diff.resources[0].changes[0].path, "/state");
+        assert_eq!(diff.resources[0].to_in_desired_state, Some(false));
+    }
+
+    #[test]
+    fn store_keeps_max_runs() {
+        let directory = std::env::temp_dir().join(format!("dsc_history_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let store = HistoryStore { directory: directory.clone(), enabled: true, max_runs: 2, max_age: None };
+        let result = json!({ "results": [], "hadErrors": false, "metadata": { "Microsoft.DSC": { "executionType": "WhatIf" } } });
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            ids.push(store.add(Operation::Get, "document", &result, &Secrets::default()).unwrap().id);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let summaries = store.list().unwrap();
+        assert_eq!(summaries.iter().map(|summary| summary.id.clone()).collect::<Vec<_>>(), ids[1..].to_vec());
+        assert_eq!(summaries[0].execution_type, ExecutionKind::WhatIf);
+        assert_eq!(store.get("latest").unwrap().id, ids[2]);
+        assert!(store.get(&ids[0]).is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
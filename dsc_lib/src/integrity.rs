@@ -0,0 +1,215 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use base64::{Engine as _, engine::general_purpose};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, ffi::OsStr, fmt::Write, fs, path::{Path, PathBuf}};
+use tracing::{debug, warn};
+
+use crate::dscerror::DscError;
+use crate::dscresources::{manifest_lint::{find_executable, get_manifest_executables}, resource_manifest::{import_manifest, ResourceManifest}};
+use crate::package::get_file_checksum;
+use crate::settings::IntegritySettings;
+
+/// The extension added to the file name of a manifest for its detached signature.
+pub const SIGNATURE_FILE_EXTENSION: &str = "sig";
+
+// prefix of the signed message, changed if the format of the message ever changes
+const SIGNATURE_MESSAGE_HEADER: &str = "dsc-manifest-signature-v1";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum IntegrityMode {
+    /// Signatures aren't verified.
+    #[default]
+    Off,
+    /// Manifests without a valid signature are loaded with a warning.
+    Warn,
+    /// Manifests without a valid signature aren't loaded.
+    Enforce,
+}
+
+/// The detached signature of a manifest, stored next to it with the `.sig` extension added.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ManifestSignature {
+    /// The base64 encoded ed25519 public key of the signer.
+    pub public_key: String,
+    /// The SHA-256 of the manifest file.
+    pub manifest_sha256: String,
+    /// The SHA-256 of the executables of the manifest by the name used in the manifest.
+    pub executables: BTreeMap<String, String>,
+    /// The base64 encoded ed25519 signature over the hashes.
+    pub signature: String,
+}
+
+/// Get the path of the detached signature of a manifest.
+#[must_use]
+pub fn get_signature_path(manifest_path: &Path) -> PathBuf {
+    let mut file_name = manifest_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(SIGNATURE_FILE_EXTENSION);
+    manifest_path.with_file_name(file_name)
+}
+
+/// Verify the signature of a manifest and the hashes of its executables as configured by the settings.
+///
+/// In `warn` mode a failed verification is only logged.
+///
+/// # Arguments
+///
+/// * `path` - The path of the manifest file.
+/// * `content` - The content of the manifest file the manifest was read from.
+/// * `manifest` - The manifest read from the file.
+/// * `settings` - The integrity settings.
+///
+/// # Errors
+///
+/// Error returned in `enforce` mode if the manifest isn't signed by a trusted key, was modified,
+/// or an executable doesn't match its hash.
+pub fn verify_manifest_integrity(path: &Path, content: &[u8], manifest: &ResourceManifest, settings: &IntegritySettings) -> Result<(), DscError> {
+    let mode = settings.mode.unwrap_or_default();
+    if mode == IntegrityMode::Off {
+        return Ok(());
+    }
+
+    match verify_signature(path, content, manifest, settings.trusted_keys.as_deref().unwrap_or_default()) {
+        Ok(()) => {
+            debug!("Signature of manifest {path:?} verified");
+            Ok(())
+        },
+        Err(err) if mode == IntegrityMode::Warn => {
+            warn!("{err}");
+            Ok(())
+        },
+        Err(err) => Err(err),
+    }
+}
+
+fn verify_signature(path: &Path, content: &[u8], manifest: &ResourceManifest, trusted_keys: &[String]) -> Result<(), DscError> {
+    let integrity_error = |message: String| DscError::Integrity(path.to_string_lossy().to_string(), message);
+    let signature_path = get_signature_path(path);
+    if !signature_path.is_file() {
+        return Err(integrity_error(format!("Signature file {signature_path:?} not found")));
+    }
+    let signature_content = fs::read_to_string(&signature_path)?;
+    let signature: ManifestSignature = serde_json::from_str(&signature_content).map_err(|err| integrity_error(format!("Invalid signature file: {err}")))?;
+
+    // the signature is checked first so the hashes can be trusted
+    if !trusted_keys.iter().any(|key| key.trim() == signature.public_key) {
+        return Err(integrity_error(format!("Signing key '{}' is not trusted", signature.public_key)));
+    }
+    let verifying_key = VerifyingKey::from_bytes(&decode_key(&signature.public_key).map_err(integrity_error)?)
+        .map_err(|err| integrity_error(format!("Invalid public key: {err}")))?;
+    let signature_bytes = general_purpose::STANDARD.decode(&signature.signature).map_err(|err| integrity_error(format!("Invalid signature: {err}")))?;
+    let ed25519_signature = Signature::from_slice(&signature_bytes).map_err(|err| integrity_error(format!("Invalid signature: {err}")))?;
+    let message = get_signed_message(&signature.manifest_sha256, &signature.executables);
+    verifying_key.verify(message.as_bytes(), &ed25519_signature).map_err(|_| integrity_error("Signature doesn't match".to_string()))?;
+
+    if format!("{:x}", Sha256::digest(content)) != signature.manifest_sha256 {
+        return Err(integrity_error("Manifest was modified after it was signed".to_string()));
+    }
+    let directory = path.parent().unwrap_or(Path::new("."));
+    for (location, executable) in get_manifest_executables(manifest) {
+        let Some(expected) = signature.executables.get(executable) else {
+            return Err(integrity_error(format!("Executable '{executable}' at '{location}' is not signed")));
+        };
+        let Some(executable_path) = find_executable(executable, directory) else {
+            return Err(integrity_error(format!("Executable '{executable}' not found")));
+        };
+        if get_file_checksum(&executable_path)? != *expected {
+            return Err(integrity_error(format!("Executable '{executable}' at {executable_path:?} doesn't match its signed hash")));
+        }
+    }
+    Ok(())
+}
+
+/// Sign a manifest and the executables it resolves to, writing the detached signature next to it.
+///
+/// # Arguments
+///
+/// * `path` - The path of the manifest file.
+/// * `private_key` - The base64 encoded 32 byte ed25519 private key.
+///
+/// # Errors
+///
+/// Error returned if the manifest or the key is invalid, an executable isn't found, or the signature can't be written.
+pub fn sign_manifest(path: &Path, private_key: &str) -> Result<ManifestSignature, DscError> {
+    let integrity_error = |message: String| DscError::Integrity(path.to_string_lossy().to_string(), message);
+    let signing_key = SigningKey::from_bytes(&decode_key(private_key).map_err(integrity_error)?);
+    let content = fs::read(path)?;
+    let manifest: Value = if path.extension() == Some(OsStr::new("json")) {
+        serde_json::from_slice(&content)?
+    } else {
+        serde_yaml::from_slice(&content)?
+    };
+    let manifest = import_manifest(manifest)?;
+
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let mut executables = BTreeMap::new();
+    for (_, executable) in get_manifest_executables(&manifest) {
+        let Some(executable_path) = find_executable(executable, directory) else {
+            return Err(integrity_error(format!("Executable '{executable}' not found")));
+        };
+        debug!("Signing executable '{executable}' at {executable_path:?}");
+        executables.insert(executable.clone(), get_file_checksum(&executable_path)?);
+    }
+    let manifest_sha256 = format!("{:x}", Sha256::digest(&content));
+    let message = get_signed_message(&manifest_sha256, &executables);
+    let signature = ManifestSignature {
+        public_key: general_purpose::STANDARD.encode(signing_key.verifying_key().as_bytes()),
+        manifest_sha256,
+        executables,
+        signature: general_purpose::STANDARD.encode(signing_key.sign(message.as_bytes()).to_bytes()),
+    };
+
+    let signature_path = get_signature_path(path);
+    fs::write(&signature_path, serde_json::to_string_pretty(&signature)?)?;
+    debug!("Signature written to {signature_path:?}");
+    Ok(signature)
+}
+
+fn decode_key(key: &str) -> Result<[u8; 32], String> {
+    let bytes = general_purpose::STANDARD.decode(key.trim()).map_err(|err| format!("Invalid key: {err}"))?;
+    bytes.try_into().map_err(|bytes: Vec<u8>| format!("Invalid key: expected 32 bytes but got {}", bytes.len()))
+}
+
+// the hashes are signed instead of the files so executables on `PATH` can be verified where they are found
+fn get_signed_message(manifest_sha256: &str, executables: &BTreeMap<String, String>) -> String {
+    let mut message = format!("{SIGNATURE_MESSAGE_HEADER}\nmanifest {manifest_sha256}\n");
+    for (executable, sha256) in executables {
+        let _ = writeln!(message, "executable {executable} {sha256}");
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_path_appends_extension() {
+        assert_eq!(get_signature_path(Path::new("/resources/echo.dsc.resource.json")), PathBuf::from("/resources/echo.dsc.resource.json.sig"));
+    }
+
+    #[test]
+    fn signed_message_covers_executables() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let executables = BTreeMap::from([("dsctest".to_string(), "abc".to_string())]);
+        let signature = key.sign(get_signed_message("123", &executables).as_bytes());
+        let tampered = BTreeMap::from([("dsctest".to_string(), "abd".to_string())]);
+        assert!(key.verifying_key().verify(get_signed_message("123", &executables).as_bytes(), &signature).is_ok());
+        assert!(key.verifying_key().verify(get_signed_message("123", &tampered).as_bytes(), &signature).is_err());
+    }
+
+    #[test]
+    fn keys_must_be_32_bytes() {
+        assert!(decode_key(&general_purpose::STANDARD.encode([1u8; 32])).is_ok());
+        assert!(decode_key(&general_purpose::STANDARD.encode([1u8; 16])).is_err());
+        assert!(decode_key("not base64!").is_err());
+    }
+}
@@ -2,15 +2,24 @@
 // Licensed under the MIT License.
 
 use configure::config_doc::ExecutionKind;
+use discovery::diagnostics::DiscoveryDiagnostics;
 use dscerror::DscError;
 use dscresources::{dscresource::{DscResource, Invoke}, invoke_result::{GetResult, SetResult, TestResult}};
+use settings::DiscoverySettings;
 
+pub mod audit;
+pub mod checkpoint;
 pub mod configure;
 pub mod discovery;
 pub mod dscerror;
 pub mod dscresources;
 pub mod functions;
+pub mod history;
+pub mod integrity;
+pub mod package;
 pub mod parser;
+pub mod policy;
+pub mod settings;
 pub mod util;
 
 pub struct DscManager {
@@ -25,8 +34,22 @@ impl DscManager {
     /// This function will return an error if the underlying discovery fails.
     ///
     pub fn new() -> Result<Self, DscError> {
+        Self::with_discovery_settings(DiscoverySettings::default())
+    }
+
+    /// Create a new `DscManager` instance with discovery settings taking precedence over the ones of the settings files.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - The discovery settings, like the ones of the command line.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying discovery fails.
+    ///
+    pub fn with_discovery_settings(settings: DiscoverySettings) -> Result<Self, DscError> {
         Ok(Self {
-            discovery: discovery::Discovery::new()?,
+            discovery: discovery::Discovery::with_settings(settings)?,
         })
     }
 
@@ -48,6 +71,16 @@ impl DscManager {
     pub fn find_resources(&mut self, required_resource_types: &[String]) {
         self.discovery.find_resources(required_resource_types);
     }
+
+    /// Report what prevents resources from being found or used.
+    ///
+    /// # Arguments
+    ///
+    /// * `type_name_filter` - The filter for the type names of the duplicate resources to report.
+    /// * `adapter_name_filter` - The filter for the adapters to enumerate, all adapters if empty.
+    pub fn get_discovery_diagnostics(&mut self, type_name_filter: &str, adapter_name_filter: &str) -> DiscoveryDiagnostics {
+        self.discovery.get_diagnostics(type_name_filter, adapter_name_filter)
+    }
     /// Invoke the get operation on a resource.
     ///
     /// # Arguments
@@ -0,0 +1,561 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use flate2::read::GzDecoder;
+use regex::RegexBuilder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{collections::{BTreeMap, HashSet}, env, ffi::OsStr, fs::{self, File}, io::{self, BufReader, Read}, path::{Component, Path, PathBuf}, process};
+use tracing::{debug, info, trace, warn};
+
+use crate::discovery::convert_wildcard_to_regex;
+use crate::dscerror::DscError;
+use crate::dscresources::{manifest_lint::{is_manifest_file_name, lint_manifest_static, LintLevel, LintRule}, resource_manifest::validate_semver};
+
+/// The file with the metadata of a package, at the root of the package.
+pub const PACKAGE_METADATA_FILE: &str = "dsc.package.json";
+/// The file with the SHA-256 checksums of all the other files of a package, in the format of `sha256sum`.
+pub const PACKAGE_CHECKSUMS_FILE: &str = "checksums.sha256";
+
+const STAGING_PREFIX: &str = ".staging-";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum PackageScope {
+    /// Packages installed for the current user.
+    User,
+    /// Packages installed for all users of the machine.
+    System,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageMetadata {
+    /// The name of the package, used as directory name in the store.
+    pub name: String,
+    /// The semantic version of the package.
+    pub version: String,
+    /// The description of the package.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The author of the package.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledPackage {
+    /// The name of the package.
+    pub name: String,
+    /// The version of the package.
+    pub version: String,
+    /// Where the package is installed.
+    pub scope: PackageScope,
+    /// The directory of the package.
+    pub path: String,
+    /// The description of the package.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The types of the resources of the package.
+    pub resources: Vec<String>,
+}
+
+/// Get the directory of the store for packages of a scope.
+///
+/// On Windows these are `%LOCALAPPDATA%\dsc\resources` and `%ProgramData%\dsc\resources`, otherwise
+/// `$XDG_DATA_HOME/dsc/resources` or `~/.local/share/dsc/resources` and `/usr/local/share/dsc/resources`.
+///
+/// # Errors
+///
+/// Error returned if the environment variable locating the store isn't set.
+pub fn get_store_path(scope: PackageScope) -> Result<PathBuf, DscError> {
+    let directory = match scope {
+        PackageScope::User if cfg!(windows) => env::var_os("LOCALAPPDATA").map(PathBuf::from),
+        PackageScope::User => match env::var_os("XDG_DATA_HOME").filter(|value| !value.is_empty()) {
+            Some(data_home) => Some(PathBuf::from(data_home)),
+            None => env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share")),
+        },
+        PackageScope::System if cfg!(windows) => env::var_os("ProgramData").map(PathBuf::from),
+        PackageScope::System => Some(PathBuf::from("/usr/local/share")),
+    };
+    let Some(directory) = directory else {
+        return Err(DscError::Package(format!("Can't locate the {scope:?} package store")));
+    };
+    Ok(directory.join("dsc").join("resources"))
+}
+
+/// Get the directories of the installed packages, the ones of the user first.
+#[must_use]
+pub fn get_package_directories() -> Vec<PathBuf> {
+    get_installed_directories().into_iter().map(|(_, _, _, directory)| directory).collect()
+}
+
+// the scope, name, version, and directory of the installed packages, in version order for each name
+fn get_installed_directories() -> Vec<(PackageScope, String, String, PathBuf)> {
+    let mut directories = Vec::new();
+    for scope in [PackageScope::User, PackageScope::System] {
+        let Ok(store) = get_store_path(scope) else {
+            continue;
+        };
+        for (name, name_directory) in read_subdirectories(&store) {
+            for (version, version_directory) in read_subdirectories(&name_directory) {
+                directories.push((scope, name.clone(), version, version_directory));
+            }
+        }
+    }
+    directories
+}
+
+// subdirectories sorted by name, skipping hidden ones like the staging directories
+fn read_subdirectories(directory: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = directory.read_dir() else {
+        trace!("Package store directory {directory:?} not found");
+        return Vec::new();
+    };
+    let mut subdirectories = entries.filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().to_str().map(|name| (name.to_string(), entry.path())))
+        .filter(|(name, _)| !name.starts_with('.'))
+        .collect::<Vec<(String, PathBuf)>>();
+    subdirectories.sort();
+    subdirectories
+}
+
+/// List the installed packages.
+///
+/// # Arguments
+///
+/// * `name_filter` - The filter for the names of the packages, can have wildcards.
+///
+/// # Errors
+///
+/// Error returned if the filter is invalid or the resources of an installed package can't be read.
+pub fn list_installed_packages(name_filter: &str) -> Result<Vec<InstalledPackage>, DscError> {
+    let mut regex_builder = RegexBuilder::new(&convert_wildcard_to_regex(name_filter));
+    regex_builder.case_insensitive(true);
+    let Ok(regex) = regex_builder.build() else {
+        return Err(DscError::Operation("Could not build Regex filter for package name".to_string()));
+    };
+
+    let mut packages = Vec::new();
+    for (scope, name, version, directory) in get_installed_directories() {
+        if !regex.is_match(&name) {
+            continue;
+        }
+        let metadata = match read_metadata(&directory) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                warn!("Skipping package '{name}' version '{version}': {err}");
+                continue;
+            }
+        };
+        packages.push(get_installed_package(&metadata, scope, &directory)?);
+    }
+    Ok(packages)
+}
+
+/// Install a package into the store of a scope, next to the other versions of the package.
+///
+/// # Arguments
+///
+/// * `source` - The package as a directory, a `.zip`, `.tar`, `.tar.gz`, or `.tgz` archive.
+/// * `scope` - The store to install the package into.
+/// * `force` - Whether to replace the version of the package if it is already installed.
+///
+/// # Errors
+///
+/// Error returned if the package can't be read, fails verification, or is already installed.
+pub fn install_package(source: &Path, scope: PackageScope, force: bool) -> Result<InstalledPackage, DscError> {
+    let store = get_store_path(scope)?;
+    fs::create_dir_all(&store)?;
+    let staging = store.join(format!("{STAGING_PREFIX}{}", process::id()));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    let result = stage_and_install(source, &store, &staging, scope, force);
+    if staging.exists() {
+        if let Err(err) = fs::remove_dir_all(&staging) {
+            warn!("Failed to remove staging directory {staging:?}: {err}");
+        }
+    }
+    result
+}
+
+fn stage_and_install(source: &Path, store: &Path, staging: &Path, scope: PackageScope, force: bool) -> Result<InstalledPackage, DscError> {
+    info!("Installing package from {source:?}");
+    extract_package(source, staging)?;
+    let root = get_package_root(staging)?;
+    let metadata = verify_package(&root)?;
+
+    let destination = store.join(&metadata.name).join(&metadata.version);
+    if destination.exists() {
+        if !force {
+            return Err(DscError::Package(format!("Package '{}' version '{}' is already installed", metadata.name, metadata.version)));
+        }
+        info!("Replacing package '{}' version '{}'", metadata.name, metadata.version);
+        fs::remove_dir_all(&destination)?;
+    }
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // the staging directory is in the store so the package is moved into place at once
+    fs::rename(&root, &destination)?;
+    info!("Installed package '{}' version '{}' to {destination:?}", metadata.name, metadata.version);
+    get_installed_package(&metadata, scope, &destination)
+}
+
+/// Uninstall versions of a package from the store of a scope.
+///
+/// # Arguments
+///
+/// * `name` - The name of the package.
+/// * `version` - The version to uninstall, all versions if not specified.
+/// * `scope` - The store the package is installed in.
+///
+/// # Returns
+///
+/// The uninstalled packages.
+///
+/// # Errors
+///
+/// Error returned if the package isn't installed or can't be removed.
+pub fn uninstall_package(name: &str, version: Option<&str>, scope: PackageScope) -> Result<Vec<InstalledPackage>, DscError> {
+    let mut uninstalled = Vec::new();
+    for (installed_scope, installed_name, installed_version, directory) in get_installed_directories() {
+        if installed_scope != scope || !installed_name.eq_ignore_ascii_case(name) || version.is_some_and(|version| version != installed_version) {
+            continue;
+        }
+        let package = match read_metadata(&directory) {
+            Ok(metadata) => get_installed_package(&metadata, scope, &directory)?,
+            // packages with damaged metadata can still be removed
+            Err(_) => InstalledPackage {
+                name: installed_name.clone(),
+                version: installed_version.clone(),
+                scope,
+                path: directory.to_string_lossy().to_string(),
+                description: None,
+                resources: Vec::new(),
+            },
+        };
+        info!("Uninstalling package '{installed_name}' version '{installed_version}' from {directory:?}");
+        fs::remove_dir_all(&directory)?;
+        if let Some(name_directory) = directory.parent() {
+            // the directory of the name is only removed once no version is left
+            if name_directory.read_dir().is_ok_and(|mut entries| entries.next().is_none()) {
+                fs::remove_dir(name_directory)?;
+            }
+        }
+        uninstalled.push(package);
+    }
+
+    if uninstalled.is_empty() {
+        return Err(DscError::Package(match version {
+            Some(version) => format!("Package '{name}' version '{version}' is not installed"),
+            None => format!("Package '{name}' is not installed"),
+        }));
+    }
+    Ok(uninstalled)
+}
+
+fn extract_package(source: &Path, staging: &Path) -> Result<(), DscError> {
+    if source.is_dir() {
+        debug!("Copying package directory {source:?}");
+        return copy_directory(source, staging);
+    }
+    if !source.is_file() {
+        return Err(DscError::Package(format!("Package {source:?} not found")));
+    }
+
+    let extension = source.extension().unwrap_or_default();
+    let file = File::open(source)?;
+    fs::create_dir_all(staging)?;
+    if extension.eq_ignore_ascii_case("zip") {
+        debug!("Extracting zip package {source:?}");
+        extract_zip(file, staging)
+    } else if extension.eq_ignore_ascii_case("tgz") || (extension.eq_ignore_ascii_case("gz") && is_tar(source.file_stem())) {
+        debug!("Extracting gzipped tar package {source:?}");
+        extract_tar(GzDecoder::new(BufReader::new(file)), staging).map_err(|err| DscError::Package(format!("Failed to extract {source:?}: {err}")))
+    } else if extension.eq_ignore_ascii_case("tar") {
+        debug!("Extracting tar package {source:?}");
+        extract_tar(BufReader::new(file), staging).map_err(|err| DscError::Package(format!("Failed to extract {source:?}: {err}")))
+    } else {
+        Err(DscError::Package(format!("Package {source:?} must be a directory or a .zip, .tar, .tar.gz, or .tgz archive")))
+    }
+}
+
+fn is_tar(file_stem: Option<&OsStr>) -> bool {
+    file_stem.map(Path::new).and_then(Path::extension).is_some_and(|extension| extension.eq_ignore_ascii_case("tar"))
+}
+
+// links could point outside of the package, so they are rejected like the ones of package directories
+fn extract_tar<R: Read>(reader: R, staging: &Path) -> Result<(), DscError> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            return Err(DscError::Package(format!("Package contains link {:?}", entry.path()?)));
+        }
+        // entries with absolute paths or parent components would be extracted outside of the staging directory
+        if !entry.unpack_in(staging)? {
+            return Err(DscError::Package(format!("Tar entry {:?} has an unsafe path", entry.path()?)));
+        }
+    }
+    Ok(())
+}
+
+fn extract_zip(file: File, staging: &Path) -> Result<(), DscError> {
+    let mut archive = zip::ZipArchive::new(BufReader::new(file)).map_err(|err| DscError::Package(format!("Invalid zip archive: {err}")))?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(|err| DscError::Package(format!("Invalid zip archive: {err}")))?;
+        // entries with absolute paths or parent components would be extracted outside of the staging directory
+        let Some(relative_path) = entry.enclosed_name() else {
+            return Err(DscError::Package(format!("Zip entry '{}' has an unsafe path", entry.name())));
+        };
+        let path = staging.join(relative_path);
+        if entry.is_dir() {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut output = File::create(&path)?;
+        io::copy(&mut entry, &mut output)?;
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))?;
+        }
+    }
+    Ok(())
+}
+
+fn copy_directory(source: &Path, destination: &Path) -> Result<(), DscError> {
+    fs::create_dir_all(destination)?;
+    for entry in source.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        let target = destination.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            return Err(DscError::Package(format!("Package contains symbolic link {path:?}")));
+        } else if file_type.is_dir() {
+            copy_directory(&path, &target)?;
+        } else {
+            fs::copy(&path, &target)?;
+        }
+    }
+    Ok(())
+}
+
+// archives can wrap the package in a single directory
+fn get_package_root(staging: &Path) -> Result<PathBuf, DscError> {
+    if staging.join(PACKAGE_METADATA_FILE).is_file() {
+        return Ok(staging.to_path_buf());
+    }
+    let entries = staging.read_dir()?.filter_map(Result::ok).map(|entry| entry.path()).collect::<Vec<PathBuf>>();
+    if let [directory] = entries.as_slice() {
+        if directory.is_dir() && directory.join(PACKAGE_METADATA_FILE).is_file() {
+            return Ok(directory.clone());
+        }
+    }
+    Err(DscError::Package(format!("Package doesn't contain '{PACKAGE_METADATA_FILE}'")))
+}
+
+fn read_metadata(root: &Path) -> Result<PackageMetadata, DscError> {
+    let path = root.join(PACKAGE_METADATA_FILE);
+    let content = fs::read_to_string(&path).map_err(|err| DscError::Package(format!("Failed to read '{PACKAGE_METADATA_FILE}': {err}")))?;
+    serde_json::from_str(&content).map_err(|err| DscError::Package(format!("Invalid '{PACKAGE_METADATA_FILE}': {err}")))
+}
+
+/// Verify the metadata, checksums, and resource manifests of an extracted package.
+///
+/// # Arguments
+///
+/// * `root` - The directory of the package.
+///
+/// # Returns
+///
+/// The metadata of the package.
+///
+/// # Errors
+///
+/// Error returned if the package is not valid.
+pub fn verify_package(root: &Path) -> Result<PackageMetadata, DscError> {
+    let metadata = read_metadata(root)?;
+    let name_is_valid = metadata.name.chars().next().is_some_and(char::is_alphanumeric)
+        && metadata.name.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '-' || c == '_');
+    if !name_is_valid {
+        return Err(DscError::Package(format!("Package name '{}' must only contain letters, digits, '.', '-', and '_'", metadata.name)));
+    }
+    if let Err(err) = validate_semver(&metadata.version) {
+        return Err(DscError::Package(format!("Package version '{}' is not a semantic version: {err}", metadata.version)));
+    }
+
+    let checksums = read_checksums(root)?;
+    let mut files = Vec::new();
+    list_files(root, root, &mut files)?;
+    let files = files.into_iter().filter(|file| file != PACKAGE_CHECKSUMS_FILE).collect::<Vec<String>>();
+    for file in &files {
+        let Some(expected) = checksums.get(file) else {
+            return Err(DscError::Package(format!("File '{file}' is not listed in '{PACKAGE_CHECKSUMS_FILE}'")));
+        };
+        let actual = get_file_checksum(&root.join(file))?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(DscError::Package(format!("Checksum of file '{file}' doesn't match, expected '{expected}' but got '{actual}'")));
+        }
+        trace!("Checksum of '{file}' verified");
+    }
+    let files = files.iter().collect::<HashSet<&String>>();
+    if let Some(missing) = checksums.keys().find(|file| !files.contains(file)) {
+        return Err(DscError::Package(format!("File '{missing}' listed in '{PACKAGE_CHECKSUMS_FILE}' is missing")));
+    }
+
+    let manifests = files.iter().filter(|file| file.rsplit('/').next().is_some_and(is_manifest_file_name)).collect::<Vec<_>>();
+    if manifests.is_empty() {
+        return Err(DscError::Package("Package doesn't contain resource manifests".to_string()));
+    }
+    for manifest in manifests {
+        verify_manifest(&root.join(manifest.as_str()))?;
+    }
+    Ok(metadata)
+}
+
+// commands of the package aren't run before it is installed, and problems of the environment,
+// like a missing interpreter, don't prevent installing it
+fn verify_manifest(path: &Path) -> Result<(), DscError> {
+    let result = lint_manifest_static(path);
+    for diagnostic in &result.diagnostics {
+        let blocking = diagnostic.level == LintLevel::Error && diagnostic.rule != LintRule::Executable;
+        if blocking {
+            return Err(DscError::Package(format!("Invalid manifest {path:?}: {}", diagnostic.message)));
+        }
+        warn!("Manifest {path:?}: {}", diagnostic.message);
+    }
+    Ok(())
+}
+
+fn read_checksums(root: &Path) -> Result<BTreeMap<String, String>, DscError> {
+    let content = fs::read_to_string(root.join(PACKAGE_CHECKSUMS_FILE)).map_err(|err| DscError::Package(format!("Failed to read '{PACKAGE_CHECKSUMS_FILE}': {err}")))?;
+    let mut checksums = BTreeMap::new();
+    for line in content.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        // `sha256sum` separates the checksum and the path with a space and a space or `*` for binary mode
+        let Some((checksum, file)) = line.split_once(char::is_whitespace) else {
+            return Err(DscError::Package(format!("Invalid line in '{PACKAGE_CHECKSUMS_FILE}': {line}")));
+        };
+        let file = file.trim_start().trim_start_matches('*').replace('\\', "/");
+        let file = file.strip_prefix("./").unwrap_or(&file).to_string();
+        if !is_relative_path(&file) {
+            return Err(DscError::Package(format!("Path '{file}' in '{PACKAGE_CHECKSUMS_FILE}' must be relative to the package")));
+        }
+        checksums.insert(file, checksum.to_string());
+    }
+    Ok(checksums)
+}
+
+fn is_relative_path(path: &str) -> bool {
+    !path.is_empty() && Path::new(path).components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+// relative paths of the files with `/` as separator
+fn list_files(root: &Path, directory: &Path, files: &mut Vec<String>) -> Result<(), DscError> {
+    for entry in directory.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            return Err(DscError::Package(format!("Package contains symbolic link {path:?}")));
+        } else if file_type.is_dir() {
+            list_files(root, &path, files)?;
+        } else if let Ok(relative_path) = path.strip_prefix(root) {
+            files.push(relative_path.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn get_file_checksum(path: &Path) -> Result<String, DscError> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn get_installed_package(metadata: &PackageMetadata, scope: PackageScope, directory: &Path) -> Result<InstalledPackage, DscError> {
+    let mut files = Vec::new();
+    list_files(directory, directory, &mut files)?;
+    let mut resources = Vec::new();
+    for file in files.iter().filter(|file| file.rsplit('/').next().is_some_and(is_manifest_file_name)) {
+        if let Some(resource_type) = read_manifest_type(&directory.join(file)) {
+            resources.push(resource_type);
+        }
+    }
+    resources.sort();
+    Ok(InstalledPackage {
+        name: metadata.name.clone(),
+        version: metadata.version.clone(),
+        scope,
+        path: directory.to_string_lossy().to_string(),
+        description: metadata.description.clone(),
+        resources,
+    })
+}
+
+fn read_manifest_type(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let manifest: Value = if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json")) {
+        serde_json::from_str(&content).ok()?
+    } else {
+        serde_yaml::from_str(&content).ok()?
+    };
+    manifest.get("type")?.as_str().map(ToString::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_paths_must_stay_in_package() {
+        assert!(is_relative_path("bin/resource"));
+        assert!(!is_relative_path("../resource"));
+        assert!(!is_relative_path("/etc/passwd"));
+        assert!(!is_relative_path(""));
+    }
+
+    #[test]
+    fn checksum_matches_sha256sum() {
+        let directory = env::temp_dir().join(format!("dsc-package-test-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("file.txt");
+        fs::write(&path, "hello\n").unwrap();
+        let checksum = get_file_checksum(&path).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(checksum, "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03");
+    }
+
+    #[test]
+    fn tar_links_are_rejected() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder.append_link(&mut header, "resource", "/etc/passwd").unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let staging = env::temp_dir().join(format!("dsc-package-link-test-{}", process::id()));
+        let result = extract_tar(archive.as_slice(), &staging);
+        let _ = fs::remove_dir_all(&staging);
+        assert!(matches!(result, Err(DscError::Package(message)) if message.contains("link")));
+    }
+
+    #[test]
+    fn archive_extensions_are_case_insensitive() {
+        assert!(is_tar(Path::new("package.TAR.GZ").file_stem()));
+        assert!(!is_tar(Path::new("package.gz").file_stem()));
+    }
+}
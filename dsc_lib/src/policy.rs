@@ -0,0 +1,268 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use regex::{Regex, RegexBuilder};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{env, fs, path::{Path, PathBuf}, sync::OnceLock};
+use tracing::{debug, trace};
+
+use crate::discovery::convert_wildcard_to_regex;
+use crate::dscerror::DscError;
+use crate::dscresources::{dscresource::DscResource, manifest_lint::{find_executable, get_manifest_executables}, resource_manifest::{import_manifest, Kind}};
+use crate::settings::get_system_settings_directory;
+
+/// The name of the execution policy file in the system settings directory.
+pub const POLICY_FILE_NAME: &str = "dsc.policy.json";
+/// The environment variable with the path of an additional policy file, which can only add restrictions.
+pub const POLICY_ENV_VAR: &str = "DSC_POLICY_PATH";
+
+/// Restrictions on the resources a configuration may use and the executables they may launch.
+///
+/// Type patterns are case-insensitive wildcards. Executable patterns containing a path separator match
+/// the canonical path of the executable that is launched, others match the file name of that path with or without extension.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ExecutionPolicy {
+    /// Resource types allowed to be invoked, all types are allowed if not specified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_resources: Option<Vec<String>>,
+    /// Resource types not allowed to be invoked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deny_resources: Option<Vec<String>>,
+    /// Resource types not allowed to be changed with `set` or `delete`, what-if is still allowed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deny_set: Option<Vec<String>>,
+    /// Whether adapters and the resources they adapt are allowed, defaults to `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_adapters: Option<bool>,
+    /// Executables manifests are allowed to launch, all executables are allowed if not specified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_executables: Option<Vec<String>>,
+    /// Executables manifests are not allowed to launch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deny_executables: Option<Vec<String>>,
+}
+
+// a policy with its wildcard patterns compiled
+struct CompiledPolicy {
+    source: String,
+    allow_resources: Option<Vec<Regex>>,
+    deny_resources: Vec<Regex>,
+    deny_set: Vec<Regex>,
+    allow_adapters: bool,
+    allow_executables: Option<Vec<ExecutablePattern>>,
+    deny_executables: Vec<ExecutablePattern>,
+}
+
+struct ExecutablePattern {
+    regex: Regex,
+    match_path: bool,
+}
+
+/// Get the paths of the policy files, the machine-level one and the one of `DSC_POLICY_PATH`.
+#[must_use]
+pub fn get_policy_paths() -> Vec<PathBuf> {
+    let mut paths = get_system_settings_directory().into_iter().map(|directory| directory.join(POLICY_FILE_NAME)).collect::<Vec<_>>();
+    if let Some(path) = env::var_os(POLICY_ENV_VAR).filter(|value| !value.is_empty()) {
+        paths.push(PathBuf::from(path));
+    }
+    paths
+}
+
+// policies are read once per process, every policy found has to allow an operation
+fn get_policies() -> Result<&'static [CompiledPolicy], DscError> {
+    static POLICIES: OnceLock<Result<Vec<CompiledPolicy>, (String, String)>> = OnceLock::new();
+    match POLICIES.get_or_init(load_policies) {
+        Ok(policies) => Ok(policies),
+        Err((path, message)) => Err(DscError::Settings(path.clone(), message.clone())),
+    }
+}
+
+fn load_policies() -> Result<Vec<CompiledPolicy>, (String, String)> {
+    let mut policies = Vec::new();
+    for path in get_policy_paths() {
+        if !path.is_file() {
+            trace!("Policy file {path:?} not found");
+            continue;
+        }
+        debug!("Reading execution policy {path:?}");
+        let source = path.to_string_lossy().to_string();
+        let content = fs::read_to_string(&path).map_err(|err| (source.clone(), err.to_string()))?;
+        let policy: ExecutionPolicy = serde_json::from_str(&content).map_err(|err| (source.clone(), err.to_string()))?;
+        policies.push(compile_policy(source.clone(), &policy).map_err(|err| (source, err.to_string()))?);
+    }
+    Ok(policies)
+}
+
+fn compile_policy(source: String, policy: &ExecutionPolicy) -> Result<CompiledPolicy, DscError> {
+    let compile_types = |patterns: &Vec<String>| -> Result<Vec<Regex>, DscError> {
+        patterns.iter().map(|pattern| {
+            // resource types are case-insensitive
+            let Ok(regex) = RegexBuilder::new(&convert_wildcard_to_regex(pattern)).case_insensitive(true).build() else {
+                return Err(DscError::Operation(format!("Could not build Regex for resource pattern '{pattern}'")));
+            };
+            Ok(regex)
+        }).collect()
+    };
+    let compile_executables = |patterns: &Vec<String>| -> Result<Vec<ExecutablePattern>, DscError> {
+        patterns.iter().map(|pattern| {
+            let normalized = pattern.replace('\\', "/");
+            let regex_str = format!("^{}$", regex::escape(&normalized).replace("\\*", ".*").replace("\\?", "."));
+            // paths are case-insensitive on Windows
+            let Ok(regex) = RegexBuilder::new(&regex_str).case_insensitive(cfg!(windows)).build() else {
+                return Err(DscError::Operation(format!("Could not build Regex for executable pattern '{pattern}'")));
+            };
+            Ok(ExecutablePattern { regex, match_path: normalized.contains('/') })
+        }).collect()
+    };
+    Ok(CompiledPolicy {
+        source,
+        allow_resources: policy.allow_resources.as_ref().map(compile_types).transpose()?,
+        deny_resources: policy.deny_resources.as_ref().map(compile_types).transpose()?.unwrap_or_default(),
+        deny_set: policy.deny_set.as_ref().map(compile_types).transpose()?.unwrap_or_default(),
+        allow_adapters: policy.allow_adapters != Some(false),
+        allow_executables: policy.allow_executables.as_ref().map(compile_executables).transpose()?,
+        deny_executables: policy.deny_executables.as_ref().map(compile_executables).transpose()?.unwrap_or_default(),
+    })
+}
+
+/// Check that the execution policy allows a resource to be invoked.
+///
+/// The resource type, whether it is or requires an adapter, and the executables of its manifest are checked.
+///
+/// # Errors
+///
+/// Error returned if the policy doesn't allow the resource or can't be read.
+pub fn check_resource_policy(resource: &DscResource) -> Result<(), DscError> {
+    let policies = get_policies()?;
+    for policy in policies {
+        check_resource_type(policy, &resource.type_name)?;
+        if !policy.allow_adapters && (resource.kind == Kind::Adapter || resource.require_adapter.is_some()) {
+            return Err(DscError::ExecutionPolicy(format!("Resource '{}' uses an adapter, which is not allowed by {}", resource.type_name, policy.source)));
+        }
+    }
+    // adapted resources run the executables of their adapter
+    if let Some(adapter) = &resource.adapter {
+        check_resource_policy(adapter)?;
+    }
+    if let Some(manifest) = &resource.manifest {
+        if policies.iter().any(|policy| policy.allow_executables.is_some() || !policy.deny_executables.is_empty()) {
+            let manifest = import_manifest(manifest.clone())?;
+            for (_, executable) in get_manifest_executables(&manifest) {
+                check_executable_policy(executable, find_executable(executable, Path::new(&resource.directory)).as_deref())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_resource_type(policy: &CompiledPolicy, type_name: &str) -> Result<(), DscError> {
+    if let Some(allow) = &policy.allow_resources {
+        if !allow.iter().any(|regex| regex.is_match(type_name)) {
+            return Err(DscError::ExecutionPolicy(format!("Resource '{type_name}' is not allowed by {}", policy.source)));
+        }
+    }
+    if policy.deny_resources.iter().any(|regex| regex.is_match(type_name)) {
+        return Err(DscError::ExecutionPolicy(format!("Resource '{type_name}' is denied by {}", policy.source)));
+    }
+    Ok(())
+}
+
+/// Check that the execution policy allows a resource to be changed with `set` or `delete`.
+///
+/// # Errors
+///
+/// Error returned if the policy denies changing the resource or can't be read.
+pub fn check_set_policy(type_name: &str) -> Result<(), DscError> {
+    for policy in get_policies()? {
+        if policy.deny_set.iter().any(|regex| regex.is_match(type_name)) {
+            return Err(DscError::ExecutionPolicy(format!("Changing resource '{type_name}' is denied by {}", policy.source)));
+        }
+    }
+    Ok(())
+}
+
+/// Check that the execution policy allows an executable to be launched.
+///
+/// # Arguments
+///
+/// * `executable` - The executable as written in the manifest.
+/// * `path` - The path the executable is launched from, `None` if it isn't found.
+///
+/// # Errors
+///
+/// Error returned if the policy doesn't allow the executable, the executable isn't found while executables are
+/// restricted, or the policy can't be read.
+pub fn check_executable_policy(executable: &str, path: Option<&Path>) -> Result<(), DscError> {
+    let policies = get_policies()?;
+    if policies.iter().all(|policy| policy.allow_executables.is_none() && policy.deny_executables.is_empty()) {
+        return Ok(());
+    }
+
+    // the canonical path is matched so a link with an allowed name can't launch another executable
+    let Some(canonical) = path.and_then(|path| fs::canonicalize(path).ok()) else {
+        return Err(DscError::ExecutionPolicy(format!("Executable '{executable}' not found, so the execution policy can't be checked")));
+    };
+    debug!("Checking executable '{executable}' resolved to {canonical:?}");
+    for policy in policies {
+        check_executable(policy, executable, &canonical)?;
+    }
+    Ok(())
+}
+
+fn check_executable(policy: &CompiledPolicy, executable: &str, canonical: &Path) -> Result<(), DscError> {
+    let path = canonical.to_string_lossy().replace('\\', "/");
+    // canonical paths are verbatim paths on Windows
+    let path = path.strip_prefix("//?/").unwrap_or(&path);
+    let names = [canonical.file_name(), canonical.file_stem()].into_iter().flatten().map(|name| name.to_string_lossy().to_string()).collect::<Vec<_>>();
+    let is_match = |pattern: &ExecutablePattern| {
+        if pattern.match_path {
+            pattern.regex.is_match(path)
+        } else {
+            names.iter().any(|name| pattern.regex.is_match(name))
+        }
+    };
+
+    if let Some(allow) = &policy.allow_executables {
+        if !allow.iter().any(is_match) {
+            return Err(DscError::ExecutionPolicy(format!("Executable '{executable}' is not allowed by {}", policy.source)));
+        }
+    }
+    if policy.deny_executables.iter().any(is_match) {
+        return Err(DscError::ExecutionPolicy(format!("Executable '{executable}' is denied by {}", policy.source)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(policy: &str) -> CompiledPolicy {
+        compile_policy("test".to_string(), &serde_json::from_str(policy).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn resource_types_match_case_insensitive_wildcards() {
+        let policy = compile(r#"{"allowResources": ["Microsoft.DSC/*", "Test/*"], "denyResources": ["test/sleep"]}"#);
+        assert!(check_resource_type(&policy, "Test/Echo").is_ok());
+        assert!(check_resource_type(&policy, "microsoft.dsc/Group").is_ok());
+        assert!(check_resource_type(&policy, "Test/Sleep").is_err());
+        assert!(check_resource_type(&policy, "Other/Echo").is_err());
+    }
+
+    #[test]
+    fn unknown_policy_fields_are_rejected() {
+        assert!(serde_json::from_str::<ExecutionPolicy>(r#"{"allowTypes": ["*"]}"#).is_err());
+    }
+
+    #[test]
+    fn executables_match_canonical_path() {
+        let policy = compile(r#"{"allowExecutables": ["dscecho"], "denyExecutables": ["/usr/bin/*"]}"#);
+        assert!(check_executable(&policy, "echo", Path::new("/opt/dsc/dscecho")).is_ok());
+        // the name written in the manifest doesn't matter
+        assert!(check_executable(&policy, "dscecho", Path::new("/opt/dsc/bash")).is_err());
+        assert!(check_executable(&policy, "dscecho", Path::new("/usr/bin/dscecho")).is_err());
+    }
+}
@@ -0,0 +1,418 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{env, fs, path::{Path, PathBuf}};
+use tracing::{debug, trace, warn};
+
+use crate::dscerror::DscError;
+use crate::integrity::IntegrityMode;
+
+/// The name of the settings file in the system and user settings directories.
+pub const SETTINGS_FILE_NAME: &str = "dsc.settings.json";
+/// The environment variable with settings overriding the ones of the files as JSON.
+/// Nested `dsc` processes, like the ones of group resources, inherit it so they use the same settings.
+pub const SETTINGS_ENV_VAR: &str = "DSC_SETTINGS";
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DscSettings {
+    /// What the agent applies and how it keeps it applied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent: Option<AgentSettings>,
+    /// Where the changes made by `set` and `delete` are recorded, the user settings are ignored when the system settings configure a path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audit: Option<AuditSettings>,
+    /// Where the progress of interrupted configuration runs is kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<CheckpointSettings>,
+    /// Where resources are discovered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discovery: Option<DiscoverySettings>,
+    /// Where the results of configuration runs are kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history: Option<HistorySettings>,
+    /// How signatures of resource manifests are verified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<IntegritySettings>,
+    /// How resources requiring another security context than the one of the process are run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_context: Option<SecurityContextSettings>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AgentSettings {
+    /// The path of the configuration document applied by the agent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document_path: Option<String>,
+    /// The path of the JSON or YAML file with the parameters of the document.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters_path: Option<String>,
+    /// What the agent does after the document is applied, defaults to `ApplyAndMonitor`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<AgentMode>,
+    /// The seconds between runs, at least 1 and defaults to 1800.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval_seconds: Option<u64>,
+    /// The maximum of the random seconds added to the interval so agents of many machines don't run at once, defaults to 0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jitter_seconds: Option<u64>,
+    /// The directory of the lock and the state of the agent, defaults to `dsc/agent` in the local state directory of the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_path: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub enum AgentMode {
+    /// The document is applied when it changes, instances aren't tested afterwards.
+    ApplyOnly,
+    /// The document is applied when it changes and drift from it is reported.
+    #[default]
+    ApplyAndMonitor,
+    /// The document is applied when it changes and drift from it is corrected.
+    ApplyAndAutoCorrect,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AuditSettings {
+    /// The path of the JSON Lines audit log, changes aren't recorded if not specified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The size in bytes the log is rotated at, defaults to 10 MiB.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    /// How many rotated logs are kept, defaults to 5.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CheckpointSettings {
+    /// The directory of the checkpoints, defaults to `dsc/checkpoints` in the local state directory of the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DiscoverySettings {
+    /// Directories searched for resource manifests before the ones of `PATH` or `DSC_RESOURCE_PATH`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_paths: Option<Vec<String>>,
+    /// Whether subdirectories of the search paths and `DSC_RESOURCE_PATH` are searched, directories of `PATH` never are.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurse: Option<bool>,
+    /// How many levels of subdirectories are searched when recursing, defaults to 3.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    /// Wildcard patterns of files and directories not searched, patterns without a path separator match names.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<Vec<String>>,
+    /// Whether the directories of `PATH` are searched when `DSC_RESOURCE_PATH` isn't set, defaults to `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan_path: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct HistorySettings {
+    /// Whether the results of `config get`, `test` and `set` are recorded, defaults to `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// The directory of the history, defaults to `dsc/history` in the local state directory of the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// How many runs are kept, defaults to 100.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_runs: Option<usize>,
+    /// How many days runs are kept, runs are kept regardless of their age if not specified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct IntegritySettings {
+    /// Whether manifests without a valid signature are loaded, defaults to `off`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<IntegrityMode>,
+    /// Base64 encoded ed25519 public keys trusted to sign manifests, only read from the system settings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trusted_keys: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SecurityContextSettings {
+    /// Command prefixed to the commands of resources requiring elevation when not elevated, like `["sudo", "-n"]`.
+    /// The command has to preserve the environment variables the resources need.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elevate_with: Option<Vec<String>>,
+    /// Command prefixed to the commands of resources requiring a restricted context when elevated, like `["sudo", "-n", "-u", "nobody"]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restrict_with: Option<Vec<String>>,
+}
+
+impl DscSettings {
+    /// Merge settings, the values of `overrides` take precedence.
+    #[must_use]
+    pub fn merge(self, overrides: DscSettings) -> DscSettings {
+        let agent = match (self.agent, overrides.agent) {
+            (Some(agent), Some(overrides)) => Some(AgentSettings {
+                document_path: overrides.document_path.or(agent.document_path),
+                parameters_path: overrides.parameters_path.or(agent.parameters_path),
+                mode: overrides.mode.or(agent.mode),
+                interval_seconds: overrides.interval_seconds.or(agent.interval_seconds),
+                jitter_seconds: overrides.jitter_seconds.or(agent.jitter_seconds),
+                state_path: overrides.state_path.or(agent.state_path),
+            }),
+            (agent, overrides) => overrides.or(agent),
+        };
+        let audit = match (self.audit, overrides.audit) {
+            (Some(audit), Some(overrides)) => Some(AuditSettings {
+                path: overrides.path.or(audit.path),
+                max_size: overrides.max_size.or(audit.max_size),
+                max_files: overrides.max_files.or(audit.max_files),
+            }),
+            (audit, overrides) => overrides.or(audit),
+        };
+        let checkpoint = match (self.checkpoint, overrides.checkpoint) {
+            (Some(checkpoint), Some(overrides)) => Some(CheckpointSettings {
+                path: overrides.path.or(checkpoint.path),
+            }),
+            (checkpoint, overrides) => overrides.or(checkpoint),
+        };
+        let discovery = match (self.discovery, overrides.discovery) {
+            (Some(discovery), Some(overrides)) => Some(discovery.merge(overrides)),
+            (discovery, overrides) => overrides.or(discovery),
+        };
+        let history = match (self.history, overrides.history) {
+            (Some(history), Some(overrides)) => Some(HistorySettings {
+                enabled: overrides.enabled.or(history.enabled),
+                path: overrides.path.or(history.path),
+                max_runs: overrides.max_runs.or(history.max_runs),
+                max_age_days: overrides.max_age_days.or(history.max_age_days),
+            }),
+            (history, overrides) => overrides.or(history),
+        };
+        let integrity = match (self.integrity, overrides.integrity) {
+            (Some(integrity), Some(overrides)) => Some(integrity.merge(overrides)),
+            (integrity, overrides) => overrides.or(integrity),
+        };
+        let security_context = match (self.security_context, overrides.security_context) {
+            (Some(security_context), Some(overrides)) => Some(SecurityContextSettings {
+                elevate_with: overrides.elevate_with.or(security_context.elevate_with),
+                restrict_with: overrides.restrict_with.or(security_context.restrict_with),
+            }),
+            (security_context, overrides) => overrides.or(security_context),
+        };
+        DscSettings { agent, audit, checkpoint, discovery, history, integrity, security_context }
+    }
+}
+
+impl DiscoverySettings {
+    /// Merge settings, the values of `overrides` take precedence and their paths and patterns come first.
+    #[must_use]
+    pub fn merge(self, overrides: DiscoverySettings) -> DiscoverySettings {
+        DiscoverySettings {
+            search_paths: concat(overrides.search_paths, self.search_paths),
+            recurse: overrides.recurse.or(self.recurse),
+            max_depth: overrides.max_depth.or(self.max_depth),
+            exclude: concat(overrides.exclude, self.exclude),
+            scan_path: overrides.scan_path.or(self.scan_path),
+        }
+    }
+}
+
+impl IntegritySettings {
+    /// Merge settings, the stricter mode is used so user settings can't weaken the ones of the system.
+    #[must_use]
+    pub fn merge(self, overrides: IntegritySettings) -> IntegritySettings {
+        IntegritySettings {
+            mode: overrides.mode.max(self.mode),
+            trusted_keys: concat(overrides.trusted_keys, self.trusted_keys),
+        }
+    }
+}
+
+// settings securing the machine can't be changed by users, so they are only read from the system settings
+fn remove_system_settings(settings: &mut DscSettings, system: &DscSettings, source: &str) {
+    if settings.integrity.as_mut().and_then(|integrity| integrity.trusted_keys.take()).is_some() {
+        warn!("Ignoring `integrity.trustedKeys` of '{source}', trusted keys are only read from the system settings");
+    }
+    // the audit log of the system records the changes of all users, so it can't be moved, disabled or shrunk
+    let system_audit_path = system.audit.as_ref().and_then(|audit| audit.path.as_ref());
+    if system_audit_path.is_some_and(|path| !path.is_empty()) && settings.audit.take().is_some() {
+        warn!("Ignoring `audit` of '{source}', the audit log is configured by the system settings");
+    }
+}
+
+fn concat(first: Option<Vec<String>>, second: Option<Vec<String>>) -> Option<Vec<String>> {
+    match (first, second) {
+        (Some(mut first), Some(second)) => {
+            first.extend(second);
+            Some(first)
+        },
+        (first, second) => first.or(second),
+    }
+}
+
+/// Get the directory of the machine-level settings, `%ProgramData%\dsc` on Windows and `/etc/dsc` otherwise.
+#[must_use]
+pub fn get_system_settings_directory() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env::var_os("ProgramData").map(|program_data| PathBuf::from(program_data).join("dsc"))
+    } else {
+        Some(PathBuf::from("/etc/dsc"))
+    }
+}
+
+/// Get the directory of the local state of the user, `%LOCALAPPDATA%\dsc` on Windows and
+/// `$XDG_STATE_HOME/dsc` or `~/.local/state/dsc` otherwise.
+#[must_use]
+pub fn get_user_state_directory() -> Option<PathBuf> {
+    let directory = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if let Some(state_home) = env::var_os("XDG_STATE_HOME").filter(|value| !value.is_empty()) {
+        Some(PathBuf::from(state_home))
+    } else {
+        env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("state"))
+    };
+    directory.map(|directory| directory.join("dsc"))
+}
+
+/// Get the paths of the system and user settings files, in order of precedence from lowest to highest.
+///
+/// On Windows these are `%ProgramData%\dsc` and `%APPDATA%\dsc`, otherwise `/etc/dsc`
+/// and `$XDG_CONFIG_HOME/dsc` or `~/.config/dsc`.
+#[must_use]
+pub fn get_settings_paths() -> Vec<PathBuf> {
+    let mut paths = get_system_settings_directory().into_iter().map(|directory| directory.join(SETTINGS_FILE_NAME)).collect::<Vec<_>>();
+    let user_directory = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if let Some(config_home) = env::var_os("XDG_CONFIG_HOME").filter(|value| !value.is_empty()) {
+        Some(PathBuf::from(config_home))
+    } else {
+        env::var_os("HOME").map(|home| Path::new(&home).join(".config"))
+    };
+    paths.extend(user_directory.map(|directory| directory.join("dsc").join(SETTINGS_FILE_NAME)));
+    paths
+}
+
+/// Get the settings from the system and user settings files and the `DSC_SETTINGS` environment variable.
+///
+/// # Errors
+///
+/// Error returned if a settings file or the environment variable isn't valid.
+pub fn get_settings() -> Result<DscSettings, DscError> {
+    let mut settings = DscSettings::default();
+    let system_directory = get_system_settings_directory();
+    for path in get_settings_paths() {
+        if !path.is_file() {
+            trace!("Settings file {path:?} not found");
+            continue;
+        }
+        debug!("Reading settings file {path:?}");
+        let content = fs::read_to_string(&path).map_err(|err| DscError::Settings(path.to_string_lossy().to_string(), err.to_string()))?;
+        let mut file_settings: DscSettings = serde_json::from_str(&content).map_err(|err| DscError::Settings(path.to_string_lossy().to_string(), err.to_string()))?;
+        // relative paths of a settings file are relative to its directory
+        if let Some(directory) = path.parent() {
+            if let Some(search_paths) = file_settings.discovery.as_mut().and_then(|discovery| discovery.search_paths.as_mut()) {
+                for search_path in search_paths.iter_mut() {
+                    *search_path = directory.join(&*search_path).to_string_lossy().to_string();
+                }
+            }
+            if let Some(audit_path) = file_settings.audit.as_mut().and_then(|audit| audit.path.as_mut()) {
+                *audit_path = directory.join(&*audit_path).to_string_lossy().to_string();
+            }
+            if let Some(checkpoint_path) = file_settings.checkpoint.as_mut().and_then(|checkpoint| checkpoint.path.as_mut()) {
+                *checkpoint_path = directory.join(&*checkpoint_path).to_string_lossy().to_string();
+            }
+            if let Some(history_path) = file_settings.history.as_mut().and_then(|history| history.path.as_mut()) {
+                *history_path = directory.join(&*history_path).to_string_lossy().to_string();
+            }
+            if let Some(agent) = file_settings.agent.as_mut() {
+                for agent_path in [&mut agent.document_path, &mut agent.parameters_path, &mut agent.state_path].into_iter().flatten() {
+                    *agent_path = directory.join(&*agent_path).to_string_lossy().to_string();
+                }
+            }
+        }
+        if path.parent() != system_directory.as_deref() {
+            remove_system_settings(&mut file_settings, &settings, &path.to_string_lossy());
+        }
+        settings = settings.merge(file_settings);
+    }
+
+    if let Ok(value) = env::var(SETTINGS_ENV_VAR) {
+        debug!("Using settings from {SETTINGS_ENV_VAR}: {value}");
+        let mut env_settings: DscSettings = serde_json::from_str(&value).map_err(|err| DscError::Settings(SETTINGS_ENV_VAR.to_string(), err.to_string()))?;
+        remove_system_settings(&mut env_settings, &settings, SETTINGS_ENV_VAR);
+        settings = settings.merge(env_settings);
+    }
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_prefers_overrides_and_concatenates_lists() {
+        let settings = DiscoverySettings {
+            search_paths: Some(vec!["system".to_string()]),
+            recurse: Some(false),
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let overrides = DiscoverySettings {
+            search_paths: Some(vec!["user".to_string()]),
+            recurse: Some(true),
+            exclude: Some(vec!["*.bak".to_string()]),
+            ..Default::default()
+        };
+        let merged = settings.merge(overrides);
+        assert_eq!(merged.search_paths, Some(vec!["user".to_string(), "system".to_string()]));
+        assert_eq!(merged.recurse, Some(true));
+        assert_eq!(merged.max_depth, Some(2));
+        assert_eq!(merged.exclude, Some(vec!["*.bak".to_string()]));
+        assert_eq!(merged.scan_path, None);
+    }
+
+    #[test]
+    fn merge_keeps_stricter_integrity_mode() {
+        let settings = IntegritySettings { mode: Some(IntegrityMode::Enforce), trusted_keys: Some(vec!["system".to_string()]) };
+        let overrides = IntegritySettings { mode: Some(IntegrityMode::Off), trusted_keys: Some(vec!["user".to_string()]) };
+        let merged = settings.merge(overrides);
+        assert_eq!(merged.mode, Some(IntegrityMode::Enforce));
+        assert_eq!(merged.trusted_keys, Some(vec!["user".to_string(), "system".to_string()]));
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert!(serde_json::from_str::<DscSettings>(r#"{"discovery": {"recursive": true}}"#).is_err());
+    }
+
+    #[test]
+    fn trusted_keys_are_only_read_from_system_settings() {
+        let mut settings: DscSettings = serde_json::from_str(r#"{"integrity": {"mode": "enforce", "trustedKeys": ["user"]}}"#).unwrap();
+        remove_system_settings(&mut settings, &DscSettings::default(), "test");
+        assert_eq!(settings.integrity, Some(IntegritySettings { mode: Some(IntegrityMode::Enforce), trusted_keys: None }));
+    }
+
+    #[test]
+    fn audit_log_of_system_settings_is_kept() {
+        let user = r#"{"audit": {"path": "", "maxFiles": 1}}"#;
+        let mut settings: DscSettings = serde_json::from_str(user).unwrap();
+        remove_system_settings(&mut settings, &DscSettings::default(), "test");
+        assert_eq!(settings.audit, Some(AuditSettings { path: Some(String::new()), max_size: None, max_files: Some(1) }));
+
+        let system: DscSettings = serde_json::from_str(r#"{"audit": {"path": "/var/log/dsc/audit.jsonl"}}"#).unwrap();
+        let mut settings: DscSettings = serde_json::from_str(user).unwrap();
+        remove_system_settings(&mut settings, &system, "test");
+        assert_eq!(settings.audit, None);
+    }
+}
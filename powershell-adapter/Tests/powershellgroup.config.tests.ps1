@@ -33,6 +33,73 @@ Describe 'PowerShell adapter resource tests' {
         $res.results[0].result.actualState.result[0].properties.EnumProp | Should -BeExactly 'Expected'
     }
     
+    It 'Get works on config with adapted resource referenced directly' {
+
+        $yaml = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: Class-resource Info
+              type: TestClassResource/TestClassResource
+              properties:
+                Name: 'TestClassResource1'
+'@
+        $r = $yaml | dsc config get
+        $LASTEXITCODE | Should -Be 0
+        $res = $r | ConvertFrom-Json
+        $res.results[0].type | Should -BeExactly 'TestClassResource/TestClassResource'
+        $res.results[0].result.actualState.Prop1 | Should -BeExactly 'ValueForProp1'
+    }
+
+    It 'Get sends consecutive adapted resources in one batch' {
+
+        $yaml = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: First
+              type: TestClassResource/TestClassResource
+              properties:
+                Name: 'TestClassResource1'
+            - name: Second
+              type: TestClassResource/TestClassResource
+              properties:
+                Name: 'TestClassResource1'
+'@
+        $r = $yaml | dsc -l trace config get 2> $TestDrive/tracing.txt
+        $LASTEXITCODE | Should -Be 0
+        $res = $r | ConvertFrom-Json
+        $res.results.Count | Should -Be 2
+        $res.results[0].name | Should -BeExactly 'First'
+        $res.results[1].name | Should -BeExactly 'Second'
+        $res.results[1].result.actualState.Prop1 | Should -BeExactly 'ValueForProp1'
+        "$TestDrive/tracing.txt" | Should -FileContentMatchExactly 'Invoking get for 2 instances'
+    }
+
+    It 'Set sends consecutive adapted resources in one batch' {
+
+        $yaml = @'
+            $schema: https://raw.githubusercontent.com/PowerShell/DSC/main/schemas/2024/04/config/document.json
+            resources:
+            - name: First
+              type: TestClassResource/TestClassResource
+              properties:
+                Name: 'TestClassResource1'
+                Prop1: 'ValueForProp1'
+            - name: Second
+              type: TestClassResource/TestClassResource
+              properties:
+                Name: 'TestClassResource1'
+                Prop1: 'ValueForProp1'
+'@
+        $r = $yaml | dsc -l trace config set 2> $TestDrive/tracing.txt
+        $LASTEXITCODE | Should -Be 0
+        $res = $r | ConvertFrom-Json
+        $res.results.Count | Should -Be 2
+        $res.results[0].name | Should -BeExactly 'First'
+        $res.results[1].name | Should -BeExactly 'Second'
+        $res.results[1].metadata.'Microsoft.DSC'.duration | Should -Not -BeNullOrEmpty
+        "$TestDrive/tracing.txt" | Should -FileContentMatchExactly 'Invoking set for 2 instances'
+    }
+
     It 'Test works on config with class-based resources' {
 
         $r = Get-Content -Raw $pwshConfigPath | dsc config test
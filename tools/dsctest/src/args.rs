@@ -5,10 +5,13 @@ use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Debug, Clone, PartialEq, Eq, ValueEnum)]
 pub enum Schemas {
+    Arguments,
     Delete,
     Echo,
     Exist,
     ExitCode,
+    Host,
+    RestartRequired,
     Sleep,
     Trace,
     WhatIf,
@@ -24,6 +27,18 @@ pub struct Args {
 
 #[derive(Debug, PartialEq, Eq, Subcommand)]
 pub enum SubCommand {
+    #[clap(name = "arguments", about = "Return the arguments as properties")]
+    Arguments {
+        #[clap(long, help = "The name to return")]
+        name: Option<String>,
+        #[clap(long, help = "The version to return")]
+        version: Option<String>,
+        #[clap(long, help = "Return force as true")]
+        force: bool,
+        #[clap(long, help = "The tags to return, can be specified multiple times")]
+        tags: Vec<String>,
+    },
+
     #[clap(name = "delete", about = "delete operation")]
     Delete {
         #[clap(name = "input", short, long, help = "The input to the delete command as JSON")]
@@ -48,6 +63,16 @@ pub enum SubCommand {
         input: String,
     },
 
+    #[clap(name = "host", about = "Handle operations as a persistent resource host")]
+    Host,
+
+    #[clap(name = "restart-required", about = "Return the input, requesting a restart on set")]
+    RestartRequired {
+        #[clap(name = "input", short, long, help = "The input to the restart required command as JSON")]
+        input: String,
+        #[clap(long, help = "Run as the set operation")]
+        set: bool,
+    },
     #[clap(name = "schema", about = "Get the JSON schema for a subcommand")]
     Schema {
         #[clap(name = "subcommand", short, long, help = "The subcommand to get the schema for")]
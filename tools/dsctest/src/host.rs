@@ -0,0 +1,94 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::HashMap, io::{self, BufRead, Write}};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Host {
+    pub output: String,
+    /// The number of seconds the host waits before responding.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sleep: Option<u64>,
+    /// The process id of the host that handled the operation.
+    #[serde(rename = "processId", skip_serializing_if = "Option::is_none")]
+    pub process_id: Option<u32>,
+    /// The value of `DSC_HOST_TEST` passed with the operation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+    /// The working directory passed with the operation.
+    #[serde(rename = "workingDirectory", skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
+    /// The message the host writes to stderr before failing the operation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    id: Option<u64>,
+    method: String,
+    params: Option<Params>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Params {
+    input: Value,
+    env: Option<HashMap<String, String>>,
+    #[serde(rename = "workingDirectory")]
+    working_directory: Option<String>,
+}
+
+/// Handle line-delimited JSON-RPC requests until shutdown or end of input.
+pub fn run() {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    for line in stdin.lock().lines().map_while(Result::ok) {
+        let request = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                eprintln!("{}", json!({"Error": format!("Invalid request: {err}")}));
+                continue;
+            }
+        };
+        eprintln!("{}", json!({"Trace": format!("Host received '{}'", request.method)}));
+        if request.method == "shutdown" {
+            break;
+        }
+        let Some(id) = request.id else {
+            continue;
+        };
+        let response = match handle(&request.method, request.params) {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}}),
+        };
+        writeln!(stdout, "{response}").unwrap();
+        stdout.flush().unwrap();
+    }
+}
+
+fn handle(method: &str, params: Option<Params>) -> Result<Value, (i32, String)> {
+    match method {
+        "get" | "set" | "test" => {
+            let Some(params) = params else {
+                return Err((1, "Missing params".to_string()));
+            };
+            let mut host = serde_json::from_value::<Host>(params.input).map_err(|err| (1, err.to_string()))?;
+            if let Some(error) = &host.error {
+                eprintln!("{error}");
+                return Err((3, "Operation failed".to_string()));
+            }
+            if let Some(sleep) = host.sleep {
+                std::thread::sleep(std::time::Duration::from_secs(sleep));
+            }
+            host.process_id = Some(std::process::id());
+            host.env = params.env.and_then(|mut env| env.remove("DSC_HOST_TEST"));
+            host.working_directory = params.working_directory;
+            Ok(json!([host]))
+        },
+        _ => Err((2, format!("Method '{method}' is not supported"))),
+    }
+}
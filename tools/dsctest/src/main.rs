@@ -2,10 +2,13 @@
 // Licensed under the MIT License.
 
 mod args;
+mod arguments;
 mod delete;
 mod echo;
 mod exist;
 mod exit_code;
+mod host;
+mod restart_required;
 mod sleep;
 mod trace;
 mod whatif;
@@ -13,10 +16,13 @@ mod whatif;
 use args::{Args, Schemas, SubCommand};
 use clap::Parser;
 use schemars::schema_for;
+use crate::arguments::Arguments;
 use crate::delete::Delete;
 use crate::echo::Echo;
 use crate::exist::{Exist, State};
 use crate::exit_code::ExitCode;
+use crate::host::Host;
+use crate::restart_required::RestartRequired;
 use crate::sleep::Sleep;
 use crate::trace::Trace;
 use crate::whatif::WhatIf;
@@ -26,6 +32,15 @@ use std::{thread, time::Duration};
 fn main() {
     let args = Args::parse();
     let json = match args.subcommand {
+        SubCommand::Arguments { name, version, force, tags } => {
+            let arguments = Arguments {
+                name,
+                version,
+                force: if force { Some(true) } else { None },
+                tags: if tags.is_empty() { None } else { Some(tags) },
+            };
+            serde_json::to_string(&arguments).unwrap()
+        },
         SubCommand::Delete { input } => {
             let mut delete = match serde_json::from_str::<Delete>(&input) {
                 Ok(delete) => delete,
@@ -72,13 +87,35 @@ fn main() {
                 }
             };
             if exit_code.exit_code != 0 {
+                // the input is still returned as the state for exit codes that aren't failures
+                println!("{input}");
                 eprintln!("Exiting with code: {}", exit_code.exit_code);
                 std::process::exit(exit_code.exit_code);
             }
             input
         },
+        SubCommand::Host => {
+            host::run();
+            return;
+        },
+        SubCommand::RestartRequired { input, set } => {
+            let mut restart_required = match serde_json::from_str::<RestartRequired>(&input) {
+                Ok(restart_required) => restart_required,
+                Err(err) => {
+                    eprintln!("Error JSON does not match schema: {err}");
+                    std::process::exit(1);
+                }
+            };
+            if !set {
+                restart_required.restart_required = None;
+            }
+            serde_json::to_string(&restart_required).unwrap()
+        },
         SubCommand::Schema { subcommand } => {
             let schema = match subcommand {
+                Schemas::Arguments => {
+                    schema_for!(Arguments)
+                },
                 Schemas::Delete => {
                     schema_for!(Delete)
                 },
@@ -91,6 +128,12 @@ fn main() {
                 Schemas::ExitCode => {
                     schema_for!(ExitCode)
                 },
+                Schemas::Host => {
+                    schema_for!(Host)
+                },
+                Schemas::RestartRequired => {
+                    schema_for!(RestartRequired)
+                },
                 Schemas::Sleep => {
                     schema_for!(Sleep)
                 },
@@ -0,0 +1,14 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RestartRequired {
+    pub name: String,
+    /// Whether `set` requests a restart to complete, not returned by `get` and `test`.
+    #[serde(rename = "_restartRequired", skip_serializing_if = "Option::is_none")]
+    pub restart_required: Option<bool>,
+}
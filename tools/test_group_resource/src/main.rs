@@ -36,6 +36,8 @@ fn main() {
                     }),
                     ..Default::default()
                 }).unwrap()),
+                adapter: None,
+                security_context: None,
             };
             let resource2 = DscResource {
                 type_name: "Test/TestResource2".to_string(),
@@ -61,6 +63,8 @@ fn main() {
                     }),
                     ..Default::default()
                 }).unwrap()),
+                adapter: None,
+                security_context: None,
             };
             println!("{}", serde_json::to_string(&resource1).unwrap());
             println!("{}", serde_json::to_string(&resource2).unwrap());
@@ -79,6 +83,8 @@ fn main() {
                 properties: vec!["Property1".to_string(), "Property2".to_string()],
                 require_adapter: None,
                 manifest: None,
+                adapter: None,
+                security_context: None,
             };
             println!("{}", serde_json::to_string(&resource1).unwrap());
         }